@@ -1,7 +1,29 @@
 use anyhow::Result;
 use async_trait::async_trait;
+use std::time::Instant;
 use subd_types::Event;
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, watch};
+use tracing::{info, warn};
+
+mod receiver;
+mod subscription;
+mod supervisor;
+pub mod testing;
+pub use receiver::recv;
+pub use subscription::Subscription;
+use supervisor::OnceFactory;
+pub use supervisor::{
+    Backoff, HandlerFactory, HandlerHealth, HandlerStatus, HealthMonitor,
+    LoopReport, Registration, RestartPolicy, DEFAULT_SHUTDOWN_TIMEOUT,
+};
+
+#[doc(hidden)]
+pub mod __private {
+    pub use subd_types::Event;
+}
+
+/// How many events the shared bus holds before slow handlers start lagging.
+pub const BUS_CAPACITY: usize = 256;
 
 #[async_trait]
 pub trait EventHandler: Send {
@@ -10,40 +32,291 @@ pub trait EventHandler: Send {
         tx: broadcast::Sender<Event>,
         mut rx: broadcast::Receiver<Event>,
     ) -> Result<()>;
+
+    /// The events this handler cares about. Anything else never reaches its
+    /// receiver. Can be overridden per handler with `Registration::subscribe`.
+    fn subscription(&self) -> Subscription {
+        Subscription::all()
+    }
 }
 
 pub struct EventLoop {
-    handlers: Vec<Box<dyn EventHandler>>,
+    handlers: Vec<Registration>,
+    health: HealthMonitor,
+}
+
+impl Default for EventLoop {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl EventLoop {
     pub fn new() -> Self {
-        Self { handlers: vec![] }
+        Self {
+            handlers: vec![],
+            health: HealthMonitor::default(),
+        }
     }
 
-    pub fn push<T>(&mut self, handler: T)
+    /// Push a handler that only runs once. If it fails, the failure ends up in
+    /// the report, but the rest of the loop keeps going.
+    pub fn push<T>(&mut self, handler: T) -> &mut Registration
     where
         T: EventHandler + 'static,
     {
-        self.handlers.push(Box::new(handler));
+        let name = handler_name::<T>();
+        self.register(
+            name,
+            RestartPolicy::Never,
+            Box::new(OnceFactory::new(Box::new(handler))),
+        )
+    }
+
+    /// Push a handler that gets rebuilt from `factory` whenever `policy` says
+    /// it should be restarted.
+    ///
+    /// ```ignore
+    /// event_loop.supervise("obs", RestartPolicy::default(), || async {
+    ///     Ok(OBSMessageHandler {
+    ///         obs_client: create_obs_client().await?,
+    ///     })
+    /// });
+    /// ```
+    pub fn supervise<F>(
+        &mut self,
+        name: impl Into<String>,
+        policy: RestartPolicy,
+        factory: F,
+    ) -> &mut Registration
+    where
+        F: HandlerFactory + 'static,
+    {
+        self.register(name.into(), policy, Box::new(factory))
     }
 
-    pub async fn run(self) -> Result<()> {
-        let (base_tx, _) = broadcast::channel::<Event>(256);
+    fn register(
+        &mut self,
+        name: String,
+        policy: RestartPolicy,
+        factory: Box<dyn HandlerFactory>,
+    ) -> &mut Registration {
+        // Names are the keys for health, so make sure two handlers of the
+        // same type don't end up sharing one.
+        let mut unique = name.clone();
+        let mut count = 1;
+        while self.handlers.iter().any(|h| h.name == unique) {
+            count += 1;
+            unique = format!("{}#{}", name, count);
+        }
+
+        self.health.register(&unique);
+        self.handlers
+            .push(Registration::new(unique, policy, factory));
+        self.handlers.last_mut().unwrap()
+    }
+
+    /// Live health of every handler. Grab this before calling `run`.
+    pub fn health(&self) -> HealthMonitor {
+        self.health.clone()
+    }
+
+    /// Runs every handler until they have all stopped.
+    ///
+    /// Ctrl-C broadcasts `Event::Shutdown`, and so can any handler. From then
+    /// on nothing gets restarted, and each handler has until its
+    /// `shutdown_timeout` to return before it is aborted.
+    pub async fn run(self) -> Result<LoopReport> {
+        let started = Instant::now();
+        let (base_tx, _) = broadcast::channel::<Event>(BUS_CAPACITY);
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
+        let watcher = tokio::spawn(watch_for_shutdown(
+            base_tx.clone(),
+            base_tx.subscribe(),
+            shutdown_tx,
+        ));
 
         let mut channels = vec![];
         for handler in self.handlers {
-            let (tx, rx) = (base_tx.clone(), base_tx.subscribe());
-            channels.push(tokio::spawn(async move {
-                handler.handle(tx, rx).await.expect("pass")
-            }));
+            let (tx, health) = (base_tx.clone(), self.health.clone());
+            channels.push(tokio::spawn(handler.run(
+                tx,
+                health,
+                shutdown_rx.clone(),
+            )));
         }
 
         for c in channels {
             // Wait for all the channels to be done
             c.await?;
         }
+        watcher.abort();
+
+        let shutdown = *shutdown_rx.borrow();
+        Ok(LoopReport {
+            handlers: self.health.snapshot(),
+            shutdown,
+            uptime: started.elapsed(),
+        })
+    }
+}
+
+/// Flips `shutdown` once someone sends `Event::Shutdown`, or turns Ctrl-C into
+/// one.
+async fn watch_for_shutdown(
+    tx: broadcast::Sender<Event>,
+    mut rx: broadcast::Receiver<Event>,
+    shutdown: watch::Sender<bool>,
+) {
+    let requested = async {
+        loop {
+            match recv(&mut rx).await {
+                Ok(Event::Shutdown) | Err(_) => return,
+                Ok(_) => continue,
+            }
+        }
+    };
+
+    tokio::select! {
+        _ = requested => info!("shutdown requested"),
+        _ = ctrl_c() => {
+            info!("ctrl-c received, shutting down");
+            let _ = tx.send(Event::Shutdown);
+        }
+    }
+
+    let _ = shutdown.send(true);
+}
+
+async fn ctrl_c() {
+    if let Err(err) = tokio::signal::ctrl_c().await {
+        warn!(?err, "can't listen for ctrl-c");
+        std::future::pending::<()>().await;
+    }
+}
+
+fn handler_name<T>() -> String {
+    let name = std::any::type_name::<T>();
+    name.rsplit("::").next().unwrap_or(name).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use anyhow::bail;
+
+    use super::*;
+
+    struct Flaky {
+        runs: Arc<AtomicU32>,
+    }
+
+    #[async_trait]
+    impl EventHandler for Flaky {
+        async fn handle(
+            self: Box<Self>,
+            _: broadcast::Sender<Event>,
+            _: broadcast::Receiver<Event>,
+        ) -> Result<()> {
+            if self.runs.fetch_add(1, Ordering::SeqCst) < 2 {
+                bail!("obs went away");
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn restarts_failed_handlers() {
+        let runs = Arc::new(AtomicU32::new(0));
+
+        let mut event_loop = EventLoop::new();
+        let factory_runs = runs.clone();
+        event_loop.supervise(
+            "flaky",
+            RestartPolicy::OnFailure {
+                backoff: Backoff {
+                    initial: Duration::from_millis(1),
+                    max: Duration::from_millis(5),
+                    multiplier: 2,
+                },
+                max_restarts: None,
+            },
+            move || {
+                let runs = factory_runs.clone();
+                async move { Ok(Flaky { runs }) }
+            },
+        );
+
+        let report = event_loop.run().await.unwrap();
+        let flaky = &report.handlers[0];
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+        assert_eq!(flaky.restarts, 2);
+        assert_eq!(flaky.failures, 2);
+        assert_eq!(flaky.health, HandlerHealth::Stopped);
+    }
+
+    struct Stubborn;
+
+    #[async_trait]
+    impl EventHandler for Stubborn {
+        async fn handle(
+            self: Box<Self>,
+            _: broadcast::Sender<Event>,
+            mut rx: broadcast::Receiver<Event>,
+        ) -> Result<()> {
+            loop {
+                recv(&mut rx).await?;
+            }
+        }
+    }
+
+    struct Quitter;
+
+    #[async_trait]
+    impl EventHandler for Quitter {
+        async fn handle(
+            self: Box<Self>,
+            tx: broadcast::Sender<Event>,
+            mut rx: broadcast::Receiver<Event>,
+        ) -> Result<()> {
+            tx.send(Event::Shutdown)?;
+            loop {
+                if let Event::Shutdown = recv(&mut rx).await? {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn shutdown_aborts_stragglers() {
+        let mut event_loop = EventLoop::new();
+        event_loop
+            .push(Stubborn)
+            .shutdown_timeout(Duration::from_millis(10));
+        event_loop.push(Quitter);
+
+        let report = event_loop.run().await.unwrap();
+        assert!(report.shutdown);
+        assert_eq!(report.aborted().count(), 1);
+
+        let quitter = report.handlers.iter().find(|h| h.name == "Quitter");
+        assert_eq!(quitter.unwrap().health, HandlerHealth::Stopped);
+    }
+
+    #[tokio::test]
+    async fn pushed_handlers_are_not_restarted() {
+        let runs = Arc::new(AtomicU32::new(0));
+
+        let mut event_loop = EventLoop::new();
+        event_loop.push(Flaky { runs: runs.clone() });
 
-        Ok(())
+        let report = event_loop.run().await.unwrap();
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert_eq!(report.failed().count(), 1);
     }
 }
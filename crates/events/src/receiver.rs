@@ -0,0 +1,105 @@
+use anyhow::Result;
+use subd_types::Event;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::warn;
+
+use crate::{HealthMonitor, Subscription};
+
+/// Which handler the current task is running, so `recv` knows who to blame
+/// for lagging.
+#[derive(Clone)]
+pub(crate) struct HandlerContext {
+    pub(crate) name: String,
+    pub(crate) health: HealthMonitor,
+}
+
+tokio::task_local! {
+    pub(crate) static CURRENT_HANDLER: HandlerContext;
+}
+
+/// Receive the next event, riding out lag instead of failing.
+///
+/// The bus is a broadcast channel, so a slow handler (anything that blocks
+/// on audio, for example) can fall far enough behind that the oldest events
+/// get dropped. `broadcast::Receiver::recv` reports that as an error, which
+/// used to end the handler. Here we log how many events were skipped, count
+/// them against the handler, and keep going with the oldest event still
+/// available.
+///
+/// Only fails once every sender is gone and the bus is closed.
+pub async fn recv(rx: &mut broadcast::Receiver<Event>) -> Result<Event> {
+    loop {
+        match rx.recv().await {
+            Ok(event) => return Ok(event),
+            Err(RecvError::Lagged(skipped)) => record_lag(skipped),
+            Err(err @ RecvError::Closed) => return Err(err.into()),
+        }
+    }
+}
+
+fn record_lag(skipped: u64) {
+    let recorded = CURRENT_HANDLER.try_with(|ctx| {
+        warn!(handler = %ctx.name, skipped, "handler lagged, skipped events");
+        ctx.health.record_lag(&ctx.name, skipped);
+    });
+
+    if recorded.is_err() {
+        warn!(skipped, "receiver lagged, skipped events");
+    }
+}
+
+/// Gives a handler its own queue of `capacity` events, fed from the bus with
+/// only the events its subscription matches.
+///
+/// Forwarding never blocks, so the forwarder keeps up with the bus and the
+/// handler only lags once its own queue is full.
+pub(crate) fn spawn_queue(
+    mut bus: broadcast::Receiver<Event>,
+    capacity: usize,
+    subscription: Subscription,
+    ctx: HandlerContext,
+) -> (broadcast::Receiver<Event>, tokio::task::JoinHandle<()>) {
+    let (queue_tx, queue_rx) = broadcast::channel(capacity);
+
+    let forwarder = tokio::spawn(CURRENT_HANDLER.scope(ctx, async move {
+        while let Ok(event) = recv(&mut bus).await {
+            if !subscription.matches(&event) {
+                continue;
+            }
+
+            // Only fails if the handler's receiver is gone,
+            // and then the forwarder is aborted anyway.
+            let _ = queue_tx.send(event);
+        }
+    }));
+
+    (queue_rx, forwarder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn recv_skips_past_lag() {
+        let (tx, mut rx) = broadcast::channel(2);
+        for _ in 0..4 {
+            tx.send(Event::RequestTwitchSubCount).unwrap();
+        }
+        tx.send(Event::Shutdown).unwrap();
+
+        let health = HealthMonitor::default();
+        health.register("slow");
+        let ctx = HandlerContext {
+            name: "slow".to_string(),
+            health: health.clone(),
+        };
+
+        let event = CURRENT_HANDLER
+            .scope(ctx, async { recv(&mut rx).await.unwrap() })
+            .await;
+
+        assert!(matches!(event, Event::RequestTwitchSubCount));
+        assert_eq!(health.get("slow").unwrap().lagged, 3);
+    }
+}
@@ -0,0 +1,126 @@
+use std::fmt::{self, Debug};
+use std::sync::Arc;
+
+use subd_types::Event;
+
+type Filter = Arc<dyn Fn(&Event) -> bool + Send + Sync>;
+
+/// Which events a handler wants to see.
+///
+/// Handlers with a subscription get their own queue and only ever receive the
+/// events it matches, so a TTS handler doesn't wake up for every chat message
+/// just to `continue` past it. `Event::Shutdown` always gets through, so every
+/// handler gets the chance to wrap up.
+///
+/// ```ignore
+/// event_loop.push(UberDuckHandler { .. }).subscribe(only!(UberDuckRequest));
+/// ```
+#[derive(Clone)]
+pub struct Subscription {
+    variants: Vec<&'static str>,
+    filter: Option<Filter>,
+}
+
+impl Subscription {
+    /// Every event on the bus. This is the default.
+    pub fn all() -> Self {
+        Self {
+            variants: vec![],
+            filter: None,
+        }
+    }
+
+    /// Only events matching `filter`. `variants` is just used to describe the
+    /// subscription, prefer the `only!` macro which fills it in for you.
+    pub fn new<F>(variants: &[&'static str], filter: F) -> Self
+    where
+        F: Fn(&Event) -> bool + Send + Sync + 'static,
+    {
+        Self {
+            variants: variants.to_vec(),
+            filter: Some(Arc::new(filter)),
+        }
+    }
+
+    pub fn is_all(&self) -> bool {
+        self.filter.is_none()
+    }
+
+    pub fn matches(&self, event: &Event) -> bool {
+        if matches!(event, Event::Shutdown) {
+            return true;
+        }
+
+        match &self.filter {
+            Some(filter) => filter(event),
+            None => true,
+        }
+    }
+
+    /// Names of the `Event` variants this subscription was built from.
+    pub fn variants(&self) -> &[&'static str] {
+        &self.variants
+    }
+}
+
+impl Default for Subscription {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+impl Debug for Subscription {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_all() {
+            write!(f, "Subscription(all)")
+        } else if self.variants.is_empty() {
+            write!(f, "Subscription(filter)")
+        } else {
+            write!(f, "Subscription({})", self.variants.join(" | "))
+        }
+    }
+}
+
+/// Build a `Subscription` for a set of `Event` variants.
+///
+/// ```ignore
+/// events::only!(UberDuckRequest)
+/// events::only!(UserMessage, TwitchChatMessage)
+/// ```
+#[macro_export]
+macro_rules! only {
+    ($($variant:ident),+ $(,)?) => {
+        $crate::Subscription::new(
+            &[$(stringify!($variant)),+],
+            |event: &$crate::__private::Event| {
+                matches!(event, $($crate::__private::Event::$variant { .. })|+)
+            },
+        )
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_matches_listed_variants() {
+        let sub = crate::only!(RequestTwitchSubCount, RequestTwitchMessage);
+
+        assert!(sub.matches(&Event::RequestTwitchSubCount));
+        assert!(sub.matches(&Event::RequestTwitchMessage("hi".to_string())));
+        assert!(!sub.matches(&Event::ObsSetScene {
+            scene: "memes".to_string()
+        }));
+        assert_eq!(
+            sub.variants(),
+            ["RequestTwitchSubCount", "RequestTwitchMessage"]
+        );
+    }
+
+    #[test]
+    fn shutdown_always_matches() {
+        let sub = crate::only!(RequestTwitchSubCount);
+        assert!(sub.matches(&Event::Shutdown));
+    }
+}
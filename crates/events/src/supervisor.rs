@@ -0,0 +1,542 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use subd_types::Event;
+use tokio::sync::{broadcast, watch};
+use tracing::{error, info, warn};
+
+use crate::receiver::{self, HandlerContext, CURRENT_HANDLER};
+use crate::{EventHandler, Subscription, BUS_CAPACITY};
+
+/// Builds a fresh handler every time the supervisor (re)starts it.
+///
+/// Handlers consume themselves in `handle`, so the only way to restart one is
+/// to make a new one. Any `Fn() -> impl Future<Output = Result<H>>` closure is
+/// a factory, which makes it easy to reconnect to OBS or grab a new pool
+/// connection on every restart.
+#[async_trait]
+pub trait HandlerFactory: Send + Sync {
+    async fn build(&self) -> Result<Box<dyn EventHandler>>;
+}
+
+#[async_trait]
+impl<F, Fut, H> HandlerFactory for F
+where
+    F: Fn() -> Fut + Send + Sync,
+    Fut: Future<Output = Result<H>> + Send,
+    H: EventHandler + 'static,
+{
+    async fn build(&self) -> Result<Box<dyn EventHandler>> {
+        Ok(Box::new(self().await?))
+    }
+}
+
+/// Factory for handlers pushed without one. It can only hand out the handler
+/// once, so these can never be restarted.
+pub(crate) struct OnceFactory(Mutex<Option<Box<dyn EventHandler>>>);
+
+impl OnceFactory {
+    pub(crate) fn new(handler: Box<dyn EventHandler>) -> Self {
+        Self(Mutex::new(Some(handler)))
+    }
+}
+
+#[async_trait]
+impl HandlerFactory for OnceFactory {
+    async fn build(&self) -> Result<Box<dyn EventHandler>> {
+        self.0
+            .lock()
+            .unwrap()
+            .take()
+            .ok_or_else(|| anyhow!("handler was pushed without a factory"))
+    }
+}
+
+/// Exponential backoff between restarts.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+    pub multiplier: u32,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_millis(500),
+            max: Duration::from_secs(60),
+            multiplier: 2,
+        }
+    }
+}
+
+impl Backoff {
+    /// How long to wait before the `attempt`th retry (starting at 0).
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = self.multiplier.saturating_pow(attempt);
+        self.initial.saturating_mul(factor).min(self.max)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum RestartPolicy {
+    /// Run the handler once. Errors and panics are recorded, never retried.
+    Never,
+
+    /// Restart the handler when it returns an error or panics.
+    OnFailure {
+        backoff: Backoff,
+        max_restarts: Option<u32>,
+    },
+
+    /// Restart the handler whenever it stops, even if it returned Ok.
+    Always {
+        backoff: Backoff,
+        max_restarts: Option<u32>,
+    },
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        RestartPolicy::OnFailure {
+            backoff: Backoff::default(),
+            max_restarts: None,
+        }
+    }
+}
+
+impl RestartPolicy {
+    fn should_restart(&self, failed: bool, restarts: u32) -> Option<&Backoff> {
+        let (backoff, max_restarts) = match self {
+            RestartPolicy::Never => return None,
+            RestartPolicy::OnFailure { .. } if !failed => return None,
+            RestartPolicy::OnFailure {
+                backoff,
+                max_restarts,
+            }
+            | RestartPolicy::Always {
+                backoff,
+                max_restarts,
+            } => (backoff, max_restarts),
+        };
+
+        match max_restarts {
+            Some(max) if restarts >= *max => None,
+            _ => Some(backoff),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HandlerHealth {
+    Starting,
+    Running,
+    Restarting {
+        attempt: u32,
+    },
+    /// The handler finished without an error and won't be restarted.
+    Stopped,
+    /// The handler failed and the policy gave up on it.
+    Failed,
+    /// The handler didn't finish before its shutdown deadline and was
+    /// aborted.
+    Aborted,
+}
+
+impl Display for HandlerHealth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HandlerHealth::Starting => write!(f, "starting"),
+            HandlerHealth::Running => write!(f, "running"),
+            HandlerHealth::Restarting { attempt } => {
+                write!(f, "restarting (attempt {})", attempt)
+            }
+            HandlerHealth::Stopped => write!(f, "stopped"),
+            HandlerHealth::Failed => write!(f, "failed"),
+            HandlerHealth::Aborted => write!(f, "aborted"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HandlerStatus {
+    pub name: String,
+    pub health: HandlerHealth,
+    pub restarts: u32,
+    pub failures: u32,
+    /// How many events were dropped because the handler fell behind.
+    pub lagged: u64,
+    pub last_error: Option<String>,
+}
+
+impl HandlerStatus {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            health: HandlerHealth::Starting,
+            restarts: 0,
+            failures: 0,
+            lagged: 0,
+            last_error: None,
+        }
+    }
+}
+
+/// Shared view of how every handler in the loop is doing. Cheap to clone, so
+/// it can be handed to anything that wants to report on it while the loop runs.
+#[derive(Debug, Clone, Default)]
+pub struct HealthMonitor {
+    statuses: Arc<Mutex<BTreeMap<String, HandlerStatus>>>,
+}
+
+impl HealthMonitor {
+    pub fn snapshot(&self) -> Vec<HandlerStatus> {
+        self.statuses.lock().unwrap().values().cloned().collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<HandlerStatus> {
+        self.statuses.lock().unwrap().get(name).cloned()
+    }
+
+    pub(crate) fn register(&self, name: &str) {
+        self.statuses
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), HandlerStatus::new(name));
+    }
+
+    pub(crate) fn record_lag(&self, name: &str, skipped: u64) {
+        self.update(name, |s| s.lagged += skipped);
+    }
+
+    fn update(&self, name: &str, f: impl FnOnce(&mut HandlerStatus)) {
+        let mut statuses = self.statuses.lock().unwrap();
+        let status = statuses
+            .entry(name.to_string())
+            .or_insert_with(|| HandlerStatus::new(name));
+        f(status);
+    }
+}
+
+/// Summary of every handler, returned once the loop has finished.
+#[derive(Debug, Clone)]
+pub struct LoopReport {
+    pub handlers: Vec<HandlerStatus>,
+    /// Whether the loop ended because of an `Event::Shutdown`, rather than
+    /// every handler stopping on its own.
+    pub shutdown: bool,
+    pub uptime: Duration,
+}
+
+impl LoopReport {
+    pub fn failed(&self) -> impl Iterator<Item = &HandlerStatus> {
+        self.handlers
+            .iter()
+            .filter(|h| h.health == HandlerHealth::Failed)
+    }
+
+    pub fn aborted(&self) -> impl Iterator<Item = &HandlerStatus> {
+        self.handlers
+            .iter()
+            .filter(|h| h.health == HandlerHealth::Aborted)
+    }
+}
+
+impl Display for LoopReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = if self.shutdown {
+            "shut down"
+        } else {
+            "all handlers stopped"
+        };
+        writeln!(
+            f,
+            "Event loop report ({} after {}s):",
+            reason,
+            self.uptime.as_secs()
+        )?;
+        for handler in &self.handlers {
+            write!(
+                f,
+                "  {}: {} (restarts: {}, failures: {}, lagged: {})",
+                handler.name,
+                handler.health,
+                handler.restarts,
+                handler.failures,
+                handler.lagged
+            )?;
+            if let Some(err) = &handler.last_error {
+                write!(f, " last error: {}", err)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// How long a handler gets to wrap up after `Event::Shutdown` by default.
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A handler pushed onto the loop. Returned from `push` and `supervise` so
+/// extra options can be chained on.
+pub struct Registration {
+    pub(crate) name: String,
+    pub(crate) policy: RestartPolicy,
+    pub(crate) factory: Box<dyn HandlerFactory>,
+    pub(crate) queue: Option<usize>,
+    pub(crate) subscription: Option<Subscription>,
+    pub(crate) shutdown_timeout: Duration,
+}
+
+impl Registration {
+    pub(crate) fn new(
+        name: String,
+        policy: RestartPolicy,
+        factory: Box<dyn HandlerFactory>,
+    ) -> Self {
+        Self {
+            name,
+            policy,
+            factory,
+            queue: None,
+            subscription: None,
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+        }
+    }
+
+    /// Give this handler its own queue of `capacity` events instead of
+    /// reading straight off the shared bus. Useful for slow handlers that
+    /// would otherwise fall behind during busy parts of the stream.
+    pub fn with_queue(&mut self, capacity: usize) -> &mut Self {
+        self.queue = Some(capacity);
+        self
+    }
+
+    /// Only route events matching `subscription` to this handler, instead of
+    /// whatever the handler asks for itself.
+    pub fn subscribe(&mut self, subscription: Subscription) -> &mut Self {
+        self.subscription = Some(subscription);
+        self
+    }
+
+    /// How long this handler gets to finish up once `Event::Shutdown` goes
+    /// out, before it is aborted. Handlers should return from `handle` when
+    /// they see the event.
+    pub fn shutdown_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.shutdown_timeout = timeout;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Registration {
+    /// Keeps the handler running according to its policy. Only returns once
+    /// the policy says we are done with it, or the loop is shutting down.
+    pub(crate) async fn run(
+        self,
+        tx: broadcast::Sender<Event>,
+        health: HealthMonitor,
+        mut shutdown: watch::Receiver<bool>,
+    ) {
+        let name = self.name;
+        let mut restarts = 0;
+        let mut attempt = 0;
+
+        loop {
+            if *shutdown.borrow() {
+                health.update(&name, |s| s.health = HandlerHealth::Stopped);
+                return;
+            }
+
+            health.update(&name, |s| s.health = HandlerHealth::Starting);
+
+            let started = Instant::now();
+            let result = match self.factory.build().await {
+                Ok(handler) => {
+                    health.update(&name, |s| s.health = HandlerHealth::Running);
+                    info!(handler = %name, "handler started");
+
+                    let ctx = HandlerContext {
+                        name: name.clone(),
+                        health: health.clone(),
+                    };
+
+                    let subscription = self
+                        .subscription
+                        .clone()
+                        .unwrap_or_else(|| handler.subscription());
+
+                    // Handlers that only want some events, or asked for more
+                    // room, get their own queue. Everyone else reads the bus.
+                    let (rx, forwarder) =
+                        if self.queue.is_some() || !subscription.is_all() {
+                            let (rx, forwarder) = receiver::spawn_queue(
+                                tx.subscribe(),
+                                self.queue.unwrap_or(BUS_CAPACITY),
+                                subscription,
+                                ctx.clone(),
+                            );
+                            (rx, Some(forwarder))
+                        } else {
+                            (tx.subscribe(), None)
+                        };
+
+                    let handle = handler.handle(tx.clone(), rx);
+                    let mut task =
+                        tokio::spawn(CURRENT_HANDLER.scope(ctx, handle));
+
+                    // Once shutdown starts, the handler has until its
+                    // deadline to return before we pull the plug.
+                    let timeout = self.shutdown_timeout;
+                    let result = tokio::select! {
+                        result = &mut task => result,
+                        _ = shutdown_deadline(&mut shutdown, timeout) => {
+                            warn!(handler = %name, ?timeout, "handler missed shutdown deadline, aborting");
+                            task.abort();
+                            health.update(&name, |s| s.health = HandlerHealth::Aborted);
+                            if let Some(forwarder) = forwarder {
+                                forwarder.abort();
+                            }
+                            return;
+                        }
+                    };
+
+                    if let Some(forwarder) = forwarder {
+                        forwarder.abort();
+                    }
+
+                    match result {
+                        Ok(Ok(())) => Ok(()),
+                        Ok(Err(err)) => Err(format!("{:?}", err)),
+                        Err(err) => Err(panic_message(err)),
+                    }
+                }
+                Err(err) => Err(format!("failed to build: {:?}", err)),
+            };
+
+            let failed = result.is_err();
+            if let Err(err) = &result {
+                error!(handler = %name, error = %err, "handler failed");
+                health.update(&name, |s| {
+                    s.failures += 1;
+                    s.last_error = Some(err.clone());
+                });
+            }
+
+            let backoff = match self.policy.should_restart(failed, restarts) {
+                Some(backoff) if !*shutdown.borrow() => backoff,
+                _ => {
+                    let final_health = if failed {
+                        HandlerHealth::Failed
+                    } else {
+                        HandlerHealth::Stopped
+                    };
+                    info!(handler = %name, health = %final_health, "handler finished");
+                    health.update(&name, |s| s.health = final_health);
+                    return;
+                }
+            };
+
+            // A handler that ran for a good while before failing shouldn't
+            // have to wait out the backoff of some crash loop an hour ago.
+            if started.elapsed() > backoff.max {
+                attempt = 0;
+            }
+
+            let delay = backoff.delay(attempt);
+            attempt += 1;
+            restarts += 1;
+
+            warn!(handler = %name, ?delay, restarts, "restarting handler");
+            health.update(&name, |s| {
+                s.health = HandlerHealth::Restarting { attempt };
+                s.restarts = restarts;
+            });
+
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = shutting_down(&mut shutdown) => {}
+            }
+        }
+    }
+}
+
+/// Resolves once the loop starts shutting down. Never resolves if the loop
+/// is gone without ever shutting down.
+async fn shutting_down(shutdown: &mut watch::Receiver<bool>) {
+    while !*shutdown.borrow() {
+        if shutdown.changed().await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+async fn shutdown_deadline(
+    shutdown: &mut watch::Receiver<bool>,
+    timeout: Duration,
+) {
+    shutting_down(shutdown).await;
+    tokio::time::sleep(timeout).await;
+}
+
+fn panic_message(err: tokio::task::JoinError) -> String {
+    if !err.is_panic() {
+        return format!("{:?}", err);
+    }
+
+    let panic = err.into_panic();
+    if let Some(msg) = panic.downcast_ref::<&str>() {
+        format!("panicked: {}", msg)
+    } else if let Some(msg) = panic.downcast_ref::<String>() {
+        format!("panicked: {}", msg)
+    } else {
+        "panicked".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_grows_and_caps() {
+        let backoff = Backoff {
+            initial: Duration::from_millis(100),
+            max: Duration::from_secs(1),
+            multiplier: 2,
+        };
+
+        assert_eq!(backoff.delay(0), Duration::from_millis(100));
+        assert_eq!(backoff.delay(1), Duration::from_millis(200));
+        assert_eq!(backoff.delay(3), Duration::from_millis(800));
+        assert_eq!(backoff.delay(4), Duration::from_secs(1));
+        assert_eq!(backoff.delay(100), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn policies_decide_restarts() {
+        let on_failure = RestartPolicy::OnFailure {
+            backoff: Backoff::default(),
+            max_restarts: Some(2),
+        };
+        assert!(on_failure.should_restart(true, 0).is_some());
+        assert!(on_failure.should_restart(false, 0).is_none());
+        assert!(on_failure.should_restart(true, 2).is_none());
+
+        let always = RestartPolicy::Always {
+            backoff: Backoff::default(),
+            max_restarts: None,
+        };
+        assert!(always.should_restart(false, 1000).is_some());
+
+        assert!(RestartPolicy::Never.should_restart(true, 0).is_none());
+    }
+}
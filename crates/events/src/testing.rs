@@ -0,0 +1,79 @@
+//! Helpers for testing handlers without running a whole `EventLoop`.
+
+use anyhow::Result;
+use subd_types::Event;
+use tokio::sync::broadcast;
+
+use crate::{EventHandler, BUS_CAPACITY};
+
+/// Feed `events` to `handler`, followed by `Event::Shutdown`, and wait for it
+/// to finish. Returns every event the handler sent on the bus while it ran.
+///
+/// Handlers need to return on `Event::Shutdown` for this to ever finish.
+pub async fn run_handler<H>(
+    handler: H,
+    events: Vec<Event>,
+) -> Result<Vec<Event>>
+where
+    H: EventHandler + 'static,
+{
+    let (tx, rx) = broadcast::channel(BUS_CAPACITY);
+    let mut bus = tx.subscribe();
+
+    // Everything goes out before the handler starts, so the first events on
+    // the bus are always ours and the rest came from the handler.
+    let sent = events.len() + 1;
+    for event in events {
+        tx.send(event)?;
+    }
+    tx.send(Event::Shutdown)?;
+
+    Box::new(handler).handle(tx.clone(), rx).await?;
+
+    let mut seen = vec![];
+    while let Ok(event) = bus.try_recv() {
+        seen.push(event);
+    }
+    Ok(seen.split_off(sent.min(seen.len())))
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate::recv;
+
+    struct Echo;
+
+    #[async_trait]
+    impl EventHandler for Echo {
+        async fn handle(
+            self: Box<Self>,
+            tx: broadcast::Sender<Event>,
+            mut rx: broadcast::Receiver<Event>,
+        ) -> Result<()> {
+            loop {
+                match recv(&mut rx).await? {
+                    Event::RequestTwitchSubCount => {
+                        tx.send(Event::RequestTwitchMessage("hi".into()))?;
+                    }
+                    Event::Shutdown => return Ok(()),
+                    _ => continue,
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn collects_what_the_handler_sent() {
+        let sent = run_handler(Echo, vec![Event::RequestTwitchSubCount])
+            .await
+            .unwrap();
+
+        assert_eq!(sent.len(), 1);
+        assert!(
+            matches!(&sent[0], Event::RequestTwitchMessage(msg) if msg == "hi")
+        );
+    }
+}
@@ -87,6 +87,7 @@ pub fn database_model(_attr: TokenStream, tokens: TokenStream) -> TokenStream {
                 }
 
                 let mut new_field = f.clone();
+                new_field.attrs = vec![];
                 new_field.vis = Visibility::Public(VisPublic {
                     pub_token: syn::token::Pub {
                         span: Span::call_site().into(),
@@ -134,6 +135,28 @@ pub fn database_model(_attr: TokenStream, tokens: TokenStream) -> TokenStream {
         })
         .collect::<Punctuated<Ident, Token![,]>>();
 
+    // Fields marked #[type_override] are decoded as whatever type the field
+    // has, instead of what sqlx would pick for the column, like enums
+    let field_list = model
+        .fields
+        .iter()
+        .filter_map(|f| {
+            let ident = f.ident.as_ref()?;
+            let type_override = f.attrs.iter().any(|a| {
+                a.path
+                    .segments
+                    .iter()
+                    .any(|s| s.ident.to_string() == "type_override")
+            });
+
+            match type_override {
+                true => Some(format!("{0} AS \"{0}: _\"", ident)),
+                false => Some(ident.to_string()),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+
     // Remove attrs (todo, only immutable attrs)
     model.fields.iter_mut().for_each(|f| f.attrs = vec![]);
 
@@ -150,16 +173,6 @@ pub fn database_model(_attr: TokenStream, tokens: TokenStream) -> TokenStream {
         })
         .collect::<Vec<_>>();
 
-    let field_list = model
-        .fields
-        .iter()
-        .filter_map(|f| match &f.ident {
-            Some(ident) => Some(ident.to_string()),
-            None => None,
-        })
-        .collect::<Vec<_>>()
-        .join(", ");
-
     let read = match primary_key {
         Some(primary_key) => {
             let query = format!(
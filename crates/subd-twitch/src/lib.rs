@@ -1,5 +1,7 @@
+pub mod moderation;
 pub mod rewards;
 pub mod subscriptions;
+pub mod tokens;
 // TODO(generalize)
 // Teej
 // pub const MY_BROADCASTER_ID: &str = "114257969";
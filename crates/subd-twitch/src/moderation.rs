@@ -0,0 +1,168 @@
+//! Helix moderation: deleting messages, timeouts and warnings.
+//!
+//! These go straight to `SUBD_TWITCH_HELIX_URL`, since twitch_api2 doesn't
+//! know about warnings. The token's user is the moderator, so it has to be a
+//! mod in every channel it moderates.
+
+use anyhow::{bail, Result};
+use serde_json::json;
+use twitch_api2::twitch_oauth2::{AccessToken, TwitchToken, UserToken};
+
+pub struct HelixModeration {
+    client: reqwest::Client,
+    url: String,
+    client_id: String,
+    token: AccessToken,
+    moderator_id: String,
+}
+
+impl HelixModeration {
+    pub fn new(token: &UserToken) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: subd_types::consts::get_twitch_helix_url(),
+            client_id: token.client_id().as_str().to_string(),
+            token: token.token().clone(),
+            moderator_id: token.user_id.to_string(),
+        }
+    }
+
+    /// Send requests somewhere other than `SUBD_TWITCH_HELIX_URL`.
+    pub fn url(mut self, url: impl Into<String>) -> Self {
+        self.url = url.into();
+        self
+    }
+
+    /// The Twitch user id of `login`, if there is such a user.
+    pub async fn user_id(&self, login: &str) -> Result<Option<String>> {
+        let response = self
+            .send(
+                "look up",
+                self.client
+                    .get(format!("{}/users", self.url))
+                    .query(&[("login", login)]),
+            )
+            .await?;
+
+        let body: serde_json::Value =
+            serde_json::from_str(&response.text().await?)?;
+        Ok(body["data"][0]["id"].as_str().map(|id| id.to_string()))
+    }
+
+    pub async fn delete_message(
+        &self,
+        broadcaster_id: &str,
+        message_id: &str,
+    ) -> Result<()> {
+        self.send(
+            "delete",
+            self.client
+                .delete(format!("{}/moderation/chat", self.url))
+                .query(&self.ids(broadcaster_id))
+                .query(&[("message_id", message_id)]),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn timeout(
+        &self,
+        broadcaster_id: &str,
+        user_id: &str,
+        seconds: u32,
+        reason: &str,
+    ) -> Result<()> {
+        let body = json!({
+            "data": {
+                "user_id": user_id,
+                "duration": seconds,
+                "reason": reason,
+            },
+        });
+
+        self.send(
+            "time out",
+            self.client
+                .post(format!("{}/moderation/bans", self.url))
+                .query(&self.ids(broadcaster_id))
+                .header("Content-Type", "application/json")
+                .body(body.to_string()),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Lifts a timeout or a ban.
+    pub async fn unban(
+        &self,
+        broadcaster_id: &str,
+        user_id: &str,
+    ) -> Result<()> {
+        self.send(
+            "unban",
+            self.client
+                .delete(format!("{}/moderation/bans", self.url))
+                .query(&self.ids(broadcaster_id))
+                .query(&[("user_id", user_id)]),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// The user has to acknowledge the warning before they can chat again.
+    pub async fn warn(
+        &self,
+        broadcaster_id: &str,
+        user_id: &str,
+        reason: &str,
+    ) -> Result<()> {
+        let body = json!({
+            "data": {
+                "user_id": user_id,
+                "reason": reason,
+            },
+        });
+
+        self.send(
+            "warn",
+            self.client
+                .post(format!("{}/moderation/warnings", self.url))
+                .query(&self.ids(broadcaster_id))
+                .header("Content-Type", "application/json")
+                .body(body.to_string()),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    fn ids<'a>(&'a self, broadcaster_id: &'a str) -> [(&'a str, &'a str); 2] {
+        [
+            ("broadcaster_id", broadcaster_id),
+            ("moderator_id", &self.moderator_id),
+        ]
+    }
+
+    async fn send(
+        &self,
+        what: &str,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response> {
+        let response = request
+            .header("Client-Id", &self.client_id)
+            .bearer_auth(self.token.secret())
+            .send()
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let error = response.text().await.unwrap_or_default();
+            bail!("can't {}: {} {}", what, status, error);
+        }
+
+        Ok(response)
+    }
+}
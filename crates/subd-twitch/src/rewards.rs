@@ -1,13 +1,133 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
 use twitch_api2::{
     helix::points::{
-        CreateCustomRewardBody, CreateCustomRewardRequest,
+        CreateCustomRewardBody, CreateCustomRewardRequest, CustomReward,
+        CustomRewardRedemptionStatus, GetCustomRewardRequest,
         UpdateCustomRewardBody, UpdateCustomRewardRequest,
+        UpdateRedemptionStatusBody, UpdateRedemptionStatusRequest,
     },
     twitch_oauth2::UserToken,
     HelixClient,
 };
 
+/// Where `RewardConfig`s are declared.
+pub const REWARDS_FILE: &str = "data/rewards.json";
+
+/// A channel point reward, as we want it to look on Twitch.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RewardConfig {
+    pub title: String,
+    pub cost: usize,
+
+    #[serde(default)]
+    pub prompt: String,
+
+    /// Ask the viewer to type something in when redeeming.
+    #[serde(default)]
+    pub user_input: bool,
+
+    /// Seconds before anyone can redeem it again.
+    #[serde(default)]
+    pub cooldown: Option<usize>,
+
+    /// Like "#9146FF". Left as it is on Twitch when not set.
+    #[serde(default)]
+    pub color: Option<String>,
+
+    /// OBS scenes and stream modes it is turned off in.
+    #[serde(default)]
+    pub disabled_in: Vec<String>,
+}
+
+impl RewardConfig {
+    /// Whether `existing` needs updating to look like this. Declared rewards
+    /// are turned back on, in case we disabled them once they went missing.
+    fn differs_from(&self, existing: &CustomReward) -> bool {
+        let cooldown = existing.global_cooldown_setting.is_enabled.then_some(
+            existing.global_cooldown_setting.global_cooldown_seconds as usize,
+        );
+
+        !existing.is_enabled
+            || self.cost != existing.cost
+            || self.prompt != existing.prompt
+            || self.user_input != existing.is_user_input_required
+            || self.cooldown != cooldown
+            || self.color.as_ref().is_some_and(|color| {
+                !color.eq_ignore_ascii_case(&existing.background_color)
+            })
+    }
+
+    /// Whether it should be redeemable while in `mode`.
+    pub fn enabled_in(&self, mode: &str) -> bool {
+        !self
+            .disabled_in
+            .iter()
+            .any(|disabled| disabled.eq_ignore_ascii_case(mode))
+    }
+}
+
+pub fn load_rewards(path: &str) -> Result<Vec<RewardConfig>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("can't read rewards from {}", path))?;
+    Ok(serde_json::from_str(&contents)?)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RewardChange {
+    Create(RewardConfig),
+    Update {
+        id: String,
+        reward: RewardConfig,
+    },
+    /// On Twitch but no longer declared.
+    Disable {
+        id: String,
+        title: String,
+    },
+}
+
+/// What has to change on Twitch for `existing` to match `declared`.
+/// Rewards are matched up by title.
+pub fn diff(
+    declared: &[RewardConfig],
+    existing: &[CustomReward],
+) -> Vec<RewardChange> {
+    let find = |title: &str| {
+        existing
+            .iter()
+            .find(|reward| reward.title.eq_ignore_ascii_case(title))
+    };
+
+    let mut changes = vec![];
+    for reward in declared {
+        match find(&reward.title) {
+            None => changes.push(RewardChange::Create(reward.clone())),
+            Some(current) if reward.differs_from(current) => {
+                changes.push(RewardChange::Update {
+                    id: current.id.to_string(),
+                    reward: reward.clone(),
+                })
+            }
+            Some(_) => {}
+        }
+    }
+
+    for current in existing {
+        let is_declared = declared
+            .iter()
+            .any(|reward| reward.title.eq_ignore_ascii_case(&current.title));
+        if !is_declared && current.is_enabled {
+            changes.push(RewardChange::Disable {
+                id: current.id.to_string(),
+                title: current.title.clone(),
+            });
+        }
+    }
+
+    changes
+}
+
 pub struct RewardManager<'a, C>
 where
     C: twitch_api2::HttpClient<'a>,
@@ -27,6 +147,17 @@ where
         Self { client, token }
     }
 
+    /// Only the rewards we created can be changed through the API, so those
+    /// are the only ones we look at.
+    pub async fn get_rewards(&self) -> Result<Vec<CustomReward>> {
+        let req = GetCustomRewardRequest::builder()
+            .broadcaster_id(self.token.user_id.clone())
+            .only_manageable_rewards(Some(true))
+            .build();
+
+        Ok(self.client.req_get(req, self.token).await?.data)
+    }
+
     pub async fn set_reward_status(
         &self,
         id: &str,
@@ -46,6 +177,32 @@ where
         Ok(())
     }
 
+    /// Marks a redemption FULFILLED, or CANCELED to give the viewer their
+    /// points back. Only works for rewards we created.
+    pub async fn set_redemption_status(
+        &self,
+        reward_id: &str,
+        redemption_id: &str,
+        fulfilled: bool,
+    ) -> Result<()> {
+        let req = UpdateRedemptionStatusRequest::builder()
+            .broadcaster_id(self.token.user_id.clone())
+            .reward_id(reward_id)
+            .id(redemption_id)
+            .build();
+
+        let status = if fulfilled {
+            CustomRewardRedemptionStatus::Fulfilled
+        } else {
+            CustomRewardRedemptionStatus::Canceled
+        };
+        let body = UpdateRedemptionStatusBody::builder().status(status).build();
+
+        self.client.req_patch(req, body, self.token).await?;
+
+        Ok(())
+    }
+
     pub async fn create_reward(&self, title: &str, cost: usize) -> Result<()> {
         let req = CreateCustomRewardRequest::builder()
             .broadcaster_id(self.token.user_id.clone())
@@ -60,4 +217,204 @@ where
 
         Ok(())
     }
+
+    async fn create_from(&self, reward: &RewardConfig) -> Result<()> {
+        let req = CreateCustomRewardRequest::builder()
+            .broadcaster_id(self.token.user_id.clone())
+            .build();
+
+        let body = CreateCustomRewardBody::builder()
+            .title(reward.title.clone())
+            .cost(reward.cost)
+            .prompt(Some(reward.prompt.clone()))
+            .is_user_input_required(Some(reward.user_input))
+            .is_global_cooldown_enabled(Some(reward.cooldown.is_some()))
+            .global_cooldown_seconds(reward.cooldown)
+            .background_color(reward.color.clone())
+            .build();
+
+        self.client.req_post(req, body, self.token).await?;
+
+        Ok(())
+    }
+
+    async fn update_from(&self, id: &str, reward: &RewardConfig) -> Result<()> {
+        let req = UpdateCustomRewardRequest::builder()
+            .broadcaster_id(self.token.user_id.clone())
+            .id(id)
+            .build();
+
+        let body = UpdateCustomRewardBody::builder()
+            .is_enabled(Some(true))
+            .cost(Some(reward.cost))
+            .prompt(Some(reward.prompt.clone()))
+            .is_user_input_required(Some(reward.user_input))
+            .is_global_cooldown_enabled(Some(reward.cooldown.is_some()))
+            .global_cooldown_seconds(reward.cooldown)
+            .background_color(reward.color.clone())
+            .build();
+
+        self.client.req_patch(req, body, self.token).await?;
+
+        Ok(())
+    }
+
+    /// Makes the rewards on Twitch match `declared`, returning what changed.
+    /// With `dry_run` nothing is changed, only worked out.
+    pub async fn sync(
+        &self,
+        declared: &[RewardConfig],
+        dry_run: bool,
+    ) -> Result<Vec<RewardChange>> {
+        let changes = diff(declared, &self.get_rewards().await?);
+        if dry_run {
+            return Ok(changes);
+        }
+
+        for change in &changes {
+            match change {
+                RewardChange::Create(reward) => {
+                    self.create_from(reward).await?
+                }
+                RewardChange::Update { id, reward } => {
+                    self.update_from(id, reward).await?
+                }
+                RewardChange::Disable { id, .. } => {
+                    self.set_reward_status(id, false).await?
+                }
+            }
+        }
+
+        Ok(changes)
+    }
+
+    /// Turns the `declared` rewards on or off for `mode`, an OBS scene or a
+    /// stream mode. Rewards already in the right state are left alone.
+    pub async fn apply_mode(
+        &self,
+        declared: &[RewardConfig],
+        mode: &str,
+    ) -> Result<()> {
+        for current in self.get_rewards().await? {
+            let reward = match declared.iter().find(|reward| {
+                reward.title.eq_ignore_ascii_case(&current.title)
+            }) {
+                Some(reward) => reward,
+                None => continue,
+            };
+
+            let enabled = reward.enabled_in(mode);
+            if enabled != current.is_enabled {
+                self.set_reward_status(current.id.as_str(), enabled).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn existing(id: &str, title: &str, cost: usize) -> CustomReward {
+        serde_json::from_value(json!({
+            "broadcaster_id": "1",
+            "broadcaster_login": "beginbot",
+            "broadcaster_name": "beginbot",
+            "id": id,
+            "title": title,
+            "prompt": "",
+            "cost": cost,
+            "image": null,
+            "default_image": null,
+            "background_color": "#9146FF",
+            "is_enabled": true,
+            "is_user_input_required": false,
+            "max_per_stream_setting": {
+                "is_enabled": false,
+                "max_per_stream": 0,
+            },
+            "max_per_user_per_stream_setting": {
+                "is_enabled": false,
+                "max_per_user_per_stream": 0,
+            },
+            "global_cooldown_setting": {
+                "is_enabled": false,
+                "global_cooldown_seconds": 0,
+            },
+            "is_paused": false,
+            "is_in_stock": true,
+            "should_redemptions_skip_request_queue": false,
+            "redemptions_redeemed_current_stream": null,
+            "cooldown_expires_at": null,
+        }))
+        .unwrap()
+    }
+
+    fn declared(title: &str, cost: usize) -> RewardConfig {
+        RewardConfig {
+            title: title.to_string(),
+            cost,
+            prompt: String::new(),
+            user_input: false,
+            cooldown: None,
+            color: Some("#9146ff".to_string()),
+            disabled_in: vec!["work".to_string()],
+        }
+    }
+
+    #[test]
+    fn only_what_changed_is_synced() {
+        let changes = diff(
+            &[
+                declared("Mandatory Crab Dance", 1000),
+                declared("Hydrate", 500),
+                declared("New Reward", 100),
+            ],
+            &[
+                existing("1", "mandatory crab dance", 1000),
+                existing("2", "Hydrate", 300),
+                existing("3", "Gone", 100),
+            ],
+        );
+
+        assert_eq!(
+            changes,
+            vec![
+                RewardChange::Update {
+                    id: "2".to_string(),
+                    reward: declared("Hydrate", 500),
+                },
+                RewardChange::Create(declared("New Reward", 100)),
+                RewardChange::Disable {
+                    id: "3".to_string(),
+                    title: "Gone".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn declared_rewards_are_turned_back_on() {
+        let mut current = existing("1", "Hydrate", 500);
+        current.is_enabled = false;
+
+        assert_eq!(
+            diff(&[declared("Hydrate", 500)], &[current]),
+            vec![RewardChange::Update {
+                id: "1".to_string(),
+                reward: declared("Hydrate", 500),
+            }]
+        );
+    }
+
+    #[test]
+    fn rewards_are_off_in_their_modes() {
+        let reward = declared("Mandatory Crab Dance", 1000);
+
+        assert!(!reward.enabled_in("Work"));
+        assert!(reward.enabled_in("chill"));
+    }
 }
@@ -0,0 +1,375 @@
+//! Twitch OAuth tokens that outlive the ones in .env.
+//!
+//! Tokens are seeded from .env the first time they're needed and kept in
+//! `twitch_oauth_tokens` after that, since every refresh replaces them. Ask
+//! the store for a token whenever you need one instead of holding on to it,
+//! and it'll be refreshed before it expires.
+//!
+//! New tokens are minted with `cargo run --bin twitch_auth`.
+
+use anyhow::{anyhow, Context, Result};
+use sqlx::PgPool;
+use std::time::Duration;
+use twitch_api2::twitch_oauth2::{
+    AccessToken, ClientSecret, RefreshToken, Scope, TwitchToken, UserToken,
+};
+
+/// Refresh tokens with less than this left.
+pub const REFRESH_MARGIN: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    /// Helix, EventSub and channel points, as the broadcaster.
+    Broadcaster,
+    /// Chat, as the bot account.
+    Bot,
+}
+
+impl TokenKind {
+    pub const ALL: [TokenKind; 2] = [TokenKind::Broadcaster, TokenKind::Bot];
+
+    /// How the token is stored in the database.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenKind::Broadcaster => "broadcaster",
+            TokenKind::Bot => "bot",
+        }
+    }
+
+    fn from_env(&self) -> (AccessToken, Option<RefreshToken>) {
+        match self {
+            TokenKind::Broadcaster => (
+                subd_types::consts::get_twitch_broadcaster_oauth(),
+                subd_types::consts::get_twitch_broadcaster_refresh(),
+            ),
+            TokenKind::Bot => (
+                AccessToken::new(subd_types::consts::get_twitch_bot_oauth()),
+                subd_types::consts::get_twitch_bot_refresh(),
+            ),
+        }
+    }
+}
+
+impl std::str::FromStr for TokenKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "broadcaster" => Ok(TokenKind::Broadcaster),
+            "bot" => Ok(TokenKind::Bot),
+            _ => Err(anyhow!("{} isn't broadcaster or bot", s)),
+        }
+    }
+}
+
+/// A feature and the scopes its token needs.
+pub struct Requirement {
+    pub feature: &'static str,
+    pub kind: TokenKind,
+    pub scopes: &'static [&'static str],
+}
+
+pub const REQUIREMENTS: &[Requirement] = &[
+    Requirement {
+        feature: "chat",
+        kind: TokenKind::Bot,
+        scopes: &["chat:read", "chat:edit"],
+    },
+    Requirement {
+        feature: "channel point rewards",
+        kind: TokenKind::Broadcaster,
+        scopes: &["channel:read:redemptions", "channel:manage:redemptions"],
+    },
+    Requirement {
+        feature: "subs",
+        kind: TokenKind::Broadcaster,
+        scopes: &["channel:read:subscriptions"],
+    },
+    Requirement {
+        feature: "cheers",
+        kind: TokenKind::Broadcaster,
+        scopes: &["bits:read"],
+    },
+    Requirement {
+        feature: "moderation",
+        kind: TokenKind::Broadcaster,
+        scopes: &[
+            "moderator:manage:banned_users",
+            "moderator:manage:chat_messages",
+            "moderator:manage:warnings",
+        ],
+    },
+];
+
+/// Every scope the features using `kind` need.
+pub fn required_scopes(kind: TokenKind) -> Vec<Scope> {
+    let mut scopes: Vec<&str> = REQUIREMENTS
+        .iter()
+        .filter(|requirement| requirement.kind == kind)
+        .flat_map(|requirement| requirement.scopes.iter().copied())
+        .collect();
+    scopes.sort_unstable();
+    scopes.dedup();
+
+    scopes.into_iter().map(Scope::parse).collect()
+}
+
+/// A feature that won't work, because its token is missing scopes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MissingScopes {
+    pub feature: &'static str,
+    pub kind: TokenKind,
+    pub missing: Vec<&'static str>,
+}
+
+/// Which features can't work with a `kind` token that has `scopes`.
+pub fn missing_scopes(kind: TokenKind, scopes: &[Scope]) -> Vec<MissingScopes> {
+    let granted: Vec<String> =
+        scopes.iter().map(|scope| scope.to_string()).collect();
+
+    REQUIREMENTS
+        .iter()
+        .filter(|requirement| requirement.kind == kind)
+        .filter_map(|requirement| {
+            let missing: Vec<&'static str> = requirement
+                .scopes
+                .iter()
+                .copied()
+                .filter(|scope| !granted.iter().any(|g| g == scope))
+                .collect();
+
+            (!missing.is_empty()).then_some(MissingScopes {
+                feature: requirement.feature,
+                kind,
+                missing,
+            })
+        })
+        .collect()
+}
+
+#[derive(Clone)]
+pub struct TokenStore {
+    pool: PgPool,
+    client: reqwest::Client,
+}
+
+impl TokenStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// A token that is good for at least `REFRESH_MARGIN`.
+    pub async fn user_token(&self, kind: TokenKind) -> Result<UserToken> {
+        let mut token = match self.load(kind).await? {
+            Some(token) => token,
+            None => {
+                let (access, refresh) = kind.from_env();
+                let token = UserToken::from_existing(
+                    &self.client,
+                    access,
+                    refresh,
+                    subd_types::consts::get_twitch_client_secret(),
+                )
+                .await
+                .with_context(|| {
+                    format!("the {} token in .env isn't valid", kind.as_str())
+                })?;
+                self.save(kind, &token).await?;
+                token
+            }
+        };
+
+        if token.expires_in() < REFRESH_MARGIN && !token.never_expires() {
+            self.refresh(kind, &mut token).await?;
+        }
+
+        Ok(token)
+    }
+
+    pub async fn refresh(
+        &self,
+        kind: TokenKind,
+        token: &mut UserToken,
+    ) -> Result<()> {
+        token.refresh_token(&self.client).await.with_context(|| {
+            format!(
+                "can't refresh the {} token, is SUBD_TWITCH_CLIENT_SECRET set?",
+                kind.as_str()
+            )
+        })?;
+        self.save(kind, token).await
+    }
+
+    /// Asks Twitch whether the stored token is still good, as Twitch wants
+    /// done every hour. Returns the features it's missing scopes for.
+    pub async fn validate(
+        &self,
+        kind: TokenKind,
+    ) -> Result<Vec<MissingScopes>> {
+        let token = self.user_token(kind).await?;
+        let validated =
+            token.validate_token(&self.client).await.with_context(|| {
+                format!(
+                    "the {} token was revoked, run `cargo run --bin \
+                     twitch_auth -- --kind {}`",
+                    kind.as_str(),
+                    kind.as_str()
+                )
+            })?;
+
+        Ok(missing_scopes(kind, &validated.scopes.unwrap_or_default()))
+    }
+
+    pub async fn save(&self, kind: TokenKind, token: &UserToken) -> Result<()> {
+        let scopes: Vec<String> = token
+            .scopes()
+            .iter()
+            .map(|scope| scope.to_string())
+            .collect();
+
+        sqlx::query!(
+            r#"INSERT INTO twitch_oauth_tokens
+               (kind, login, user_id, access_token, refresh_token, scopes,
+                expires_at)
+               VALUES ( $1, $2, $3, $4, $5, $6,
+                        NOW() + make_interval(secs => $7) )
+               ON CONFLICT (kind) DO UPDATE SET
+                 login = $2,
+                 user_id = $3,
+                 access_token = $4,
+                 refresh_token = $5,
+                 scopes = $6,
+                 expires_at = NOW() + make_interval(secs => $7),
+                 updated_at = NOW()"#,
+            kind.as_str(),
+            token.login.to_string(),
+            token.user_id.to_string(),
+            token.token().secret(),
+            token.refresh_token.as_ref().map(|refresh| refresh.secret()),
+            &scopes,
+            token.expires_in().as_secs_f64(),
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn load(&self, kind: TokenKind) -> Result<Option<UserToken>> {
+        let row = sqlx::query!(
+            r#"SELECT login, user_id, access_token, refresh_token, scopes,
+                 EXTRACT(EPOCH FROM expires_at - NOW())::BIGINT
+                   AS "expires_in!"
+               FROM twitch_oauth_tokens WHERE kind = $1"#,
+            kind.as_str()
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let row = match row {
+            Some(row) => row,
+            None => return Ok(None),
+        };
+
+        let client_id = subd_types::consts::get_twitch_client_id()
+            .ok_or_else(|| anyhow!("SUBD_TWITCH_CLIENT_ID must be set"))?;
+
+        Ok(Some(UserToken::from_existing_unchecked(
+            AccessToken::new(row.access_token),
+            row.refresh_token.map(RefreshToken::new),
+            client_id,
+            subd_types::consts::get_twitch_client_secret(),
+            row.login.into(),
+            row.user_id.into(),
+            Some(row.scopes.into_iter().map(Scope::parse).collect()),
+            Some(Duration::from_secs(row.expires_in.max(0) as u64)),
+        )))
+    }
+}
+
+/// Makes a token for `kind` with every scope it needs, with the OAuth
+/// authorization code flow. `code_for` is handed the URL to open and
+/// returns the `state` and `code` Twitch redirects back with.
+pub async fn authorize<F, Fut>(
+    store: &TokenStore,
+    kind: TokenKind,
+    redirect_url: &str,
+    code_for: F,
+) -> Result<UserToken>
+where
+    F: FnOnce(String) -> Fut,
+    Fut: std::future::Future<Output = Result<(String, String)>>,
+{
+    let client_id = subd_types::consts::get_twitch_client_id()
+        .ok_or_else(|| anyhow!("SUBD_TWITCH_CLIENT_ID must be set"))?;
+    let client_secret: ClientSecret =
+        subd_types::consts::get_twitch_client_secret()
+            .ok_or_else(|| anyhow!("SUBD_TWITCH_CLIENT_SECRET must be set"))?;
+
+    let mut builder = twitch_api2::twitch_oauth2::UserTokenBuilder::new(
+        client_id,
+        client_secret,
+        twitch_api2::twitch_oauth2::url::Url::parse(redirect_url)?,
+    )
+    .set_scopes(required_scopes(kind))
+    .force_verify(true);
+
+    let (url, _) = builder.generate_url();
+    let (state, code) = code_for(url.to_string()).await?;
+
+    let token = builder
+        .get_user_token(&store.client, &state, &code)
+        .await
+        .context("Twitch didn't accept the code")?;
+    store.save(kind, &token).await?;
+
+    Ok(token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn features_report_the_scopes_they_miss() {
+        let scopes = vec![Scope::parse("channel:read:redemptions")];
+
+        assert_eq!(
+            missing_scopes(TokenKind::Broadcaster, &scopes),
+            vec![
+                MissingScopes {
+                    feature: "channel point rewards",
+                    kind: TokenKind::Broadcaster,
+                    missing: vec!["channel:manage:redemptions"],
+                },
+                MissingScopes {
+                    feature: "subs",
+                    kind: TokenKind::Broadcaster,
+                    missing: vec!["channel:read:subscriptions"],
+                },
+                MissingScopes {
+                    feature: "cheers",
+                    kind: TokenKind::Broadcaster,
+                    missing: vec!["bits:read"],
+                },
+                MissingScopes {
+                    feature: "moderation",
+                    kind: TokenKind::Broadcaster,
+                    missing: vec![
+                        "moderator:manage:banned_users",
+                        "moderator:manage:chat_messages",
+                        "moderator:manage:warnings",
+                    ],
+                },
+            ]
+        );
+        assert!(missing_scopes(
+            TokenKind::Bot,
+            &[Scope::parse("chat:edit"), Scope::parse("chat:read")]
+        )
+        .is_empty());
+    }
+}
@@ -1,5 +1,12 @@
+//! Settings from .env.
+//!
+//! The Twitch tokens here only seed `subd_twitch::tokens::TokenStore`, which
+//! keeps them refreshed from then on. Ask the store for tokens instead.
+
 use once_cell::sync::OnceCell;
-use twitch_api2::twitch_oauth2::{AccessToken, RefreshToken};
+use twitch_api2::twitch_oauth2::{
+    AccessToken, ClientId, ClientSecret, RefreshToken,
+};
 
 /// twitch_bot_oauth is the authentication for the bot that will respond to messages in chat and
 /// whispers (TODO). It can possibly be your account, but in general that will be pretty confusing
@@ -49,13 +56,32 @@ pub fn get_twitch_broadcaster_refresh() -> Option<RefreshToken> {
     static TWITCH_BROADCASTER_REFRESH: OnceCell<Option<RefreshToken>> =
         OnceCell::new();
     TWITCH_BROADCASTER_REFRESH
-        .get_or_init(|| match dotenv::var("SUBD_TWITCH_BOT_REFRESH").ok() {
-            Some(token) => Some(RefreshToken::new(token)),
-            None => None,
+        .get_or_init(|| {
+            dotenv::var("SUBD_TWITCH_BROADCASTER_REFRESH")
+                .ok()
+                .map(RefreshToken::new)
         })
         .clone()
 }
 
+pub fn get_twitch_bot_refresh() -> Option<RefreshToken> {
+    dotenv::var("SUBD_TWITCH_BOT_REFRESH")
+        .ok()
+        .map(RefreshToken::new)
+}
+
+/// The app the tokens were made for. Only needed to refresh tokens, and to
+/// make new ones with `twitch_auth`.
+pub fn get_twitch_client_id() -> Option<ClientId> {
+    dotenv::var("SUBD_TWITCH_CLIENT_ID").ok().map(ClientId::new)
+}
+
+pub fn get_twitch_client_secret() -> Option<ClientSecret> {
+    dotenv::var("SUBD_TWITCH_CLIENT_SECRET")
+        .ok()
+        .map(ClientSecret::new)
+}
+
 /// Get the broadcaster's github token.
 ///
 /// Will return "token <TOKEN>". If the env variable has "token " to start with, it will not
@@ -85,6 +111,24 @@ pub fn get_twitch_broadcaster_channel_id() -> String {
         .expect("SUBD_TWITCH_BROADCASTER_CHANNEL_ID to exist")
 }
 
+/// Where to find EventSub. Only worth changing to point at a local test server.
+pub fn get_twitch_eventsub_url() -> String {
+    dotenv::var("SUBD_TWITCH_EVENTSUB_URL")
+        .unwrap_or_else(|_| "wss://eventsub.wss.twitch.tv/ws".to_string())
+}
+
+/// Base URL for Helix. EventSub subscriptions are created here, so it has to
+/// move along with `get_twitch_eventsub_url` when testing.
+pub fn get_twitch_helix_url() -> String {
+    dotenv::var("SUBD_TWITCH_HELIX_URL")
+        .unwrap_or_else(|_| "https://api.twitch.tv/helix".to_string())
+}
+
+/// The `obs_combo` to run whenever someone raids, if any.
+pub fn get_raid_obs_combo() -> Option<String> {
+    dotenv::var("SUBD_RAID_OBS_COMBO").ok()
+}
+
 pub fn get_twitch_bot_username() -> String {
     dotenv::var("SUBD_TWITCH_BOT_USERNAME")
         .expect("SUBD_TWITCH_BOT_USERNAME to exist")
@@ -105,6 +149,11 @@ pub fn get_obs_websocket_port() -> String {
         .expect("SUBD_OBS_WEBSOCKET_PORT to exist")
 }
 
+/// Only needed when OBS has authentication turned on.
+pub fn get_obs_websocket_password() -> Option<String> {
+    dotenv::var("SUBD_OBS_WEBSOCKET_PASSWORD").ok()
+}
+
 pub fn get_obs_test_scene() -> String {
     dotenv::var("SUBD_OBS_TEST_SCENE").expect("SUBD_OBS_TEST_SCENE to exist")
 }
@@ -33,6 +33,66 @@ pub struct UserMessage {
     pub roles: UserRoles,
     pub platform: UserPlatform,
     pub contents: String,
+
+    /// The Twitch channel it was sent in, by login.
+    #[serde(default)]
+    pub channel: String,
+
+    /// The platform's ID for the message, used to reply to it.
+    #[serde(default)]
+    pub message_id: Option<String>,
+
+    /// The platform's ID for the sender, like their Twitch user id.
+    #[serde(default)]
+    pub platform_user_id: Option<String>,
+
+    /// The sender's login, like their Twitch username. Unlike `user_name`
+    /// it can't be changed to something else.
+    #[serde(default)]
+    pub user_login: Option<String>,
+}
+
+/// A message for the bot to send to Twitch chat.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatReply {
+    pub message: String,
+
+    /// The channel to send to, the bot's main channel when `None`.
+    pub channel: Option<String>,
+
+    /// Send as a reply to this message, so it shows up threaded in chat.
+    pub reply_to: Option<String>,
+}
+
+impl ChatReply {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            channel: None,
+            reply_to: None,
+        }
+    }
+
+    /// Reply to `msg` in its channel, or just say `message` there if `msg`
+    /// can't be replied to.
+    pub fn to(msg: &UserMessage, message: impl Into<String>) -> Self {
+        Self {
+            channel: Some(msg.channel.clone()),
+            reply_to: msg.message_id.clone(),
+            ..Self::new(message)
+        }
+    }
+}
+
+/// Bits cheered in a Twitch chat message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TwitchCheer {
+    /// The login of the channel it was cheered in.
+    pub channel: String,
+    pub user_id: UserID,
+    pub user_name: String,
+    pub bits: u64,
+    pub message: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -47,6 +107,36 @@ pub struct UberDuckRequest {
     pub source: Option<String>,
     // HERE I CAN CHANGE THINGS!!!!
     //
+    /// The channel point redemption that asked for this, told how it went
+    /// with `Event::RedemptionOutcome`.
+    #[serde(default)]
+    pub redemption_id: Option<String>,
+}
+
+/// How an action for a channel point redemption went, when it was handed off
+/// to another handler to finish.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RedemptionOutcome {
+    pub redemption_id: String,
+
+    /// Why it failed, `None` if it worked.
+    pub error: Option<String>,
+}
+
+/// How close the broadcaster is to `target` subs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubGoal {
+    pub title: String,
+    pub target: usize,
+    pub current: usize,
+}
+
+/// A mod taking back what moderation did to `user`, with `!pardon`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModerationPardon {
+    pub channel: String,
+    pub user: String,
+    pub by: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -94,13 +184,30 @@ pub enum Event {
     TwitchChatMessage(twitch::TwitchMessage),
 
     TwitchSubscriptionCount(usize),
+    /// The sub goal and how close we are, `None` when there is no goal.
+    TwitchSubGoal(Option<SubGoal>),
     TwitchSubscription(TwitchSubscriptionEvent),
+    TwitchCheer(TwitchCheer),
+    TwitchUserNotice(twitch::TwitchUserNotice),
+    TwitchChatClear(twitch::TwitchChatClear),
+    TwitchConnectionStatus(ConnectionStatus),
     GithubSponsorshipEvent,
 
     // OBS
     ObsSetScene {
         scene: String,
     },
+    /// How an OBS connection is doing. `channel` is set for the OBS of a
+    /// channel we co-stream with, and `None` for ours.
+    ObsConnectionStatus {
+        channel: Option<String>,
+        status: ConnectionStatus,
+    },
+
+    /// What kind of stream it is right now, like "work" or "chill".
+    StreamMode {
+        mode: String,
+    },
 
     // UserEvents
     ThemesongDownload(ThemesongDownload),
@@ -108,8 +215,16 @@ pub enum Event {
 
     // Requests
     RequestTwitchSubCount,
+    /// A new sub goal, a `target` of 0 takes it down.
+    SetTwitchSubGoal {
+        title: String,
+        target: usize,
+    },
     RequestTwitchMessage(String),
+    TwitchChatReply(ChatReply),
     TwitchChannelPointsRedeem(Redemption),
+    RedemptionOutcome(RedemptionOutcome),
+    ModerationPardon(ModerationPardon),
 
     /// Backend Only
     LunchBytesVoting(LunchBytesCommand),
@@ -124,6 +239,15 @@ pub enum Event {
     Shutdown,
 }
 
+/// How a connection to an outside service is doing, so the overlay can show
+/// when we're not hearing from it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConnectionStatus {
+    Connecting,
+    Connected,
+    Disconnected { reason: String, retry_in_ms: u64 },
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum RaffleStatus {
     Disabled,
@@ -217,14 +341,32 @@ pub struct GithubUser {
     pub name: String,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(
+    sqlx::Type, Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize,
+)]
+#[sqlx(type_name = "twitch_sub_level", rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum TwitchSubLevel {
     Unknown,
+    Prime,
     Tier1,
     Tier2,
     Tier3,
 }
 
+impl TwitchSubLevel {
+    /// What a sub at this level counts for in `UserRoles::support_amount`,
+    /// roughly what it costs.
+    fn support_amount(&self) -> f64 {
+        match self {
+            TwitchSubLevel::Unknown
+            | TwitchSubLevel::Prime
+            | TwitchSubLevel::Tier1 => 2.5,
+            TwitchSubLevel::Tier2 => 5.,
+            TwitchSubLevel::Tier3 => 12.5,
+        }
+    }
+}
+
 // cafce25: put PartialOrd into the list in derive
 // cafce25: Ord is ordering
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -240,6 +382,10 @@ pub enum Role {
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
 pub struct UserRoles {
     pub roles: HashSet<Role>,
+
+    /// How many months they've been subbed on Twitch, in total.
+    #[serde(default)]
+    pub sub_months: u64,
 }
 
 impl UserRoles {
@@ -271,10 +417,14 @@ impl UserRoles {
     }
 
     pub fn is_twitch_sub(&self) -> bool {
-        self.roles
-            .iter()
-            .find(|r| matches!(r, Role::TwitchSub(_)))
-            .is_some()
+        self.twitch_sub_level().is_some()
+    }
+
+    pub fn twitch_sub_level(&self) -> Option<&TwitchSubLevel> {
+        self.roles.iter().find_map(|r| match r {
+            Role::TwitchSub(level) => Some(level),
+            _ => None,
+        })
     }
 }
 
@@ -318,9 +468,11 @@ impl UserRoles {
             amount += 5.;
         }
 
-        // TODO: Should get twitch sub tier
-        if self.is_twitch_sub() {
-            amount += 2.5;
+        if let Some(level) = self.twitch_sub_level() {
+            amount += level.support_amount();
+
+            // A little extra for every year subbed
+            amount += (self.sub_months / 12) as f64 * 0.5;
         }
 
         amount
@@ -409,3 +561,23 @@ pub fn get_prime_sub() -> TwitchSubscriptionEvent {
     let subscription = serde_json::from_value(message).unwrap();
     TwitchSubscriptionEvent { subscription }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subbed(level: TwitchSubLevel, sub_months: u64) -> UserRoles {
+        UserRoles {
+            roles: HashSet::from([Role::TwitchSub(level)]),
+            sub_months,
+        }
+    }
+
+    #[test]
+    fn bigger_and_longer_subs_support_more() {
+        assert_eq!(UserRoles::default().support_amount(), 0.);
+        assert_eq!(subbed(TwitchSubLevel::Prime, 1).support_amount(), 2.5);
+        assert_eq!(subbed(TwitchSubLevel::Tier3, 1).support_amount(), 12.5);
+        assert_eq!(subbed(TwitchSubLevel::Tier1, 25).support_amount(), 3.5);
+    }
+}
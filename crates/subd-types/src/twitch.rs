@@ -1,7 +1,10 @@
 use std::collections::HashSet;
 
 use serde::{Deserialize, Serialize};
-use twitch_irc::message::PrivmsgMessage;
+use twitch_irc::message::{
+    ClearChatAction, ClearChatMessage, ClearMsgMessage, PrivmsgMessage,
+    UserNoticeEvent, UserNoticeMessage,
+};
 
 use crate::{Role, TwitchSubLevel, TwitchUserID, UserRoles};
 
@@ -15,27 +18,64 @@ pub struct TwitchUser {
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TwitchChannel {
-    id: String,
-    login: String,
+    pub id: String,
+    pub login: String,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Emote {
-    // FIXME: Mention how to get the emote picture
     /// ID of emote
     pub id: String,
-    // Start index of emote in message
-    // pub start: i64,
-    // End index of emote in message
-    // pub end: i64,
+    /// Start index of emote in message, in chars
+    #[serde(default)]
+    pub start: usize,
+    /// End index of emote in message, in chars. Not part of the emote.
+    #[serde(default)]
+    pub end: usize,
+    /// Where the picture is, for emotes that aren't Twitch's, like 7TV's.
+    #[serde(default)]
+    pub url: Option<String>,
+}
+
+impl Emote {
+    /// Where to get the picture of the emote.
+    pub fn image_url(&self) -> String {
+        match &self.url {
+            Some(url) => url.clone(),
+            // https://static-cdn.jtvnw.net/emoticons/v2/<id>/<format>/<theme_mode>/<scale>
+            None => format!(
+                "https://static-cdn.jtvnw.net/emoticons/v2/{}/default/dark/2.0",
+                self.id
+            ),
+        }
+    }
 }
 
 impl From<twitch_irc::message::Emote> for Emote {
     fn from(emote: twitch_irc::message::Emote) -> Self {
-        Self { id: emote.id }
+        Self {
+            id: emote.id,
+            start: emote.char_range.start,
+            end: emote.char_range.end,
+            url: None,
+        }
     }
 }
 
+/// A chat badge, like "subscriber" version "3012".
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Badge {
+    pub name: String,
+    pub version: String,
+}
+
+/// The message a chat message replies to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplyParent {
+    pub message_id: String,
+    pub user_login: String,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Color {
     /// Red component
@@ -88,6 +128,14 @@ pub struct TwitchMessage {
     /// A list of emotes in this message. Each emote replaces a part of the `message_text`.
     /// These emotes are sorted in the order that they appear in the message.
     pub emotes: Vec<Emote>,
+
+    /// Every badge shown next to the sender's name.
+    #[serde(default)]
+    pub badges: Vec<Badge>,
+
+    /// Set when this is a reply to another message.
+    #[serde(default)]
+    pub reply_parent: Option<ReplyParent>,
 }
 
 fn get_twitch_roles_from_msg(msg: &PrivmsgMessage) -> UserRoles {
@@ -106,23 +154,39 @@ fn get_twitch_roles_from_msg(msg: &PrivmsgMessage) -> UserRoles {
     if msg.badges.iter().any(|b| b.name == "founder") {
         roles.insert(Role::TwitchFounder);
     }
-    if msg.badges.iter().any(|b| b.name == "subscriber") {
-        roles.insert(Role::TwitchSub(TwitchSubLevel::Unknown));
+    if let Some(badge) = msg.badges.iter().find(|b| b.name == "subscriber") {
+        roles.insert(Role::TwitchSub(TwitchSubLevel::from_badge(
+            &badge.version,
+        )));
     }
     if msg.badges.iter().any(|b| b.name == "staff") {
         roles.insert(Role::TwitchStaff);
     }
 
-    UserRoles {
-        roles,
-        ..Default::default()
-    }
+    // badge_info has the exact months, the badge itself only the milestone.
+    // Founders get a founder badge_info instead.
+    let sub_months = msg
+        .badge_info
+        .iter()
+        .find(|b| b.name == "subscriber" || b.name == "founder")
+        .and_then(|b| b.version.parse().ok())
+        .unwrap_or_default();
+
+    UserRoles { roles, sub_months }
 }
 
 impl TwitchMessage {
     pub fn from_msg(msg: PrivmsgMessage) -> Self {
         let roles = get_twitch_roles_from_msg(&msg);
 
+        // twitch_irc doesn't parse replies yet
+        let tag = |name: &str| msg.source.tags.0.get(name).cloned().flatten();
+        let reply_parent =
+            tag("reply-parent-msg-id").map(|message_id| ReplyParent {
+                message_id,
+                user_login: tag("reply-parent-user-login").unwrap_or_default(),
+            });
+
         Self {
             message_id: msg.message_id,
             channel: TwitchChannel {
@@ -144,6 +208,279 @@ impl TwitchMessage {
                 b: c.b,
             }),
             emotes: msg.emotes.into_iter().map(|e| e.into()).collect(),
+            badges: msg
+                .badges
+                .into_iter()
+                .map(|b| Badge {
+                    name: b.name,
+                    version: b.version,
+                })
+                .collect(),
+            reply_parent,
         }
     }
 }
+
+/// Messages mods took out of chat (CLEARMSG and CLEARCHAT).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum TwitchChatClear {
+    /// A single message was deleted.
+    Message { channel: String, message_id: String },
+    /// Everything `user` said is gone, and they were timed out or banned.
+    User {
+        channel: String,
+        user: TwitchUserID,
+        login: String,
+        /// `None` for a ban.
+        timeout_seconds: Option<u64>,
+    },
+    /// The whole chat was cleared.
+    All { channel: String },
+}
+
+impl TwitchChatClear {
+    pub fn from_clear_msg(msg: ClearMsgMessage) -> Self {
+        TwitchChatClear::Message {
+            channel: msg.channel_login,
+            message_id: msg.message_id,
+        }
+    }
+
+    pub fn from_clear_chat(msg: ClearChatMessage) -> Self {
+        let channel = msg.channel_login;
+        match msg.action {
+            ClearChatAction::ChatCleared => TwitchChatClear::All { channel },
+            ClearChatAction::UserBanned {
+                user_login,
+                user_id,
+            } => TwitchChatClear::User {
+                channel,
+                user: TwitchUserID(user_id),
+                login: user_login,
+                timeout_seconds: None,
+            },
+            ClearChatAction::UserTimedOut {
+                user_login,
+                user_id,
+                timeout_length,
+            } => TwitchChatClear::User {
+                channel,
+                user: TwitchUserID(user_id),
+                login: user_login,
+                timeout_seconds: Some(timeout_length.as_secs()),
+            },
+        }
+    }
+}
+
+/// Something Twitch announced in chat (a USERNOTICE), rather than a message
+/// someone typed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TwitchUserNotice {
+    /// Login of the channel it happened in.
+    pub channel: String,
+
+    /// The raider, subscriber, gifter or announcer.
+    pub user: TwitchUser,
+
+    /// What the user typed along with it, like a resub message.
+    pub text: Option<String>,
+
+    /// Twitch's own description of what happened, ready to show as is.
+    pub system_message: String,
+
+    pub kind: UserNoticeKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum UserNoticeKind {
+    Raid {
+        viewers: u64,
+    },
+    Sub {
+        tier: TwitchSubLevel,
+    },
+    Resub {
+        tier: TwitchSubLevel,
+        cumulative_months: u64,
+        streak_months: Option<u64>,
+    },
+    SubGift {
+        tier: TwitchSubLevel,
+        recipient: TwitchUser,
+        months: u64,
+    },
+    /// Someone gifting a bunch of subs at once. The single `SubGift`s for
+    /// each recipient come in right after.
+    MysteryGift {
+        tier: TwitchSubLevel,
+        count: u64,
+    },
+    Announcement,
+}
+
+impl TwitchSubLevel {
+    /// From the `sub_plan` Twitch sends, "Prime", "1000", "2000" or "3000".
+    pub fn from_plan(plan: &str) -> Self {
+        match plan {
+            "Prime" => TwitchSubLevel::Prime,
+            "1000" => TwitchSubLevel::Tier1,
+            "2000" => TwitchSubLevel::Tier2,
+            "3000" => TwitchSubLevel::Tier3,
+            _ => TwitchSubLevel::Unknown,
+        }
+    }
+
+    /// From the version of a subscriber badge, like "3012" for a tier 3 sub
+    /// of a year. Tier 1 badges are only the months, and Prime subs get those
+    /// too, so they can't be told apart here.
+    pub fn from_badge(version: &str) -> Self {
+        match version.len() {
+            4 if version.starts_with('2') => TwitchSubLevel::Tier2,
+            4 if version.starts_with('3') => TwitchSubLevel::Tier3,
+            _ => TwitchSubLevel::Tier1,
+        }
+    }
+}
+
+impl TwitchUserNotice {
+    /// `None` for the notices nobody listens to, like rituals and upgraded
+    /// gift subs.
+    pub fn from_msg(msg: UserNoticeMessage) -> Option<Self> {
+        let kind = match msg.event {
+            UserNoticeEvent::Raid { viewer_count, .. } => {
+                UserNoticeKind::Raid {
+                    viewers: viewer_count,
+                }
+            }
+            UserNoticeEvent::SubOrResub {
+                is_resub: false,
+                sub_plan,
+                ..
+            } => UserNoticeKind::Sub {
+                tier: TwitchSubLevel::from_plan(&sub_plan),
+            },
+            UserNoticeEvent::SubOrResub {
+                is_resub: true,
+                cumulative_months,
+                streak_months,
+                sub_plan,
+                ..
+            } => UserNoticeKind::Resub {
+                tier: TwitchSubLevel::from_plan(&sub_plan),
+                cumulative_months,
+                streak_months,
+            },
+            UserNoticeEvent::SubGift {
+                recipient,
+                sub_plan,
+                num_gifted_months,
+                ..
+            } => UserNoticeKind::SubGift {
+                tier: TwitchSubLevel::from_plan(&sub_plan),
+                recipient: TwitchUser {
+                    id: TwitchUserID(recipient.id),
+                    login: recipient.login,
+                    name: recipient.name,
+                },
+                months: num_gifted_months,
+            },
+            UserNoticeEvent::SubMysteryGift {
+                mass_gift_count,
+                sub_plan,
+                ..
+            }
+            | UserNoticeEvent::AnonSubMysteryGift {
+                mass_gift_count,
+                sub_plan,
+            } => UserNoticeKind::MysteryGift {
+                tier: TwitchSubLevel::from_plan(&sub_plan),
+                count: mass_gift_count,
+            },
+            // twitch_irc doesn't know about announcements yet
+            _ if msg.event_id == "announcement" => UserNoticeKind::Announcement,
+            _ => return None,
+        };
+
+        Some(Self {
+            channel: msg.channel_login,
+            user: TwitchUser {
+                id: TwitchUserID(msg.sender.id),
+                login: msg.sender.login,
+                name: msg.sender.name,
+            },
+            text: msg.message_text,
+            system_message: msg.system_message,
+            kind,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use twitch_irc::message::IRCMessage;
+
+    #[test]
+    fn messages_keep_replies_badges_and_emotes() {
+        let irc = IRCMessage::parse(
+            "@badge-info=subscriber/14;badges=subscriber/12,bits/100;\
+             color=#FF0000;display-name=Viewer;emotes=25:6-10;id=abc-123;\
+             mod=0;reply-parent-msg-id=def-456;\
+             reply-parent-user-login=beginbot;room-id=424038378;\
+             subscriber=1;tmi-sent-ts=1594556065407;turbo=0;user-id=1234;\
+             user-type= :viewer!viewer@viewer.tmi.twitch.tv \
+             PRIVMSG #beginbot :hello Kappa",
+        )
+        .unwrap();
+        let msg =
+            TwitchMessage::from_msg(PrivmsgMessage::try_from(irc).unwrap());
+
+        assert_eq!(
+            msg.reply_parent,
+            Some(ReplyParent {
+                message_id: "def-456".to_string(),
+                user_login: "beginbot".to_string(),
+            })
+        );
+        assert_eq!(msg.badges.len(), 2);
+        assert_eq!(
+            msg.emotes,
+            vec![Emote {
+                id: "25".to_string(),
+                start: 6,
+                end: 11,
+                url: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn sub_badges_show_the_tier() {
+        assert_eq!(TwitchSubLevel::from_badge("2012"), TwitchSubLevel::Tier2);
+        assert_eq!(TwitchSubLevel::from_badge("3000"), TwitchSubLevel::Tier3);
+        assert_eq!(TwitchSubLevel::from_badge("24"), TwitchSubLevel::Tier1);
+    }
+
+    #[test]
+    fn timeouts_clear_the_user() {
+        let irc = IRCMessage::parse(
+            "@ban-duration=600;room-id=424038378;target-user-id=1234;\
+             tmi-sent-ts=1594556065407 :tmi.twitch.tv CLEARCHAT #beginbot \
+             :viewer",
+        )
+        .unwrap();
+
+        assert_eq!(
+            TwitchChatClear::from_clear_chat(
+                ClearChatMessage::try_from(irc).unwrap()
+            ),
+            TwitchChatClear::User {
+                channel: "beginbot".to_string(),
+                user: TwitchUserID("1234".to_string()),
+                login: "viewer".to_string(),
+                timeout_seconds: Some(600),
+            }
+        );
+    }
+}
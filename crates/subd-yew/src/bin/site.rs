@@ -1,14 +1,15 @@
 use std::cmp::max;
-use std::collections::VecDeque;
 
-use subd_types::twitch::TwitchMessage;
-use subd_types::{Event as SubdEvent, LunchBytesStatus};
+use subd_types::twitch::{TwitchMessage, UserNoticeKind};
+use subd_types::{ConnectionStatus, Event as SubdEvent, LunchBytesStatus};
+use subd_yew::components::connection_status::TwitchConnection;
 use subd_yew::components::lunchbytes::{self, status};
 use subd_yew::components::raffle::RaffleComponent;
+use subd_yew::components::raid_notification::RaidNotification;
+use subd_yew::components::sub_goal::SubGoalBar;
 use subd_yew::components::sub_notification::SubNotification;
 use subd_yew::components::themesong_downloader::ThemesongDownloader;
 use subd_yew::components::themesong_player::ThemesongPlayer;
-use twitch_irc::message::Emote;
 use yew::prelude::*;
 use yew_hooks::{use_list, use_web_socket};
 
@@ -16,14 +17,6 @@ use yew_hooks::{use_list, use_web_socket};
 //  Probably what we want to end up using to dispatch over Event
 // Might not need to though
 
-// https://static-cdn.jtvnw.net/emoticons/v2/<id>/<format>/<theme_mode>/<scale>
-fn _make_emote_url(emote: &Emote) -> String {
-    format!(
-        "https://static-cdn.jtvnw.net/emoticons/v2/{}/default/dark/2.0",
-        emote.id
-    )
-}
-
 fn render_message(message: &TwitchMessage) -> Html {
     let color = message
         .name_color
@@ -52,39 +45,42 @@ fn render_message(message: &TwitchMessage) -> Html {
             </p>
         });
     } else {
-        let contents = contents.chars();
-        let _last_emote_finish = 0;
-
-        let _emotes = VecDeque::from(message.emotes.clone());
-        // while let Some(emote) = emotes.pop_front() {
-        //     // Get the missing text contents
-        //     let segment = contents
-        //         .by_ref()
-        //         .take(emote.char_range.start - last_emote_finish)
-        //         .collect::<String>();
-        //
-        //     if !segment.is_empty() {
-        //         pieces.push(html! { <span> { segment } </span> });
-        //     }
-        //
-        //     pieces.push(
-        //         html! { <img src={make_emote_url(&emote)} alt={"emote"} /> },
-        //     );
-        //
-        //     last_emote_finish = emote.char_range.end;
-        //
-        //     // This is just stupid skip cause i'm stupid and tired today
-        //     _ = contents
-        //         .by_ref()
-        //         .take(emote.char_range.len())
-        //         .collect::<String>();
-        // }
+        let mut contents = contents.chars();
+        let mut last_emote_finish = 0;
+
+        let mut emotes = message.emotes.clone();
+        emotes.sort_by_key(|emote| emote.start);
+        for emote in emotes {
+            // Overlapping emotes would eat text that's already shown
+            if emote.start < last_emote_finish {
+                continue;
+            }
+
+            // Get the missing text contents
+            let segment = contents
+                .by_ref()
+                .take(emote.start - last_emote_finish)
+                .collect::<String>();
+            if !segment.is_empty() {
+                pieces.push(html! { <span> { segment } </span> });
+            }
+
+            // The emote's name, for when the picture doesn't load
+            let name = contents
+                .by_ref()
+                .take(emote.end - emote.start)
+                .collect::<String>();
+            pieces.push(html! {
+                <img class={"subd-emote"} src={emote.image_url()} alt={name} />
+            });
+
+            last_emote_finish = emote.end;
+        }
 
         let remaining = contents.collect::<String>();
         if !remaining.is_empty() {
             pieces.push(html! { <span> { remaining } </span> });
         }
-        // pieces.push(html! { <p> { "You think i'll just show your emotes??" } </p> });
     }
 
     let color_str = format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b);
@@ -199,8 +195,10 @@ fn reducer(props: &Props) -> Html {
 
     let history = use_list(default_messages());
     let subcount = use_state(|| 0);
+    let sub_goal = use_state(|| None);
 
     let new_sub = use_state(|| None);
+    let new_raid = use_state(|| None);
     let themesong = use_state(|| None);
     let player = use_state(|| None);
     let lb_status = use_state(|| LunchBytesStatus {
@@ -208,16 +206,20 @@ fn reducer(props: &Props) -> Html {
         topics: vec![],
     });
     let raffle_status = use_state(|| subd_types::RaffleStatus::Disabled);
+    let twitch_status = use_state(|| ConnectionStatus::Connected);
 
     {
         let history = history.clone();
         let ws = ws.clone();
         let subcount = subcount.clone();
+        let sub_goal = sub_goal.clone();
         let new_sub = new_sub.clone();
+        let new_raid = new_raid.clone();
         let themesong = themesong.clone();
         let player = player.clone();
         let lb_status = lb_status.clone();
         let raffle_status = raffle_status.clone();
+        let twitch_status = twitch_status.clone();
 
         // Receive message by depending on `ws.message`.
         use_effect_with_deps(
@@ -233,6 +235,7 @@ fn reducer(props: &Props) -> Html {
                         SubdEvent::TwitchSubscriptionCount(count) => {
                             subcount.set(count)
                         }
+                        SubdEvent::TwitchSubGoal(goal) => sub_goal.set(goal),
                         SubdEvent::TwitchSubscription(subscription) => {
                             log::info!(
                                 "Got a new subscription: {:?}",
@@ -241,6 +244,13 @@ fn reducer(props: &Props) -> Html {
                             // handle_twitch_sub(subscription)
                             new_sub.set(Some(subscription))
                         }
+                        SubdEvent::TwitchUserNotice(notice) => {
+                            if let UserNoticeKind::Raid { viewers } =
+                                notice.kind
+                            {
+                                new_raid.set(Some((notice.user.name, viewers)))
+                            }
+                        }
                         SubdEvent::ThemesongDownload(download) => {
                             let download_type = match download {
                                 subd_types::ThemesongDownload::Request {
@@ -276,6 +286,9 @@ fn reducer(props: &Props) -> Html {
                         SubdEvent::RaffleStatus(raffle_msg) => {
                             raffle_status.set(raffle_msg);
                         }
+                        SubdEvent::TwitchConnectionStatus(status) => {
+                            twitch_status.set(status);
+                        }
 
                         _ => {}
                     }
@@ -298,6 +311,15 @@ fn reducer(props: &Props) -> Html {
         None => html! {},
     };
 
+    let raid = match &(*new_raid) {
+        Some((raider, viewers)) => {
+            html! {
+                <RaidNotification raider={raider.clone()} viewers={*viewers} />
+            }
+        }
+        None => html! {},
+    };
+
     let themesong = match &(*themesong) {
         Some(themesong) => {
             let themesong = themesong.clone();
@@ -334,9 +356,10 @@ fn reducer(props: &Props) -> Html {
             .collect(),
     };
 
-    // <div class={"subd-goal"}>
-    //     <p>{ format!("{} / 420", *subcount) }</p>
-    // </div>
+    let sub_goal = match &(*sub_goal) {
+        Some(goal) => html! { <SubGoalBar goal={goal.clone()} /> },
+        None => html! {},
+    };
 
     html! {
         <div class={ "subd" }>
@@ -352,10 +375,13 @@ fn reducer(props: &Props) -> Html {
             }
             </div>
             <> { notification } </>
+            <> { raid } </>
             <> { themesong } </>
             <> { player } </>
             <> { raffle_html } </>
+            <> { sub_goal } </>
             <> <lunchbytes::status::Status ..status_props/> </>
+            <> <TwitchConnection status={(*twitch_status).clone()} /> </>
         </div>
     }
 }
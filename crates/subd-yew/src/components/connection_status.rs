@@ -0,0 +1,33 @@
+use subd_types::ConnectionStatus;
+use yew::prelude::*;
+
+#[derive(Clone, PartialEq, Properties)]
+pub struct Props {
+    pub status: ConnectionStatus,
+}
+
+/// Only shows up while we can't hear from Twitch, so we know subs and
+/// redemptions aren't making it through.
+#[function_component(TwitchConnection)]
+pub fn twitch_connection(props: &Props) -> Html {
+    match &props.status {
+        ConnectionStatus::Connected => html! {},
+        ConnectionStatus::Connecting => html! {
+            <div class={"subd-connection"}>
+                { "Connecting to Twitch..." }
+            </div>
+        },
+        ConnectionStatus::Disconnected {
+            reason,
+            retry_in_ms,
+        } => html! {
+            <div class={"subd-connection subd-connection-lost"}>
+                { format!(
+                    "Lost Twitch ({}), retrying in {}s",
+                    reason,
+                    retry_in_ms.div_ceil(1000)
+                ) }
+            </div>
+        },
+    }
+}
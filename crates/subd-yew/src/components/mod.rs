@@ -1,5 +1,8 @@
+pub mod connection_status;
 pub mod lunchbytes;
 pub mod raffle;
+pub mod raid_notification;
+pub mod sub_goal;
 pub mod sub_notification;
 pub mod themesong_downloader;
 pub mod themesong_player;
@@ -0,0 +1,78 @@
+use gloo_timers::callback::Timeout;
+use yew::prelude::*;
+
+#[derive(Clone, PartialEq, Properties)]
+pub struct Props {
+    pub raider: String,
+    pub viewers: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Msg {
+    ShowNotification,
+    HideNotification,
+}
+
+/// Stays up a little longer than a sub, raiders like to see their name.
+#[derive(Debug)]
+pub struct RaidNotification {
+    show: bool,
+
+    #[allow(unused)]
+    timeout: Timeout,
+}
+
+impl RaidNotification {
+    fn get_timeout(ctx: &Context<Self>) -> Timeout {
+        let link = ctx.link().clone();
+        Timeout::new(8000, move || link.send_message(Msg::HideNotification))
+    }
+}
+
+impl Component for RaidNotification {
+    type Message = Msg;
+    type Properties = Props;
+
+    fn create(ctx: &Context<Self>) -> Self {
+        Self {
+            show: true,
+            timeout: Self::get_timeout(ctx),
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Msg::ShowNotification => {
+                self.show = true;
+                self.timeout = Self::get_timeout(ctx);
+            }
+            Msg::HideNotification => self.show = false,
+        }
+
+        true
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let props = ctx.props();
+        let animation = if self.show {
+            "animate__bounceInDown"
+        } else {
+            "animate__bounceOutLeft"
+        };
+
+        html! {
+            <div class={classes!("subd-notification", "subd-raid",
+                                 "animate__animated", animation)}>
+                { format!(
+                    "{} is raiding with {} viewers!",
+                    props.raider, props.viewers
+                ) }
+            </div>
+        }
+    }
+
+    fn changed(&mut self, ctx: &Context<Self>) -> bool {
+        ctx.link().send_message(Msg::ShowNotification);
+        true
+    }
+}
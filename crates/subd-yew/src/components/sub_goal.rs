@@ -0,0 +1,29 @@
+use subd_types::SubGoal;
+use yew::prelude::*;
+
+#[derive(Clone, PartialEq, Properties)]
+pub struct Props {
+    pub goal: SubGoal,
+}
+
+/// How close we are to the sub goal, as a bar that fills up.
+#[function_component(SubGoalBar)]
+pub fn sub_goal_bar(props: &Props) -> Html {
+    let goal = &props.goal;
+    let percentage =
+        (goal.current as f32 / goal.target.max(1) as f32).min(1.0) * 100.0;
+
+    html! {
+        <div class={"subd-goal"}>
+            <div class={"subd-goal-title"}>
+                { format!("{} {} / {}", goal.title, goal.current, goal.target) }
+            </div>
+            <div class={"subd-goal-bar"}>
+                <div
+                    class={"subd-goal-progress"}
+                    style={ format!("width: {}%", percentage) }
+                />
+            </div>
+        </div>
+    }
+}
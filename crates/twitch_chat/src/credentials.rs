@@ -0,0 +1,40 @@
+//! Logging in to chat with the bot token in `TokenStore`.
+//!
+//! Chat asks for credentials every time it connects, so a token refreshed
+//! while the bot runs is the one used when chat reconnects.
+
+use async_trait::async_trait;
+use std::fmt;
+use subd_twitch::tokens::{TokenKind, TokenStore};
+use twitch_api2::twitch_oauth2::TwitchToken;
+use twitch_irc::login::{CredentialsPair, LoginCredentials};
+
+#[derive(Clone)]
+pub struct TokenCredentials {
+    tokens: TokenStore,
+}
+
+impl TokenCredentials {
+    pub fn new(tokens: TokenStore) -> Self {
+        Self { tokens }
+    }
+}
+
+impl fmt::Debug for TokenCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenCredentials").finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl LoginCredentials for TokenCredentials {
+    type Error = anyhow::Error;
+
+    async fn get_credentials(&self) -> Result<CredentialsPair, Self::Error> {
+        let token = self.tokens.user_token(TokenKind::Bot).await?;
+        Ok(CredentialsPair {
+            login: token.login.to_string(),
+            token: Some(token.token().secret().to_string()),
+        })
+    }
+}
@@ -0,0 +1,521 @@
+//! Emotes from outside Twitch, like 7TV and BTTV.
+//!
+//! Twitch tells us where its own emotes are in a message, but other
+//! providers' emotes are just words. `EmoteMap` knows every provider's
+//! emotes for each channel, by name, and `TwitchChat` adds them to messages
+//! before they go on the bus. It keeps them in data/emotes.json, so we don't
+//! ask every provider again each time we start.
+//!
+//! The providers are only ever asked from a task of their own, so a slow one
+//! never holds up chat. Messages get the emotes that were known when they
+//! came in.
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use subd_types::twitch::{Emote, TwitchMessage};
+use tokio::sync::mpsc;
+
+pub const EMOTES_FILE: &str = "data/emotes.json";
+
+/// How long before we ask the providers again.
+const CACHE_FOR: Duration = Duration::from_secs(60 * 60);
+
+/// How long before we ask again when a provider failed.
+const RETRY_AFTER: Duration = Duration::from_secs(5 * 60);
+
+/// A slow provider holds up refreshing every other set, so it doesn't get
+/// long.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What the global emotes are kept under, instead of a channel id.
+const GLOBAL: &str = "global";
+
+/// Where picture urls of emotes come from, by the emote's name.
+#[async_trait]
+pub trait EmoteProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Emotes anyone can use in any channel.
+    async fn global(&self) -> Result<HashMap<String, String>>;
+
+    /// Emotes only for the channel with this Twitch id.
+    async fn channel(
+        &self,
+        channel_id: &str,
+    ) -> Result<HashMap<String, String>>;
+}
+
+fn http_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .expect("the HTTP client to build")
+}
+
+/// 7TV, at https://7tv.app
+pub struct SevenTv {
+    client: reqwest::Client,
+}
+
+impl Default for SevenTv {
+    fn default() -> Self {
+        Self {
+            client: http_client(),
+        }
+    }
+}
+
+impl SevenTv {
+    fn emotes(set: &serde_json::Value) -> HashMap<String, String> {
+        set["emotes"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|emote| {
+                let id = emote["id"].as_str()?;
+                let name = emote["name"].as_str()?;
+                Some((
+                    name.to_string(),
+                    format!("https://cdn.7tv.app/emote/{}/2x.webp", id),
+                ))
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl EmoteProvider for SevenTv {
+    fn name(&self) -> &'static str {
+        "7tv"
+    }
+
+    async fn global(&self) -> Result<HashMap<String, String>> {
+        let set = get_json(
+            &self.client,
+            "https://7tv.io/v3/emote-sets/global".to_string(),
+        )
+        .await?;
+        Ok(Self::emotes(&set))
+    }
+
+    async fn channel(
+        &self,
+        channel_id: &str,
+    ) -> Result<HashMap<String, String>> {
+        let user = get_json(
+            &self.client,
+            format!("https://7tv.io/v3/users/twitch/{}", channel_id),
+        )
+        .await?;
+        Ok(Self::emotes(&user["emote_set"]))
+    }
+}
+
+/// BetterTTV, at https://betterttv.com
+pub struct Bttv {
+    client: reqwest::Client,
+}
+
+impl Default for Bttv {
+    fn default() -> Self {
+        Self {
+            client: http_client(),
+        }
+    }
+}
+
+impl Bttv {
+    fn emotes(emotes: &serde_json::Value) -> HashMap<String, String> {
+        emotes
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|emote| {
+                let id = emote["id"].as_str()?;
+                let name = emote["code"].as_str()?;
+                Some((
+                    name.to_string(),
+                    format!("https://cdn.betterttv.net/emote/{}/2x", id),
+                ))
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl EmoteProvider for Bttv {
+    fn name(&self) -> &'static str {
+        "bttv"
+    }
+
+    async fn global(&self) -> Result<HashMap<String, String>> {
+        let emotes = get_json(
+            &self.client,
+            "https://api.betterttv.net/3/cached/emotes/global".to_string(),
+        )
+        .await?;
+        Ok(Self::emotes(&emotes))
+    }
+
+    async fn channel(
+        &self,
+        channel_id: &str,
+    ) -> Result<HashMap<String, String>> {
+        let user = get_json(
+            &self.client,
+            format!(
+                "https://api.betterttv.net/3/cached/users/twitch/{}",
+                channel_id
+            ),
+        )
+        .await?;
+
+        let mut emotes = Self::emotes(&user["sharedEmotes"]);
+        emotes.extend(Self::emotes(&user["channelEmotes"]));
+        Ok(emotes)
+    }
+}
+
+async fn get_json(
+    client: &reqwest::Client,
+    url: String,
+) -> Result<serde_json::Value> {
+    let response = client.get(&url).send().await?;
+
+    let status = response.status();
+    // Channels that never signed up are a 404, and just have no emotes
+    if status == reqwest::StatusCode::NOT_FOUND {
+        return Ok(serde_json::Value::Null);
+    }
+    if !status.is_success() {
+        return Err(anyhow!("can't get {}: {}", url, status));
+    }
+
+    Ok(serde_json::from_str(&response.text().await?)?)
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct EmoteSet {
+    /// When we last asked the providers, in seconds since the epoch.
+    fetched_at: u64,
+
+    /// Picture urls, by emote name.
+    emotes: HashMap<String, String>,
+}
+
+/// By channel id, and the global ones under `GLOBAL`.
+type EmoteSets = Arc<RwLock<HashMap<String, EmoteSet>>>;
+
+pub struct EmoteMap {
+    path: String,
+    providers: Vec<Box<dyn EmoteProvider>>,
+    sets: EmoteSets,
+
+    /// When a provider last failed for a set, by the set's key. Only kept
+    /// while running, so we try again on start.
+    failed_at: HashMap<String, u64>,
+}
+
+impl EmoteMap {
+    /// Starts from what's kept at `path`, if anything. Providers that come
+    /// first win when two have an emote with the same name.
+    pub fn load(path: &str, providers: Vec<Box<dyn EmoteProvider>>) -> Self {
+        let sets = match std::fs::read_to_string(path) {
+            Ok(contents) => {
+                serde_json::from_str(&contents).unwrap_or_else(|err| {
+                    println!("Ignoring emotes in {}: {}", path, err);
+                    HashMap::new()
+                })
+            }
+            Err(_) => HashMap::new(),
+        };
+
+        Self {
+            path: path.to_string(),
+            providers,
+            sets: Arc::new(RwLock::new(sets)),
+            failed_at: HashMap::new(),
+        }
+    }
+
+    /// Keeps the emotes fresh from a task of its own, for as long as any
+    /// clone of the returned `Emotes` is around.
+    pub fn spawn(mut self) -> Emotes {
+        let (refresh, mut channels) = mpsc::unbounded_channel::<String>();
+        let emotes = Emotes {
+            sets: self.sets.clone(),
+            refresh,
+        };
+
+        tokio::spawn(async move {
+            self.refresh(GLOBAL).await;
+            while let Some(channel_id) = channels.recv().await {
+                self.refresh(GLOBAL).await;
+                self.refresh(&channel_id).await;
+            }
+        });
+
+        emotes
+    }
+
+    async fn refresh(&mut self, key: &str) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let previous = self.sets.read().unwrap().get(key).cloned();
+        let fresh = previous
+            .as_ref()
+            .map_or(false, |set| now < set.fetched_at + CACHE_FOR.as_secs());
+        let retrying = self
+            .failed_at
+            .get(key)
+            .map_or(false, |failed_at| now < failed_at + RETRY_AFTER.as_secs());
+        if fresh || retrying {
+            return;
+        }
+
+        // Going backwards, so the first provider's emotes are the ones kept
+        let mut failed = false;
+        let mut emotes = HashMap::new();
+        for provider in self.providers.iter().rev() {
+            let fetched = match key {
+                GLOBAL => provider.global().await,
+                channel_id => provider.channel(channel_id).await,
+            };
+            match fetched {
+                Ok(fetched) => emotes.extend(fetched),
+                Err(err) => {
+                    // Keep what we had, and try again later
+                    failed = true;
+                    println!(
+                        "Error getting {} emotes: {:#}",
+                        provider.name(),
+                        err
+                    );
+                    if let Some(set) = &previous {
+                        emotes.extend(set.emotes.clone());
+                    }
+                }
+            }
+        }
+
+        // Only a set every provider answered for counts as fetched
+        let fetched_at = if failed {
+            self.failed_at.insert(key.to_string(), now);
+            previous.map_or(0, |set| set.fetched_at)
+        } else {
+            self.failed_at.remove(key);
+            now
+        };
+        self.sets
+            .write()
+            .unwrap()
+            .insert(key.to_string(), EmoteSet { fetched_at, emotes });
+
+        if let Err(err) = self.save() {
+            println!("Error saving emotes to {}: {:#}", self.path, err);
+        }
+    }
+
+    /// 7TV then BTTV, kept in `EMOTES_FILE`.
+    pub fn with_defaults() -> Self {
+        Self::load(
+            EMOTES_FILE,
+            vec![Box::new(SevenTv::default()), Box::new(Bttv::default())],
+        )
+    }
+
+    fn save(&self) -> Result<()> {
+        let sets = serde_json::to_string(&*self.sets.read().unwrap())?;
+        std::fs::write(&self.path, sets)?;
+        Ok(())
+    }
+}
+
+/// Looks up the emotes an `EmoteMap` has fetched so far.
+#[derive(Clone)]
+pub struct Emotes {
+    sets: EmoteSets,
+    refresh: mpsc::UnboundedSender<String>,
+}
+
+impl Emotes {
+    /// Adds every other provider's emote in `msg` to its emotes. Never waits
+    /// on a provider, a channel's emotes are only fetched once one of its
+    /// messages has come in.
+    pub fn inline(&self, msg: &mut TwitchMessage) {
+        // Only fails once the map's task is gone, then we keep what we have
+        let _ = self.refresh.send(msg.channel.id.clone());
+
+        msg.emotes = find_emotes(&msg.text, &msg.emotes, |word| {
+            self.lookup(&msg.channel.id, word)
+        });
+    }
+
+    /// The picture url of the emote called `word` in the channel with this
+    /// Twitch id.
+    pub fn lookup(&self, channel_id: &str, word: &str) -> Option<String> {
+        let sets = self.sets.read().unwrap();
+        sets.get(channel_id)
+            .and_then(|set| set.emotes.get(word))
+            .or_else(|| sets.get(GLOBAL).and_then(|set| set.emotes.get(word)))
+            .cloned()
+    }
+}
+
+/// `emotes`, plus every word of `text` that `lookup` has a picture for.
+/// Words that are already part of an emote are left alone.
+pub fn find_emotes(
+    text: &str,
+    emotes: &[Emote],
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Vec<Emote> {
+    let mut found = emotes.to_vec();
+
+    let chars: Vec<char> = text.chars().collect();
+    let mut start = 0;
+    while start < chars.len() {
+        if chars[start].is_whitespace() {
+            start += 1;
+            continue;
+        }
+
+        let mut end = start;
+        while end < chars.len() && !chars[end].is_whitespace() {
+            end += 1;
+        }
+
+        let taken = emotes
+            .iter()
+            .any(|emote| emote.start < end && start < emote.end);
+        if !taken {
+            let word: String = chars[start..end].iter().collect();
+            if let Some(url) = lookup(&word) {
+                found.push(Emote {
+                    id: word,
+                    start,
+                    end,
+                    url: Some(url),
+                });
+            }
+        }
+
+        start = end;
+    }
+
+    found.sort_by_key(|emote| emote.start);
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Has one global emote, and fails for every channel.
+    #[derive(Clone, Default)]
+    struct Flaky {
+        asked: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl EmoteProvider for Flaky {
+        fn name(&self) -> &'static str {
+            "flaky"
+        }
+
+        async fn global(&self) -> Result<HashMap<String, String>> {
+            self.asked.fetch_add(1, Ordering::SeqCst);
+            Ok(HashMap::from([(
+                "catJAM".to_string(),
+                "https://emotes/catJAM".to_string(),
+            )]))
+        }
+
+        async fn channel(&self, _: &str) -> Result<HashMap<String, String>> {
+            self.asked.fetch_add(1, Ordering::SeqCst);
+            Err(anyhow!("down"))
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_sets_are_not_cached() {
+        let path = std::env::temp_dir().join("subd-failed-emotes.json");
+        let _ = std::fs::remove_file(&path);
+        let flaky = Flaky::default();
+        let mut map = EmoteMap::load(
+            path.to_str().unwrap(),
+            vec![Box::new(flaky.clone())],
+        );
+
+        map.refresh(GLOBAL).await;
+        map.refresh("1234").await;
+        // Not asked again right away, even though it failed
+        map.refresh("1234").await;
+
+        assert_eq!(flaky.asked.load(Ordering::SeqCst), 2);
+        let sets = map.sets.read().unwrap();
+        assert!(sets[GLOBAL].fetched_at > 0);
+        assert_eq!(sets["1234"].fetched_at, 0);
+
+        // Starting again tries the channel again
+        let map = EmoteMap::load(path.to_str().unwrap(), vec![]);
+        assert_eq!(map.sets.read().unwrap()["1234"].fetched_at, 0);
+    }
+
+    #[tokio::test]
+    async fn emotes_are_fetched_in_the_background() {
+        let path = std::env::temp_dir().join("subd-background-emotes.json");
+        let _ = std::fs::remove_file(&path);
+        let emotes = EmoteMap::load(
+            path.to_str().unwrap(),
+            vec![Box::new(Flaky::default())],
+        )
+        .spawn();
+
+        // Nothing is fetched until the map's task gets to run
+        assert_eq!(emotes.lookup("1234", "catJAM"), None);
+
+        let url = tokio::time::timeout(Duration::from_secs(1), async {
+            loop {
+                match emotes.lookup("1234", "catJAM") {
+                    Some(url) => return url,
+                    None => tokio::task::yield_now().await,
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(url, "https://emotes/catJAM");
+    }
+
+    #[test]
+    fn finds_other_emotes_between_twitch_ones() {
+        let twitch = Emote {
+            id: "25".to_string(),
+            start: 6,
+            end: 11,
+            url: None,
+        };
+
+        let found = find_emotes("héllo Kappa catJAM ok", &[twitch], |word| {
+            (word == "catJAM" || word == "Kappa")
+                .then(|| format!("https://emotes/{}", word))
+        });
+
+        assert_eq!(
+            found
+                .iter()
+                .map(|emote| (emote.id.as_str(), emote.start, emote.end))
+                .collect::<Vec<_>>(),
+            vec![("25", 6, 11), ("catJAM", 12, 18)]
+        );
+        assert_eq!(found[1].image_url(), "https://emotes/catJAM");
+    }
+}
@@ -1,18 +1,31 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use async_trait::async_trait;
-use events::EventHandler;
+use events::{EventHandler, Subscription};
 use reqwest::Client as ReqwestClient;
-use subd_types::{Event, UserID, UserMessage, UserPlatform};
+use std::time::Duration;
+use subd_twitch::tokens::TokenStore;
+use subd_types::twitch::{
+    TwitchChatClear, TwitchMessage, TwitchUserNotice, UserNoticeKind,
+};
+use subd_types::{
+    ChatReply, Event, TwitchCheer, UserID, UserMessage, UserPlatform,
+};
 use tokio::sync::{broadcast, mpsc::UnboundedReceiver};
 use twitch_api2::{
     helix::subscriptions::GetBroadcasterSubscriptionsRequest,
     twitch_oauth2::UserToken, HelixClient,
 };
 use twitch_irc::{
-    login::StaticLoginCredentials, message::ServerMessage, ClientConfig,
-    SecureTCPTransport, TwitchIRCClient,
+    message::ServerMessage, ClientConfig, SecureTCPTransport, TwitchIRCClient,
 };
 
+mod credentials;
+pub mod emotes;
+mod outgoing;
+pub use credentials::TokenCredentials;
+use emotes::EmoteMap;
+use outgoing::Outgoing;
+
 // fn get_chat_config() -> ClientConfig<StaticLoginCredentials> {
 //     let twitch_username = subd_types::consts::get_twitch_bot_username();
 //     ClientConfig::new_simple(StaticLoginCredentials::new(
@@ -23,61 +36,123 @@ use twitch_irc::{
 
 #[allow(dead_code)]
 pub struct TwitchChat {
-    broadcaster_username: String,
+    /// Every channel joined. The first is the main one, where replies
+    /// without a channel go.
+    channels: Vec<String>,
     incoming: UnboundedReceiver<ServerMessage>,
-    client: TwitchIRCClient<SecureTCPTransport, StaticLoginCredentials>,
+    client: TwitchIRCClient<SecureTCPTransport, TokenCredentials>,
     pool: sqlx::PgPool,
+
+    /// Adds other providers' emotes to messages, when set.
+    emotes: Option<EmoteMap>,
 }
 
 impl TwitchChat {
+    /// Joins `channels` as the bot, with the bot token in `tokens`.
     pub fn new(
         pool: sqlx::PgPool,
-        broadcaster_username: String,
+        channels: Vec<String>,
+        tokens: TokenStore,
     ) -> Result<Self> {
-        // TODO: Should make bot configurable via this too
-        let twitch_username = subd_types::consts::get_twitch_bot_username();
-        let config = ClientConfig::new_simple(StaticLoginCredentials::new(
-            twitch_username,
-            Some(subd_types::consts::get_twitch_bot_oauth()),
-        ));
+        if channels.is_empty() {
+            bail!("TwitchChat needs at least one channel to join");
+        }
+
+        let config = ClientConfig::new_simple(TokenCredentials::new(tokens));
 
         let (incoming, client) = TwitchIRCClient::<
             SecureTCPTransport,
-            StaticLoginCredentials,
+            TokenCredentials,
         >::new(config);
 
-        client.join(broadcaster_username.clone())?;
+        for channel in &channels {
+            client.join(channel.clone())?;
+        }
 
         Ok(Self {
-            broadcaster_username,
+            channels,
             incoming,
             client,
             pool,
+            emotes: None,
         })
     }
+
+    /// Adds the emotes `emotes` knows to every message, like 7TV's.
+    pub fn emotes(mut self, emotes: EmoteMap) -> Self {
+        self.emotes = Some(emotes);
+        self
+    }
 }
 
 #[async_trait]
 impl EventHandler for TwitchChat {
+    fn subscription(&self) -> Subscription {
+        events::only!(RequestTwitchMessage, TwitchChatReply)
+    }
+
     async fn handle(
         mut self: Box<Self>,
         tx: broadcast::Sender<Event>,
-        _: broadcast::Receiver<Event>,
+        mut rx: broadcast::Receiver<Event>,
     ) -> Result<()> {
+        let mut outgoing =
+            Outgoing::new(self.client.clone(), self.channels[0].clone());
+        let emotes = self.emotes.take().map(EmoteMap::spawn);
+
         // Listen for incoming IRC messages from Twitch
         // we send an TwitchChatMessage event
-        // which loop handles somewhere
-        while let Some(message) = self.incoming.recv().await {
+        // which loop handles somewhere.
+        //
+        // Replies from the rest of the bot come in over the bus.
+        loop {
+            let message = tokio::select! {
+                message = self.incoming.recv() => match message {
+                    Some(message) => message,
+                    None => return Ok(()),
+                },
+                event = events::recv(&mut rx) => {
+                    match event? {
+                        Event::RequestTwitchMessage(message) => {
+                            outgoing.send(ChatReply::new(message))
+                        }
+                        Event::TwitchChatReply(reply) => outgoing.send(reply),
+                        Event::Shutdown => return Ok(()),
+                        _ => {}
+                    }
+                    continue;
+                },
+            };
+
             match message {
                 ServerMessage::Privmsg(private) => {
-                    tx.send(Event::TwitchChatMessage(
-                        subd_types::twitch::TwitchMessage::from_msg(private),
+                    let mut msg =
+                        subd_types::twitch::TwitchMessage::from_msg(private);
+                    if let Some(emotes) = &emotes {
+                        emotes.inline(&mut msg);
+                    }
+                    tx.send(Event::TwitchChatMessage(msg))?;
+                }
+                ServerMessage::UserNotice(notice) => {
+                    if let Some(notice) =
+                        subd_types::twitch::TwitchUserNotice::from_msg(notice)
+                    {
+                        tx.send(Event::TwitchUserNotice(notice))?;
+                    }
+                }
+                ServerMessage::ClearMsg(clear) => {
+                    tx.send(Event::TwitchChatClear(
+                        TwitchChatClear::from_clear_msg(clear),
+                    ))?;
+                }
+                ServerMessage::ClearChat(clear) => {
+                    tx.send(Event::TwitchChatClear(
+                        TwitchChatClear::from_clear_chat(clear),
                     ))?;
                 }
                 _ => {}
             }
         }
-        Ok(())
     }
 }
 
@@ -97,6 +172,30 @@ impl TwitchMessageHandler {
     pub fn new(pool: sqlx::PgPool, twitch: twitch_service::Service) -> Self {
         Self { pool, twitch }
     }
+
+    /// Keeps the sub tier and months of whoever the notice is about.
+    async fn record_sub(&self, notice: TwitchUserNotice) -> Result<()> {
+        let (user, tier, months) = match notice.kind {
+            UserNoticeKind::Sub { tier } => (notice.user, tier, Some(1)),
+            UserNoticeKind::Resub {
+                tier,
+                cumulative_months,
+                ..
+            } => (notice.user, tier, Some(cumulative_months)),
+            UserNoticeKind::SubGift {
+                tier, recipient, ..
+            } => (recipient, tier, None),
+            _ => return Ok(()),
+        };
+
+        let user_id =
+            upsert_twitch_user(&self.pool, &user.id, &user.login).await?;
+        self.twitch
+            .update_twitch_sub(&user_id, &tier, months)
+            .await?;
+
+        Ok(())
+    }
 }
 
 async fn create_new_user(conn: &sqlx::PgPool) -> Result<UserID> {
@@ -161,17 +260,162 @@ pub async fn save_twitch_message(
     Ok(())
 }
 
+/// Keeps `msg` in `twitch_chat_messages`, with everything Twitch sent along.
+pub async fn archive_twitch_message(
+    pool: &sqlx::PgPool,
+    user_id: &UserID,
+    msg: &TwitchMessage,
+) -> Result<()> {
+    // Same as the IRC emotes tag, where the end is part of the emote
+    let emotes: Vec<String> = msg
+        .emotes
+        .iter()
+        .map(|e| format!("{}:{}-{}", e.id, e.start, e.end.saturating_sub(1)))
+        .collect();
+    let badges: Vec<String> = msg
+        .badges
+        .iter()
+        .map(|b| format!("{}/{}", b.name, b.version))
+        .collect();
+    let (reply_parent_id, reply_parent_login) = match &msg.reply_parent {
+        Some(parent) => (Some(&parent.message_id), Some(&parent.user_login)),
+        None => (None, None),
+    };
+
+    sqlx::query!(
+        r#"INSERT INTO twitch_chat_messages
+           (message_id, channel, channel_id, twitch_user_id, user_login,
+            user_id, contents, is_action, bits, emotes, badges,
+            reply_parent_id, reply_parent_login)
+           VALUES ( $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13 )
+           ON CONFLICT (message_id) DO NOTHING"#,
+        msg.message_id,
+        msg.channel.login,
+        msg.channel.id,
+        msg.sender.id.0,
+        msg.sender.login,
+        user_id.0,
+        msg.text,
+        msg.is_action,
+        msg.bits.map(|bits| bits as i64),
+        &emotes,
+        &badges,
+        reply_parent_id,
+        reply_parent_login,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// How far back timeouts, bans and clears reach. Chat only still shows
+/// messages from about this long ago, anything older was already gone.
+const CLEARED_WITHIN: Duration = Duration::from_secs(60 * 60);
+
+/// Marks the messages a mod took out of chat as deleted. Returns how many
+/// there were.
+pub async fn clear_twitch_messages(
+    pool: &sqlx::PgPool,
+    clear: &TwitchChatClear,
+) -> Result<u64> {
+    let result = match clear {
+        TwitchChatClear::Message { message_id, .. } => {
+            sqlx::query!(
+                r#"UPDATE twitch_chat_messages
+                   SET deleted_at = NOW(), deleted_reason = 'deleted'
+                   WHERE message_id = $1 AND deleted_at IS NULL"#,
+                message_id,
+            )
+            .execute(pool)
+            .await?
+        }
+        TwitchChatClear::User {
+            channel,
+            user,
+            timeout_seconds,
+            ..
+        } => {
+            let reason = match timeout_seconds {
+                Some(_) => "timeout",
+                None => "ban",
+            };
+            sqlx::query!(
+                r#"UPDATE twitch_chat_messages
+                   SET deleted_at = NOW(), deleted_reason = $3
+                   WHERE channel = $1 AND twitch_user_id = $2
+                     AND deleted_at IS NULL
+                     AND created_at > NOW() - make_interval(secs => $4)"#,
+                channel,
+                user.0,
+                reason,
+                CLEARED_WITHIN.as_secs_f64(),
+            )
+            .execute(pool)
+            .await?
+        }
+        TwitchChatClear::All { channel } => {
+            sqlx::query!(
+                r#"UPDATE twitch_chat_messages
+                   SET deleted_at = NOW(), deleted_reason = 'clear'
+                   WHERE channel = $1 AND deleted_at IS NULL
+                     AND created_at > NOW() - make_interval(secs => $2)"#,
+                channel,
+                CLEARED_WITHIN.as_secs_f64(),
+            )
+            .execute(pool)
+            .await?
+        }
+    };
+
+    Ok(result.rows_affected())
+}
+
+pub async fn save_twitch_cheer(
+    pool: &sqlx::PgPool,
+    channel: &str,
+    user_id: &UserID,
+    bits: u64,
+    message: &str,
+) -> Result<()> {
+    sqlx::query!(
+        r#"INSERT INTO twitch_cheers (channel, user_id, bits, message)
+           VALUES ( $1, $2, $3, $4 )"#,
+        channel,
+        user_id.0,
+        bits as i64,
+        message
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
 #[async_trait]
 impl EventHandler for TwitchMessageHandler {
+    fn subscription(&self) -> Subscription {
+        events::only!(TwitchChatMessage, TwitchUserNotice, TwitchChatClear)
+    }
+
     async fn handle(
         mut self: Box<Self>,
         tx: broadcast::Sender<Event>,
         mut rx: broadcast::Receiver<Event>,
     ) -> Result<()> {
         loop {
-            let event = rx.recv().await?;
+            let event = events::recv(&mut rx).await?;
             let msg = match event {
                 Event::TwitchChatMessage(msg) => msg,
+                Event::TwitchUserNotice(notice) => {
+                    self.record_sub(notice).await?;
+                    continue;
+                }
+                Event::TwitchChatClear(clear) => {
+                    clear_twitch_messages(&self.pool, &clear).await?;
+                    continue;
+                }
+                Event::Shutdown => return Ok(()),
                 _ => continue,
             };
 
@@ -189,6 +433,26 @@ impl EventHandler for TwitchMessageHandler {
                 &msg.text,
             )
             .await?;
+            archive_twitch_message(&self.pool, &user_id, &msg).await?;
+
+            if let Some(bits) = msg.bits.filter(|bits| *bits > 0) {
+                save_twitch_cheer(
+                    &self.pool,
+                    &msg.channel.login,
+                    &user_id,
+                    bits,
+                    &msg.text,
+                )
+                .await?;
+
+                tx.send(Event::TwitchCheer(TwitchCheer {
+                    channel: msg.channel.login.clone(),
+                    user_id: user_id.clone(),
+                    user_name: msg.sender.name.clone(),
+                    bits,
+                    message: msg.text.clone(),
+                }))?;
+            }
 
             let user_roles =
                 self.twitch.update_user_roles(&user_id, &msg.roles).await?;
@@ -201,24 +465,26 @@ impl EventHandler for TwitchMessageHandler {
                 roles: user_roles,
                 platform: UserPlatform::Twitch,
                 contents: msg.text,
+                channel: msg.channel.login,
+                message_id: Some(msg.message_id),
+                platform_user_id: Some(msg.sender.id.0),
+                user_login: Some(msg.sender.login),
             }))?;
         }
     }
 }
 
+/// How many subs the broadcaster `token` belongs to has.
 pub async fn get_twitch_sub_count<'a>(
     client: &HelixClient<'a, ReqwestClient>,
     token: UserToken,
-) -> usize {
+) -> Result<usize> {
     let req = GetBroadcasterSubscriptionsRequest::builder()
         .broadcaster_id(token.user_id.clone())
         .first("1".to_string())
         .build();
 
-    let response = client
-        .req_get(req, &token)
-        .await
-        .expect("Error Fetching Twitch Subs");
+    let response = client.req_get(req, &token).await?;
 
-    response.total.unwrap() as usize
+    Ok(response.total.unwrap_or_default().max(0) as usize)
 }
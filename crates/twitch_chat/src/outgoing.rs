@@ -0,0 +1,179 @@
+//! Sending messages to chat without going over Twitch's rate limits.
+//!
+//! Twitch counts every message the bot sends against one limit, whichever
+//! channel it goes to, so every channel shares one `Limiter`. Each channel
+//! still gets its own queue and task, so its replies go out in order.
+//! Messages over the limit wait their turn instead of being dropped by
+//! Twitch.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::credentials::TokenCredentials;
+use subd_types::ChatReply;
+use tokio::sync::{mpsc, Mutex};
+use tracing::warn;
+use twitch_irc::{SecureTCPTransport, TwitchIRCClient};
+
+type Client = TwitchIRCClient<SecureTCPTransport, TokenCredentials>;
+
+/// Twitch drops anything longer than this.
+const MAX_MESSAGE_LENGTH: usize = 500;
+
+/// How many messages the bot can send within `per`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub messages: usize,
+    pub per: Duration,
+}
+
+impl RateLimit {
+    /// What Twitch allows a regular user.
+    pub const USER: Self = Self {
+        messages: 20,
+        per: Duration::from_secs(30),
+    };
+}
+
+/// Sliding window over the messages the bot sent.
+#[derive(Debug)]
+pub struct Limiter {
+    limit: RateLimit,
+    sent: VecDeque<Instant>,
+}
+
+impl Limiter {
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            sent: VecDeque::new(),
+        }
+    }
+
+    /// How long until another message can be sent.
+    pub fn wait(&mut self, now: Instant) -> Duration {
+        while let Some(oldest) = self.sent.front() {
+            if now.saturating_duration_since(*oldest) < self.limit.per {
+                break;
+            }
+            self.sent.pop_front();
+        }
+
+        match self.sent.front() {
+            Some(oldest) if self.sent.len() >= self.limit.messages => {
+                self.limit.per - now.saturating_duration_since(*oldest)
+            }
+            _ => Duration::ZERO,
+        }
+    }
+
+    pub fn record(&mut self, now: Instant) {
+        self.sent.push_back(now);
+    }
+}
+
+/// Queues replies per channel and sends them as fast as the limit allows.
+pub struct Outgoing {
+    client: Client,
+    default_channel: String,
+    limiter: Arc<Mutex<Limiter>>,
+    channels: HashMap<String, mpsc::UnboundedSender<ChatReply>>,
+}
+
+impl Outgoing {
+    pub fn new(client: Client, default_channel: String) -> Self {
+        Self {
+            client,
+            default_channel,
+            limiter: Arc::new(Mutex::new(Limiter::new(RateLimit::USER))),
+            channels: HashMap::new(),
+        }
+    }
+
+    pub fn send(&mut self, reply: ChatReply) {
+        let channel = reply
+            .channel
+            .clone()
+            .unwrap_or_else(|| self.default_channel.clone())
+            .trim_start_matches('#')
+            .to_lowercase();
+
+        let queue = self.channels.entry(channel.clone()).or_insert_with(|| {
+            let (queue, rx) = mpsc::unbounded_channel();
+            tokio::spawn(send_to_channel(
+                self.client.clone(),
+                channel,
+                self.limiter.clone(),
+                rx,
+            ));
+            queue
+        });
+
+        // Only fails if the channel's task is gone, which means the
+        // client is shutting down anyway
+        let _ = queue.send(reply);
+    }
+}
+
+async fn send_to_channel(
+    client: Client,
+    channel: String,
+    limiter: Arc<Mutex<Limiter>>,
+    mut rx: mpsc::UnboundedReceiver<ChatReply>,
+) {
+    while let Some(reply) = rx.recv().await {
+        {
+            // Hold on to the limiter while waiting, so the other channels
+            // queue up behind this message instead of racing it
+            let mut limiter = limiter.lock().await;
+            let wait = limiter.wait(Instant::now());
+            if !wait.is_zero() {
+                tokio::time::sleep(wait).await;
+            }
+            limiter.record(Instant::now());
+        }
+
+        let message = reply.message.chars().take(MAX_MESSAGE_LENGTH).collect();
+        let sent = match &reply.reply_to {
+            Some(message_id) => {
+                client
+                    .say_in_reply_to(
+                        &(channel.as_str(), message_id.as_str()),
+                        message,
+                    )
+                    .await
+            }
+            None => client.say(channel.clone(), message).await,
+        };
+
+        if let Err(err) = sent {
+            warn!(%channel, ?err, "failed to send chat message");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn waits_for_the_oldest_message_to_leave_the_window() {
+        let mut limiter = Limiter::new(RateLimit {
+            messages: 2,
+            per: Duration::from_secs(30),
+        });
+        let start = Instant::now();
+
+        assert_eq!(limiter.wait(start), Duration::ZERO);
+        limiter.record(start);
+        limiter.record(start + Duration::from_secs(10));
+
+        let now = start + Duration::from_secs(20);
+        assert_eq!(limiter.wait(now), Duration::from_secs(10));
+        assert_eq!(
+            limiter.wait(start + Duration::from_secs(30)),
+            Duration::ZERO
+        );
+    }
+}
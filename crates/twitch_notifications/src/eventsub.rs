@@ -0,0 +1,401 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use futures::{SinkExt, StreamExt};
+use serde::Deserialize;
+use subd_types::Event;
+use tokio::net::TcpStream;
+use tokio::sync::broadcast;
+use tokio::time::{timeout_at, Instant};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use tracing::{debug, info, warn};
+
+use crate::notifications;
+use crate::subscriptions::Subscriber;
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// How long to wait for the welcome message, before Twitch has told us how
+/// often to expect a keepalive.
+const WELCOME_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Slack on top of the keepalive timeout, so a slow keepalive isn't mistaken
+/// for a dead socket.
+const KEEPALIVE_GRACE: Duration = Duration::from_secs(5);
+
+/// How often we ping Twitch, on top of the keepalives it sends us.
+const PING_EVERY: Duration = Duration::from_secs(30);
+
+/// How long Twitch gets to answer a ping before the socket counts as dead.
+const PONG_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Twitch can send the same message twice, mostly around reconnects.
+const REMEMBER_MESSAGES: usize = 64;
+
+#[derive(Debug, Deserialize)]
+pub struct Message {
+    pub metadata: Metadata,
+    #[serde(default)]
+    pub payload: Payload,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Metadata {
+    pub message_id: String,
+    pub message_type: MessageType,
+    pub message_timestamp: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageType {
+    SessionWelcome,
+    SessionKeepalive,
+    SessionReconnect,
+    Notification,
+    Revocation,
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct Payload {
+    pub session: Option<Session>,
+    pub subscription: Option<SubscriptionInfo>,
+    pub event: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Session {
+    pub id: String,
+    pub keepalive_timeout_seconds: Option<u64>,
+    pub reconnect_url: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SubscriptionInfo {
+    pub id: String,
+    pub status: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+/// A client for the EventSub websocket.
+pub struct EventSub {
+    url: String,
+}
+
+impl EventSub {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self { url: url.into() }
+    }
+
+    /// Connects, waits for the welcome, and creates the subscriptions for the
+    /// new session through `subscriber`.
+    pub async fn connect(
+        &self,
+        subscriber: &dyn Subscriber,
+    ) -> Result<Connection> {
+        info!(url = %self.url, "connecting to eventsub");
+        let mut socket = connect(&self.url).await?;
+        let session = welcome(&mut socket).await?;
+        subscriber.subscribe(&session.id).await?;
+
+        Ok(Connection::new(socket, &session))
+    }
+}
+
+/// One EventSub session.
+///
+/// Besides waiting on Twitch's keepalives we ping the socket ourselves, so a
+/// connection that died quietly is noticed even between keepalives.
+pub struct Connection {
+    socket: Socket,
+    keepalive: Duration,
+    last_message: Instant,
+    next_ping: Instant,
+    ping_sent: Option<Instant>,
+    seen: VecDeque<String>,
+}
+
+impl Connection {
+    fn new(socket: Socket, session: &Session) -> Self {
+        let now = Instant::now();
+        let mut connection = Self {
+            socket,
+            keepalive: WELCOME_TIMEOUT,
+            last_message: now,
+            next_ping: now,
+            ping_sent: None,
+            seen: VecDeque::with_capacity(REMEMBER_MESSAGES),
+        };
+        connection.start(session);
+        connection
+    }
+
+    /// Resets the timers for a freshly welcomed `session`.
+    fn start(&mut self, session: &Session) {
+        let now = Instant::now();
+        if let Some(seconds) = session.keepalive_timeout_seconds {
+            self.keepalive = Duration::from_secs(seconds) + KEEPALIVE_GRACE;
+        }
+        self.last_message = now;
+        self.next_ping = now + PING_EVERY;
+        self.ping_sent = None;
+    }
+
+    /// Sends every notification on `tx` until the connection is lost.
+    ///
+    /// Returns `Ok` when Twitch closes the socket, and an error when it stops
+    /// talking to us.
+    pub async fn run(&mut self, tx: &broadcast::Sender<Event>) -> Result<()> {
+        loop {
+            let frame =
+                match timeout_at(self.deadline(), next_frame(&mut self.socket))
+                    .await
+                {
+                    Ok(frame) => frame?,
+                    Err(_) => {
+                        self.check_alive().await?;
+                        continue;
+                    }
+                };
+
+            match frame {
+                Frame::Message(message) => {
+                    self.last_message = Instant::now();
+                    self.handle(*message, tx).await?;
+                }
+                Frame::Pong => self.ping_sent = None,
+                Frame::Closed => {
+                    info!("eventsub closed the socket");
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// When we next need to look at the socket, even if nothing came in.
+    fn deadline(&self) -> Instant {
+        let ping = match self.ping_sent {
+            Some(sent) => sent + PONG_TIMEOUT,
+            None => self.next_ping,
+        };
+        ping.min(self.last_message + self.keepalive)
+    }
+
+    /// Gives up on the socket if Twitch has gone quiet, otherwise sends a
+    /// ping when one is due.
+    async fn check_alive(&mut self) -> Result<()> {
+        let now = Instant::now();
+        if now >= self.last_message + self.keepalive {
+            bail!("no message from eventsub in {:?}", self.keepalive);
+        }
+
+        match self.ping_sent {
+            Some(sent) if now >= sent + PONG_TIMEOUT => {
+                bail!("no pong from eventsub in {:?}", PONG_TIMEOUT)
+            }
+            None if now >= self.next_ping => {
+                self.socket.send(tungstenite::Message::Ping(vec![])).await?;
+                self.ping_sent = Some(now);
+                self.next_ping = now + PING_EVERY;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    async fn handle(
+        &mut self,
+        message: Message,
+        tx: &broadcast::Sender<Event>,
+    ) -> Result<()> {
+        if self.seen.contains(&message.metadata.message_id) {
+            return Ok(());
+        }
+        if self.seen.len() == REMEMBER_MESSAGES {
+            self.seen.pop_front();
+        }
+        self.seen.push_back(message.metadata.message_id.clone());
+
+        match message.metadata.message_type {
+            MessageType::SessionWelcome | MessageType::SessionKeepalive => {}
+            MessageType::SessionReconnect => {
+                let url = message
+                    .payload
+                    .session
+                    .and_then(|session| session.reconnect_url)
+                    .context("reconnect message without a url")?;
+                info!(%url, "eventsub asked us to reconnect");
+
+                // Subscriptions follow us to the new session, so there is
+                // nothing to subscribe to again. The old socket stays up until
+                // the new one has said hello.
+                let mut socket = connect(&url).await?;
+                let session = welcome(&mut socket).await?;
+                let mut old = std::mem::replace(&mut self.socket, socket);
+                let _ = old.close(None).await;
+                self.start(&session);
+            }
+            MessageType::Notification => {
+                let (subscription, event) =
+                    match (message.payload.subscription, message.payload.event)
+                    {
+                        (Some(subscription), Some(event)) => {
+                            (subscription, event)
+                        }
+                        _ => {
+                            warn!("eventsub notification without an event");
+                            return Ok(());
+                        }
+                    };
+
+                match notifications::events(
+                    &subscription.kind,
+                    &message.metadata.message_timestamp,
+                    event,
+                ) {
+                    Ok(events) => {
+                        for event in events {
+                            tx.send(event)?;
+                        }
+                    }
+                    Err(err) => warn!(
+                        ?err,
+                        kind = %subscription.kind,
+                        "can't read eventsub notification"
+                    ),
+                }
+            }
+            MessageType::Revocation => {
+                if let Some(subscription) = message.payload.subscription {
+                    warn!(
+                        id = %subscription.id,
+                        kind = %subscription.kind,
+                        status = %subscription.status,
+                        "eventsub subscription revoked"
+                    );
+                }
+            }
+            MessageType::Unknown => {
+                debug!(?message, "unknown eventsub message");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+async fn connect(url: &str) -> Result<Socket> {
+    let (socket, _) = tokio_tungstenite::connect_async(url)
+        .await
+        .with_context(|| format!("can't connect to eventsub at {}", url))?;
+    Ok(socket)
+}
+
+/// Waits for the welcome on a new socket, skipping anything else.
+async fn welcome(socket: &mut Socket) -> Result<Session> {
+    let deadline = Instant::now() + WELCOME_TIMEOUT;
+    loop {
+        let frame = timeout_at(deadline, next_frame(socket))
+            .await
+            .context("no welcome from eventsub")??;
+
+        match frame {
+            Frame::Message(message)
+                if message.metadata.message_type
+                    == MessageType::SessionWelcome =>
+            {
+                let session = message
+                    .payload
+                    .session
+                    .context("welcome message without a session")?;
+                info!(session = %session.id, "eventsub session started");
+                return Ok(session);
+            }
+            Frame::Closed => bail!("eventsub closed the socket before welcome"),
+            _ => continue,
+        }
+    }
+}
+
+enum Frame {
+    Message(Box<Message>),
+    Pong,
+    Closed,
+}
+
+/// The next thing worth looking at on the socket. Pings from Twitch are
+/// answered by tungstenite on its own.
+async fn next_frame(socket: &mut Socket) -> Result<Frame> {
+    while let Some(message) = socket.next().await {
+        match message? {
+            tungstenite::Message::Text(text) => {
+                let message =
+                    serde_json::from_str(&text).with_context(|| {
+                        format!("can't parse eventsub message: {}", text)
+                    })?;
+                return Ok(Frame::Message(Box::new(message)));
+            }
+            tungstenite::Message::Pong(_) => return Ok(Frame::Pong),
+            tungstenite::Message::Close(_) => return Ok(Frame::Closed),
+            _ => continue,
+        }
+    }
+
+    Ok(Frame::Closed)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::testing::{message, serve, subscribed, welcome, Sessions};
+
+    #[tokio::test]
+    async fn subscribes_once_and_follows_reconnects() {
+        let second = serve(vec![vec![
+            welcome("3", "second"),
+            subscribed("4"),
+            // Sent twice, only counted once
+            subscribed("4"),
+        ]])
+        .await;
+        let first = serve(vec![vec![
+            welcome("1", "first"),
+            message(
+                "2",
+                "session_reconnect",
+                json!({
+                    "session": {
+                        "id": "first",
+                        "status": "reconnecting",
+                        "keepalive_timeout_seconds": null,
+                        "reconnect_url": second,
+                    },
+                }),
+            ),
+        ]])
+        .await;
+
+        let (tx, mut rx) = broadcast::channel(16);
+        let subscriber = Sessions::default();
+        let mut connection =
+            EventSub::new(first).connect(&subscriber).await.unwrap();
+        connection.run(&tx).await.unwrap();
+
+        assert_eq!(subscriber.sessions(), vec!["first".to_string()]);
+        match rx.try_recv().unwrap() {
+            Event::TwitchSubscription(sub) => {
+                assert_eq!(sub.display_name(), "NyxKrage")
+            }
+            event => panic!("expected a subscription, got {:?}", event),
+        }
+        assert!(matches!(rx.try_recv(), Ok(Event::RequestTwitchSubCount)));
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -1,158 +1,178 @@
 use anyhow::Result;
-use futures::{SinkExt, StreamExt};
-use subd_types::Event;
+use async_trait::async_trait;
+use events::{Backoff, EventHandler, Subscription};
+use subd_twitch::tokens::TokenStore;
+use subd_types::{ConnectionStatus, Event};
 use tokio::sync::broadcast;
-use tracing::info;
-use twitch_api2::pubsub::{self, Topic};
-
-pub async fn handle_twitch_notifications(
-    tx: broadcast::Sender<Event>,
-    _: broadcast::Receiver<Event>,
-) -> Result<()> {
-    // Listen to subscriptions as well
-
-    // Is it OK cloning the string here?
-    let channel_id = subd_types::consts::get_twitch_broadcaster_channel_id()
-        .parse::<u32>()
-        .unwrap();
-    let subscriptions =
-        pubsub::channel_subscriptions::ChannelSubscribeEventsV1 { channel_id }
-            .into_topic();
-
-    let redeems = pubsub::channel_points::ChannelPointsChannelV1 { channel_id }
-        .into_topic();
-
-    // Create the topic command to send to twitch
-    let command = pubsub::listen_command(
-        &[redeems, subscriptions],
-        Some(subd_types::consts::get_twitch_broadcaster_raw().as_str()),
-        "",
-    )
-    .expect("serializing failed");
-
-    // Send the message with your favorite websocket client
-    info!("trying to connect to stream");
-    let (mut ws_stream, _resp) =
-        tokio_tungstenite::connect_async("wss://pubsub-edge.twitch.tv")
-            .await
-            .expect("asdfasdfasdf");
-
-    ws_stream.send(tungstenite::Message::Text(command)).await?;
-    ws_stream
-        .send(tungstenite::Message::Text(
-            r#"{"type": "PING"}"#.to_string(),
-        ))
-        .await?;
-
-    while let Some(msg) = ws_stream.next().await {
-        match msg {
-            Ok(msg) => {
-                let msg = match msg {
-                    tungstenite::Message::Text(msg) => msg,
-                    _ => continue,
-                };
+use tracing::{info, warn};
+
+mod eventsub;
+mod notifications;
+mod subscriptions;
+#[cfg(test)]
+mod testing;
+pub use eventsub::{Connection, EventSub};
+pub use subscriptions::{HelixSubscriber, Subscriber, SUBSCRIPTIONS};
+
+/// Subs and channel point redemptions, from Twitch EventSub.
+///
+/// Keeps the connection up for as long as the bot runs. Whenever it drops we
+/// wait a bit longer each time, connect again and subscribe to everything on
+/// the new session. Every change is sent as `Event::TwitchConnectionStatus`.
+pub struct TwitchNotifications {
+    eventsub: EventSub,
+    subscriber: Box<dyn Subscriber>,
+    backoff: Backoff,
+}
+
+impl TwitchNotifications {
+    /// Listens for the broadcaster whose token is in `tokens`, at
+    /// `SUBD_TWITCH_EVENTSUB_URL`.
+    pub fn new(tokens: TokenStore) -> Self {
+        Self::with_subscriber(
+            subd_types::consts::get_twitch_eventsub_url(),
+            Box::new(HelixSubscriber::new(tokens)),
+        )
+    }
+
+    pub fn with_subscriber(
+        url: impl Into<String>,
+        subscriber: Box<dyn Subscriber>,
+    ) -> Self {
+        Self {
+            eventsub: EventSub::new(url),
+            subscriber,
+            backoff: Backoff::default(),
+        }
+    }
+
+    /// How long to wait between reconnects.
+    pub fn backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Only returns if the bus is gone.
+    async fn stay_connected(
+        &self,
+        tx: &broadcast::Sender<Event>,
+    ) -> Result<()> {
+        let mut attempt = 0;
+        loop {
+            tx.send(Event::TwitchConnectionStatus(
+                ConnectionStatus::Connecting,
+            ))?;
 
-                let parsed = pubsub::Response::parse(msg.as_str())?;
-                match parsed {
-                    pubsub::Response::Response(resp) => {
-                        info!(response = ?resp, "(current unhandled)");
+            let result =
+                match self.eventsub.connect(self.subscriber.as_ref()).await {
+                    Ok(mut connection) => {
+                        attempt = 0;
+                        tx.send(Event::TwitchConnectionStatus(
+                            ConnectionStatus::Connected,
+                        ))?;
+                        connection.run(tx).await
                     }
-                    pubsub::Response::Message { data } => {
-                        match data {
-                            pubsub::TopicData::ChannelPointsChannelV1 {
-                                topic,
-                                reply,
-                            } => {
-                                use pubsub::channel_points::ChannelPointsChannelV1Reply;
-                                info!(topic = ?topic, "channel point redemption");
-                                match *reply {
-                                    ChannelPointsChannelV1Reply::RewardRedeemed {
-                                        redemption,
-                                        ..
-                                    } => {
-                                        tx.send(Event::TwitchChannelPointsRedeem(redemption))?;
-                                    }
-                                    // ChannelPointsChannelV1Reply::CustomRewardUpdated { timestamp, updated_reward } => todo!(),
-                                    // ChannelPointsChannelV1Reply::RedemptionStatusUpdate { timestamp, redemption } => todo!(),
-                                    // ChannelPointsChannelV1Reply::UpdateRedemptionStatusesFinished { timestamp, progress } => todo!(),
-                                    // ChannelPointsChannelV1Reply::UpdateRedemptionStatusProgress { timestamp, progress } => todo!(),
-                                    // _ => todo!(),
-                                    _ => {}
-                                }
-                            }
-                            pubsub::TopicData::ChannelSubscribeEventsV1 {
-                                topic,
-                                reply,
-                            } => {
-                                info!(topic = ?topic, "subscription event");
-                                tx.send(Event::TwitchSubscription(
-                                    (*reply).into(),
-                                ))?;
-                                tx.send(Event::RequestTwitchSubCount)?;
-                            }
-                            // pubsub::TopicData::ChatModeratorActions { topic, reply } => todo!(),
-                            // pubsub::TopicData::ChannelBitsEventsV2 { topic, reply } => todo!(),
-                            // pubsub::TopicData::ChannelBitsBadgeUnlocks { topic, reply } => todo!(),
-                            // pubsub::TopicData::AutoModQueue { topic, reply } => todo!(),
-                            // pubsub::TopicData::UserModerationNotifications { topic, reply } => todo!(),
-                            _ => continue,
-                        }
+                    Err(err) => Err(err),
+                };
+
+            let reason = match result {
+                Ok(()) => "closed by twitch".to_string(),
+                Err(err) => format!("{:#}", err),
+            };
+            let retry_in = self.backoff.delay(attempt);
+            attempt += 1;
+
+            warn!(%reason, ?retry_in, "lost the eventsub connection");
+            tx.send(Event::TwitchConnectionStatus(
+                ConnectionStatus::Disconnected {
+                    reason,
+                    retry_in_ms: retry_in.as_millis() as u64,
+                },
+            ))?;
+            tokio::time::sleep(retry_in).await;
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for TwitchNotifications {
+    fn subscription(&self) -> Subscription {
+        events::only!(Shutdown)
+    }
+
+    async fn handle(
+        self: Box<Self>,
+        tx: broadcast::Sender<Event>,
+        mut rx: broadcast::Receiver<Event>,
+    ) -> Result<()> {
+        let connection = self.stay_connected(&tx);
+        tokio::pin!(connection);
+
+        loop {
+            tokio::select! {
+                result = &mut connection => return result,
+                event = events::recv(&mut rx) => {
+                    if let Event::Shutdown = event? {
+                        info!("closing the eventsub connection");
+                        return Ok(());
                     }
-                    pubsub::Response::Pong => continue,
-                    pubsub::Response::Reconnect => todo!(),
                 }
             }
-            Err(err) => {
-                println!("Error in twitch notifications: {:?}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::testing::{serve, subscribed, welcome, Sessions};
+
+    #[tokio::test]
+    async fn reconnects_and_subscribes_again() {
+        let url = serve(vec![
+            vec![welcome("1", "first")],
+            vec![welcome("2", "second"), subscribed("3")],
+        ])
+        .await;
+
+        let subscriber = Sessions::default();
+        let handler = TwitchNotifications::with_subscriber(
+            url,
+            Box::new(subscriber.clone()),
+        )
+        .backoff(Backoff {
+            initial: Duration::from_millis(1),
+            max: Duration::from_millis(10),
+            multiplier: 2,
+        });
+
+        let (tx, rx) = broadcast::channel(events::BUS_CAPACITY);
+        let mut bus = tx.subscribe();
+        let running = tokio::spawn(Box::new(handler).handle(tx.clone(), rx));
+
+        let mut statuses = vec![];
+        loop {
+            match bus.recv().await.unwrap() {
+                Event::TwitchConnectionStatus(status) => statuses.push(status),
+                Event::TwitchSubscription(_) => break,
+                _ => {}
             }
         }
+        tx.send(Event::Shutdown).unwrap();
+        running.await.unwrap().unwrap();
 
-        // TODO: Sometimes sub count is a bit late... oh well
-        tx.send(Event::RequestTwitchSubCount)?;
+        assert_eq!(subscriber.sessions(), vec!["first", "second"]);
+        assert!(matches!(
+            statuses[..],
+            [
+                ConnectionStatus::Connecting,
+                ConnectionStatus::Connected,
+                ConnectionStatus::Disconnected { .. },
+                ConnectionStatus::Connecting,
+                ConnectionStatus::Connected,
+            ]
+        ));
     }
-
-    // let ws = TcpListener::bind(TWITCH_PUBSUB_URL.as_str()).await?;
-    // let (mut stream, resp) = tungstenite::connect("wss://pubsub-edge.twitch.tv".to_string())?;
-    // println!("  Response: {:?}", resp);
-    //
-    // while let Ok(msg) = stream.read_message() {
-    //     match msg {
-    //         tungstenite::Message::Text(msg) => {
-    //             let parsed = pubsub::Response::parse(msg.as_str())?;
-    //             match parsed {
-    //                 pubsub::Response::Response(resp) => {
-    //                     println!("[handle_twitch_notifications] got new response: {:?}", resp);
-    //                 }
-    //                 pubsub::Response::Message { data } => {
-    //                     // println!("[handle_twitch_notifications] new msg data: {:?}", data);
-    //                     match data {
-    //                         pubsub::TopicData::ChannelPointsChannelV1 { topic, reply } => {
-    //                             println!("POINTS: {:?}", topic);
-    //                             tx.send(Event::RequestTwitchSubCount)?;
-    //                         }
-    //                         pubsub::TopicData::ChannelSubscribeEventsV1 { topic, reply } => {
-    //                             println!("SUBSCRIBE: {:?}", topic);
-    //                             tx.send(Event::RequestTwitchSubCount)?;
-    //                         }
-    //                         // pubsub::TopicData::ChatModeratorActions { topic, reply } => todo!(),
-    //                         // pubsub::TopicData::ChannelBitsEventsV2 { topic, reply } => todo!(),
-    //                         // pubsub::TopicData::ChannelBitsBadgeUnlocks { topic, reply } => todo!(),
-    //                         // pubsub::TopicData::AutoModQueue { topic, reply } => todo!(),
-    //                         // pubsub::TopicData::UserModerationNotifications { topic, reply } => todo!(),
-    //                         _ => continue,
-    //                     }
-    //                 }
-    //                 pubsub::Response::Pong => continue,
-    //                 pubsub::Response::Reconnect => todo!(),
-    //             }
-    //         }
-    //         _ => {
-    //             println!("unexpected new msg: {:?}", msg);
-    //         }
-    //     }
-    // }
-    //
-    println!("Oh no, exiting");
-
-    Ok(())
 }
@@ -0,0 +1,142 @@
+//! Turns EventSub notifications into `Event`s.
+//!
+//! The rest of the bot still speaks the PubSub types, so the EventSub payloads
+//! get reshaped into those rather than every handler changing at once.
+
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use subd_types::{
+    ChannelSubscribeEventsV1Reply, Event, TwitchSubscriptionEvent,
+};
+use tracing::debug;
+use twitch_api2::pubsub::channel_points::Redemption;
+
+/// The events to send for a notification of type `kind`, sent at `timestamp`.
+pub fn events(kind: &str, timestamp: &str, event: Value) -> Result<Vec<Event>> {
+    let events = match kind {
+        "channel.subscribe" => vec![
+            Event::TwitchSubscription(subscription("sub", timestamp, &event)?),
+            Event::RequestTwitchSubCount,
+        ],
+        "channel.subscription.message" => vec![
+            Event::TwitchSubscription(subscription(
+                "resub", timestamp, &event,
+            )?),
+            Event::RequestTwitchSubCount,
+        ],
+        "channel.channel_points_custom_reward_redemption.add" => {
+            vec![Event::TwitchChannelPointsRedeem(redemption(&event)?)]
+        }
+        _ => {
+            debug!(kind, "unhandled eventsub notification");
+            vec![]
+        }
+    };
+
+    Ok(events)
+}
+
+fn subscription(
+    context: &str,
+    timestamp: &str,
+    event: &Value,
+) -> Result<TwitchSubscriptionEvent> {
+    let message = &event["message"];
+    let emotes: Vec<Value> = message["emotes"]
+        .as_array()
+        .map(|emotes| {
+            emotes
+                .iter()
+                .map(|emote| {
+                    json!({
+                        "id": emote["id"],
+                        "start": emote["begin"],
+                        "end": emote["end"],
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let reply: ChannelSubscribeEventsV1Reply = serde_json::from_value(json!({
+        "context": context,
+        "channel_id": event["broadcaster_user_id"],
+        "channel_name": event["broadcaster_user_login"],
+        "user_id": event["user_id"],
+        "user_name": event["user_login"],
+        "display_name": event["user_name"],
+        "time": timestamp,
+        "sub_plan": event["tier"],
+        "sub_plan_name": "",
+        "is_gift": event["is_gift"].as_bool().unwrap_or(false),
+        "cumulative_months": event["cumulative_months"].as_i64().unwrap_or(1),
+        "streak_months": event["streak_months"],
+        "months": 0,
+        "benefit_end_month": 0,
+        "multi_month_duration": event["duration_months"].as_i64().unwrap_or(0),
+        "sub_message": {
+            "message": message["text"].as_str().unwrap_or_default(),
+            "emotes": emotes,
+        },
+    }))
+    .context("bad subscription notification")?;
+
+    Ok(reply.into())
+}
+
+fn redemption(event: &Value) -> Result<Redemption> {
+    let user_input = match event["user_input"].as_str() {
+        Some("") | None => Value::Null,
+        Some(input) => input.into(),
+    };
+    let status = event["status"]
+        .as_str()
+        .unwrap_or("unfulfilled")
+        .to_uppercase();
+
+    // EventSub only sends the basics of the reward. The rest are filled with
+    // what a plain reward would have.
+    let reward = &event["reward"];
+    serde_json::from_value(json!({
+        "id": event["id"],
+        "channel_id": event["broadcaster_user_id"],
+        "redeemed_at": event["redeemed_at"],
+        "user": {
+            "id": event["user_id"],
+            "login": event["user_login"],
+            "display_name": event["user_name"],
+        },
+        "user_input": user_input,
+        "status": status,
+        "reward": {
+            "id": reward["id"],
+            "channel_id": event["broadcaster_user_id"],
+            "title": reward["title"],
+            "prompt": reward["prompt"],
+            "cost": reward["cost"],
+            "is_user_input_required": !user_input.is_null(),
+            "is_sub_only": false,
+            "image": null,
+            "default_image": null,
+            "background_color": "",
+            "is_enabled": true,
+            "is_paused": false,
+            "is_in_stock": true,
+            "max_per_stream": { "is_enabled": false, "max_per_stream": 0 },
+            "max_per_user_per_stream": {
+                "is_enabled": false,
+                "max_per_user_per_stream": 0,
+            },
+            "global_cooldown": {
+                "is_enabled": false,
+                "global_cooldown_seconds": 0,
+            },
+            "should_redemptions_skip_request_queue": false,
+            "template_id": null,
+            "updated_for_indicator_at": null,
+            "redemptions_redeemed_current_stream": null,
+            "cooldown_expires_at": null,
+        },
+    }))
+    .context("bad redemption notification")
+}
@@ -0,0 +1,109 @@
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use serde_json::json;
+use subd_twitch::tokens::{TokenKind, TokenStore};
+use tracing::{info, warn};
+use twitch_api2::twitch_oauth2::{TwitchToken, UserToken};
+
+/// The EventSub subscription types we listen to, and their versions.
+pub const SUBSCRIPTIONS: &[(&str, &str)] = &[
+    ("channel.subscribe", "1"),
+    ("channel.subscription.message", "1"),
+    ("channel.channel_points_custom_reward_redemption.add", "1"),
+];
+
+/// Creates EventSub subscriptions for a websocket session.
+#[async_trait]
+pub trait Subscriber: Send + Sync {
+    /// Called once the session is welcomed. Websocket subscriptions only last
+    /// as long as the session they were made for. Only fails if nothing could
+    /// be subscribed to at all.
+    async fn subscribe(&self, session_id: &str) -> Result<()>;
+}
+
+/// Subscribes to `SUBSCRIPTIONS` for the broadcaster through Helix. Gets
+/// the broadcaster token again for every session, so a token refreshed while
+/// we were connected is the one used when we connect again.
+pub struct HelixSubscriber {
+    client: reqwest::Client,
+    url: String,
+    tokens: TokenStore,
+}
+
+impl HelixSubscriber {
+    pub fn new(tokens: TokenStore) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: subd_types::consts::get_twitch_helix_url(),
+            tokens,
+        }
+    }
+
+    /// Send subscriptions somewhere other than `SUBD_TWITCH_HELIX_URL`.
+    pub fn url(mut self, url: impl Into<String>) -> Self {
+        self.url = url.into();
+        self
+    }
+
+    async fn subscribe_to(
+        &self,
+        token: &UserToken,
+        body: &serde_json::Value,
+    ) -> Result<()> {
+        let response = self
+            .client
+            .post(format!("{}/eventsub/subscriptions", self.url))
+            .header("Client-Id", token.client_id().as_str())
+            .bearer_auth(token.token().secret())
+            .header("Content-Type", "application/json")
+            .body(body.to_string())
+            .send()
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let error = response.text().await.unwrap_or_default();
+            bail!("{} {}", status, error);
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Subscriber for HelixSubscriber {
+    async fn subscribe(&self, session_id: &str) -> Result<()> {
+        let token = self.tokens.user_token(TokenKind::Broadcaster).await?;
+        let mut subscribed = 0;
+        for (kind, version) in SUBSCRIPTIONS {
+            let body = json!({
+                "type": kind,
+                "version": version,
+                "condition": { "broadcaster_user_id": token.user_id.as_str() },
+                "transport": {
+                    "method": "websocket",
+                    "session_id": session_id,
+                },
+            });
+
+            // A token missing the scope for one type shouldn't cost us the
+            // others. `check_scopes` says which scopes are missing at start.
+            match self.subscribe_to(&token, &body).await {
+                Ok(()) => {
+                    subscribed += 1;
+                    info!(%kind, "subscribed to eventsub");
+                }
+                Err(err) => {
+                    let error = format!("{:#}", err);
+                    warn!(%kind, %error, "can't subscribe to eventsub");
+                }
+            }
+        }
+
+        if subscribed == 0 {
+            bail!("can't subscribe to any eventsub type");
+        }
+
+        Ok(())
+    }
+}
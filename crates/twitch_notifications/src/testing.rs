@@ -0,0 +1,106 @@
+//! A fake EventSub server and subscriber for tests.
+
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::SinkExt;
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+
+use crate::Subscriber;
+
+/// Remembers every session it was asked to subscribe for.
+#[derive(Clone, Default)]
+pub struct Sessions(Arc<Mutex<Vec<String>>>);
+
+impl Sessions {
+    pub fn sessions(&self) -> Vec<String> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl Subscriber for Sessions {
+    async fn subscribe(&self, session_id: &str) -> Result<()> {
+        self.0.lock().unwrap().push(session_id.to_string());
+        Ok(())
+    }
+}
+
+pub fn message(id: &str, kind: &str, payload: Value) -> String {
+    json!({
+        "metadata": {
+            "message_id": id,
+            "message_type": kind,
+            "message_timestamp": "2023-01-12T19:13:30.536153182Z",
+        },
+        "payload": payload,
+    })
+    .to_string()
+}
+
+pub fn welcome(id: &str, session: &str) -> String {
+    message(
+        id,
+        "session_welcome",
+        json!({
+            "session": {
+                "id": session,
+                "status": "connected",
+                "keepalive_timeout_seconds": 10,
+                "reconnect_url": null,
+            },
+        }),
+    )
+}
+
+/// A `channel.subscribe` notification from NyxKrage.
+pub fn subscribed(id: &str) -> String {
+    message(
+        id,
+        "notification",
+        json!({
+            "subscription": {
+                "id": "sub-1",
+                "status": "enabled",
+                "type": "channel.subscribe",
+                "version": "1",
+            },
+            "event": {
+                "user_id": "1234",
+                "user_login": "nyxkrage",
+                "user_name": "NyxKrage",
+                "broadcaster_user_id": "5678",
+                "broadcaster_user_login": "beginbot",
+                "broadcaster_user_name": "beginbot",
+                "tier": "1000",
+                "is_gift": false,
+            },
+        }),
+    )
+}
+
+/// Serves one connection per script: sends it the messages, then hangs up.
+/// Stops listening after the last one.
+pub async fn serve(scripts: Vec<Vec<String>>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+
+    tokio::spawn(async move {
+        for messages in scripts {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut socket =
+                tokio_tungstenite::accept_async(stream).await.unwrap();
+            for message in messages {
+                socket
+                    .send(tungstenite::Message::Text(message))
+                    .await
+                    .unwrap();
+            }
+            let _ = socket.close(None).await;
+        }
+    });
+
+    url
+}
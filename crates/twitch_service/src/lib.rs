@@ -1,5 +1,5 @@
 use anyhow::Result;
-use subd_types::{TwitchUserID, UserID, UserRoles};
+use subd_types::{TwitchSubLevel, TwitchUserID, UserID, UserRoles};
 
 #[allow(dead_code)]
 pub struct TwitchUser {
@@ -46,6 +46,19 @@ impl Service {
         user_id: &UserID,
         user_roles: &UserRoles,
     ) -> Result<UserRoles> {
+        // Prime subs have the same badge as tier 1, so keep what the sub
+        // itself told us.
+        let tier = match user_roles.twitch_sub_level() {
+            Some(TwitchSubLevel::Tier1) => {
+                let current = self.users.get_roles(user_id).await?;
+                match current.as_ref().and_then(|r| r.twitch_sub_level()) {
+                    Some(TwitchSubLevel::Prime) => TwitchSubLevel::Prime,
+                    _ => TwitchSubLevel::Tier1,
+                }
+            }
+            tier => tier.cloned().unwrap_or(TwitchSubLevel::Unknown),
+        };
+
         self.users
             .update_roles(
                 user_id,
@@ -53,8 +66,31 @@ impl Service {
                     is_twitch_mod: Some(user_roles.is_twitch_mod()),
                     is_twitch_vip: Some(user_roles.is_twitch_vip()),
                     is_twitch_founder: Some(user_roles.is_twitch_founder()),
-                    is_twitch_sub: Some(user_roles.is_twitch_sub()),
                     is_twitch_staff: Some(user_roles.is_twitch_staff()),
+                    twitch_sub_tier: Some(
+                        user_roles.is_twitch_sub().then_some(tier),
+                    ),
+                    twitch_sub_months: Some(user_roles.sub_months as i32),
+                    ..Default::default()
+                },
+            )
+            .await
+    }
+
+    /// Records a sub from a sub or resub notice, which unlike chat badges
+    /// knows about Prime. `months` is left alone when `None`, like for gifts.
+    pub async fn update_twitch_sub(
+        &self,
+        user_id: &UserID,
+        tier: &TwitchSubLevel,
+        months: Option<u64>,
+    ) -> Result<UserRoles> {
+        self.users
+            .update_roles(
+                user_id,
+                user_service::UserRolesUpdate {
+                    twitch_sub_tier: Some(Some(tier.clone())),
+                    twitch_sub_months: months.map(|months| months as i32),
                     ..Default::default()
                 },
             )
@@ -27,10 +27,7 @@ impl Service {
         todo!()
     }
 
-    pub async fn get_roles(
-        &mut self,
-        id: &UserID,
-    ) -> Result<Option<UserRoles>> {
+    pub async fn get_roles(&self, id: &UserID) -> Result<Option<UserRoles>> {
         Ok(models::user_roles::Model::read(&self.pool, id.0)
             .await?
             .map(models::user_roles::to_user_roles))
@@ -3,6 +3,7 @@ use anyhow::Result;
 
 use sqlx::PgPool;
 use subd_macros::database_model;
+use subd_types::TwitchSubLevel;
 use subd_types::UserPlatform;
 use subd_types::UserRoles;
 
@@ -52,14 +53,17 @@ pub mod user_roles {
         pub is_twitch_mod: bool,
         pub is_twitch_vip: bool,
         pub is_twitch_founder: bool,
-        pub is_twitch_sub: bool,
         pub is_twitch_staff: bool,
+        #[type_override]
+        pub twitch_sub_tier: Option<TwitchSubLevel>,
+        pub twitch_sub_months: i32,
     }
 
     pub fn to_user_roles(m: Model) -> UserRoles {
         // Map to roles
         let mut roles = UserRoles {
             roles: std::collections::HashSet::new(),
+            sub_months: m.twitch_sub_months.max(0) as u64,
         };
 
         if m.is_github_sponsor {
@@ -84,11 +88,8 @@ pub mod user_roles {
             roles.add_role(subd_types::Role::TwitchFounder);
         }
 
-        // TODO: Handle subs correctly
-        if m.is_twitch_sub {
-            roles.add_role(subd_types::Role::TwitchSub(
-                subd_types::TwitchSubLevel::Unknown,
-            ));
+        if let Some(tier) = m.twitch_sub_tier {
+            roles.add_role(subd_types::Role::TwitchSub(tier));
         }
 
         roles
@@ -104,8 +105,9 @@ impl user_roles::Model {
             is_twitch_mod: false,
             is_twitch_vip: false,
             is_twitch_founder: false,
-            is_twitch_sub: false,
             is_twitch_staff: false,
+            twitch_sub_tier: None,
+            twitch_sub_months: 0,
         }
     }
 
@@ -134,8 +136,9 @@ impl user_roles::Model {
                 is_twitch_mod,
                 is_twitch_vip,
                 is_twitch_founder,
-                is_twitch_sub,
-                is_twitch_staff
+                is_twitch_staff,
+                twitch_sub_tier,
+                twitch_sub_months
             ) VALUES (
                 $1,
                 $2,
@@ -143,15 +146,17 @@ impl user_roles::Model {
                 $4,
                 $5,
                 $6,
-                $7
+                $7,
+                $8
             )  ON CONFLICT (user_id) DO UPDATE
             SET 
                 is_github_sponsor = EXCLUDED.is_github_sponsor,
                 is_twitch_mod = EXCLUDED.is_twitch_mod,
                 is_twitch_vip = EXCLUDED.is_twitch_vip,
                 is_twitch_founder = EXCLUDED.is_twitch_founder,
-                is_twitch_sub = EXCLUDED.is_twitch_sub,
-                is_twitch_staff = EXCLUDED.is_twitch_staff
+                is_twitch_staff = EXCLUDED.is_twitch_staff,
+                twitch_sub_tier = EXCLUDED.twitch_sub_tier,
+                twitch_sub_months = EXCLUDED.twitch_sub_months
 
              RETURNING 
                 user_id,
@@ -159,16 +164,18 @@ impl user_roles::Model {
                 is_twitch_mod,
                 is_twitch_vip,
                 is_twitch_founder,
-                is_twitch_sub,
-                is_twitch_staff
+                is_twitch_staff,
+                twitch_sub_tier as \"twitch_sub_tier: TwitchSubLevel\",
+                twitch_sub_months
             ",
             self.user_id,
             self.is_github_sponsor,
             self.is_twitch_mod,
             self.is_twitch_vip,
             self.is_twitch_founder,
-            self.is_twitch_sub,
             self.is_twitch_staff,
+            self.twitch_sub_tier as _,
+            self.twitch_sub_months,
         )
         .fetch_one(conn)
         .await?)
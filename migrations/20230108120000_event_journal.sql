@@ -0,0 +1,14 @@
+-- Every event that goes over the bus, so a stream can be replayed later
+CREATE TABLE event_journal (
+  event_journal_id BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+
+  -- One session per run of the bot
+  session_id  UUID NOT NULL,
+  -- Milliseconds since the session started, used to pace replays
+  offset_ms   BIGINT NOT NULL,
+  event_type  TEXT NOT NULL,
+  event       JSONB NOT NULL,
+  created_at  TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX event_journal_session_idx ON event_journal (session_id, offset_ms);
@@ -0,0 +1,30 @@
+-- How often chat commands can be used. The '*' row is the global limit,
+-- shared by every command.
+CREATE TABLE command_cooldowns (
+  command         TEXT PRIMARY KEY,
+  -- Between two uses by anyone
+  window_ms       BIGINT NOT NULL DEFAULT 0,
+  -- Between two uses by the same user, see cooldown_bursts
+  user_window_ms  BIGINT NOT NULL DEFAULT 0,
+  updated_at      TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+);
+
+-- How many times each role can use a command within its user window.
+-- Mods and the broadcaster are never throttled.
+CREATE TABLE cooldown_bursts (
+  role        TEXT PRIMARY KEY,
+  burst       INT NOT NULL,
+  updated_at  TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+);
+
+INSERT INTO command_cooldowns (command, window_ms, user_window_ms) VALUES
+  ('*', 0, 2000),
+  ('!random', 5000, 30000),
+  ('!voice', 5000, 30000),
+  ('!spin', 3000, 15000),
+  ('!blur', 3000, 15000);
+
+INSERT INTO cooldown_bursts (role, burst) VALUES
+  ('everyone', 1),
+  ('subscriber', 2),
+  ('vip', 3);
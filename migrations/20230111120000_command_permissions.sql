@@ -0,0 +1,19 @@
+-- Lets a user run a command their roles wouldn't allow, granted by the
+-- broadcaster from chat with !grant
+CREATE TABLE command_permission_grants (
+  username    TEXT NOT NULL,
+  command     TEXT NOT NULL,
+  granted_by  TEXT NOT NULL,
+  created_at  TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+
+  PRIMARY KEY (username, command)
+);
+
+-- Every time someone tried to run a command they weren't allowed to
+CREATE TABLE command_refusals (
+  command_refusal_id BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+  username    TEXT NOT NULL,
+  command     TEXT NOT NULL,
+  required    TEXT NOT NULL,
+  created_at  TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+);
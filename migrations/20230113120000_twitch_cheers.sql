@@ -0,0 +1,31 @@
+-- Every cheer from Twitch chat, for the leaderboards. channel is the login of
+-- the channel it was cheered in.
+CREATE TABLE twitch_cheers (
+  twitch_cheer_id BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+  user_id     UUID NOT NULL references users,
+  channel     TEXT NOT NULL,
+  bits        BIGINT NOT NULL,
+  message     TEXT NOT NULL,
+  created_at  TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX twitch_cheers_user_id ON twitch_cheers (user_id);
+CREATE INDEX twitch_cheers_channel ON twitch_cheers (channel);
+
+-- What happens when someone cheers at least min_bits. Only the biggest tier
+-- they reach is used, and any of its reactions can be left out.
+CREATE TABLE cheer_tiers (
+  min_bits    BIGINT PRIMARY KEY,
+  -- A file in ./MP3s, without the .mp3
+  sound       TEXT,
+  -- One of obs_combo::COMBOS
+  obs_combo   TEXT,
+  -- The UberDuck voice that reads out the cheer
+  voice       TEXT,
+  updated_at  TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+);
+
+INSERT INTO cheer_tiers (min_bits, sound, obs_combo, voice) VALUES
+  (100, 'bits', NULL, NULL),
+  (500, 'bits', 'blur', NULL),
+  (1000, NULL, 'staff', 'Randall');
@@ -0,0 +1,16 @@
+-- What a channel point reward does when it's redeemed. reward is the reward's
+-- id or its title, the id wins when both are mapped.
+CREATE TABLE channel_point_rewards (
+  reward      TEXT PRIMARY KEY,
+  -- One of scene, filter, sound, tts or hotkey
+  action      TEXT NOT NULL,
+  -- The scene, the source with the filter, the sound in ./MP3s (without the
+  -- .mp3), the UberDuck voice, or the OBS hotkey
+  target      TEXT NOT NULL,
+  -- Only for filter
+  filter      TEXT,
+  updated_at  TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+);
+
+INSERT INTO channel_point_rewards (reward, action, target, filter) VALUES
+  ('Mandatory Crab Dance', 'scene', 'PC - Crab Rave', NULL);
@@ -0,0 +1,13 @@
+-- Every channel point redemption and how it ended up.
+CREATE TABLE channel_point_redemptions (
+  redemption_id  TEXT PRIMARY KEY,
+  reward_id      TEXT NOT NULL,
+  reward_title   TEXT NOT NULL,
+  user_login     TEXT NOT NULL,
+  user_input     TEXT,
+  -- FULFILLED, CANCELED (refunded) or UNMAPPED (no action set up for it)
+  status         TEXT NOT NULL,
+  -- Why it was refunded, or why Twitch wouldn't take the status
+  error          TEXT,
+  created_at     TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,17 @@
+-- Twitch subs get their tier and months instead of only a yes or no.
+-- twitch_sub_tier is NULL when not subbed.
+CREATE TYPE twitch_sub_level AS ENUM (
+  'UNKNOWN',
+  'PRIME',
+  'TIER1',
+  'TIER2',
+  'TIER3'
+);
+
+ALTER TABLE user_roles
+  ADD COLUMN twitch_sub_tier   twitch_sub_level,
+  ADD COLUMN twitch_sub_months INT NOT NULL DEFAULT 0;
+
+UPDATE user_roles SET twitch_sub_tier = 'UNKNOWN' WHERE is_twitch_sub;
+
+ALTER TABLE user_roles DROP COLUMN is_twitch_sub;
@@ -0,0 +1,19 @@
+-- Stream state, voices and command grants are kept per Twitch channel, by
+-- login. An empty channel is the default, used by every channel that doesn't
+-- have its own.
+ALTER TABLE twitch_stream_state
+  ADD COLUMN channel TEXT NOT NULL DEFAULT '',
+  ADD CONSTRAINT twitch_stream_state_channel_key UNIQUE (channel);
+
+ALTER TABLE user_stream_character_information
+  ADD COLUMN channel TEXT NOT NULL DEFAULT '',
+  DROP CONSTRAINT user_stream_character_information_username_key,
+  ADD CONSTRAINT user_stream_character_information_channel_username_key
+    UNIQUE (channel, username);
+
+ALTER TABLE command_permission_grants
+  ADD COLUMN channel TEXT NOT NULL DEFAULT '',
+  DROP CONSTRAINT command_permission_grants_pkey,
+  ADD PRIMARY KEY (channel, username, command);
+
+ALTER TABLE command_refusals ADD COLUMN channel TEXT NOT NULL DEFAULT '';
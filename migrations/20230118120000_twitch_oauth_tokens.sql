@@ -0,0 +1,12 @@
+-- The Twitch tokens currently in use, kept up to date as they're refreshed.
+CREATE TABLE twitch_oauth_tokens (
+  -- broadcaster or bot
+  kind           TEXT PRIMARY KEY,
+  login          TEXT NOT NULL,
+  user_id        TEXT NOT NULL,
+  access_token   TEXT NOT NULL,
+  refresh_token  TEXT,
+  scopes         TEXT[] NOT NULL DEFAULT '{}',
+  expires_at     TIMESTAMPTZ NOT NULL,
+  updated_at     TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,44 @@
+-- What moderation does when someone with `role` breaks a rule. Roles
+-- without a row for a violation get away with it, and mods and the
+-- broadcaster are never moderated.
+CREATE TABLE moderation_rules (
+  -- everyone, subscriber or vip
+  role             TEXT NOT NULL,
+  -- profanity, links, caps or repeated
+  violation        TEXT NOT NULL,
+  -- warn, delete or timeout
+  action           TEXT NOT NULL,
+  timeout_seconds  INT NOT NULL DEFAULT 0,
+  updated_at       TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (role, violation)
+);
+
+INSERT INTO moderation_rules (role, violation, action, timeout_seconds) VALUES
+  ('everyone', 'profanity', 'timeout', 600),
+  ('everyone', 'links', 'delete', 0),
+  ('everyone', 'repeated', 'delete', 0),
+  ('everyone', 'caps', 'warn', 0),
+  ('subscriber', 'profanity', 'delete', 0),
+  ('subscriber', 'repeated', 'warn', 0),
+  ('vip', 'profanity', 'delete', 0);
+
+-- Everything moderation did, and whether a mod took it back.
+CREATE TABLE moderation_log (
+  moderation_log_id  BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+  channel            TEXT NOT NULL,
+  user_login         TEXT NOT NULL,
+  -- The Twitch user id, to pardon them with
+  user_id            TEXT,
+  message_id         TEXT,
+  message            TEXT NOT NULL,
+  violation          TEXT NOT NULL,
+  action             TEXT NOT NULL,
+  timeout_seconds    INT NOT NULL DEFAULT 0,
+  -- Why Twitch wouldn't take the action, if it didn't
+  error              TEXT,
+  pardoned_by        TEXT,
+  pardoned_at        TIMESTAMPTZ,
+  created_at         TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX moderation_log_user ON moderation_log (channel, user_login);
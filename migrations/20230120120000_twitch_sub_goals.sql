@@ -0,0 +1,9 @@
+-- Sub goals, the latest one is shown on the overlay. A target of 0 means
+-- there's no goal right now.
+CREATE TABLE twitch_sub_goals (
+  twitch_sub_goal_id  BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+  title               TEXT NOT NULL,
+  target              INT NOT NULL,
+  set_by              TEXT NOT NULL,
+  created_at          TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+);
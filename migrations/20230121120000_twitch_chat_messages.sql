@@ -0,0 +1,29 @@
+-- Everything said in Twitch chat, with what user_messages leaves out.
+CREATE TABLE twitch_chat_messages (
+  message_id          TEXT PRIMARY KEY,
+  channel             TEXT NOT NULL,
+  channel_id          TEXT NOT NULL,
+  twitch_user_id      TEXT NOT NULL,
+  user_login          TEXT NOT NULL,
+  user_id             UUID NOT NULL references users,
+  contents            TEXT NOT NULL,
+  -- Sent with /me
+  is_action           BOOLEAN NOT NULL DEFAULT FALSE,
+  bits                BIGINT,
+  -- Like "25:0-4", the emote id and the chars it covers, end included
+  emotes              TEXT[] NOT NULL DEFAULT '{}',
+  -- Like "subscriber/3012"
+  badges              TEXT[] NOT NULL DEFAULT '{}',
+  reply_parent_id     TEXT,
+  reply_parent_login  TEXT,
+  -- Set when a mod deleted it, or cleared the user or the whole chat
+  deleted_at          TIMESTAMPTZ,
+  -- deleted, timeout, ban or clear
+  deleted_reason      TEXT,
+  created_at          TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX twitch_chat_messages_channel_user
+  ON twitch_chat_messages (channel, twitch_user_id);
+CREATE INDEX twitch_chat_messages_created_at
+  ON twitch_chat_messages (created_at);
@@ -1,7 +1,26 @@
+use crate::obs::OBSOperations;
 use anyhow::Result;
 use rodio::cpal::traits::{DeviceTrait, HostTrait};
 use rodio::OutputStream;
 use rodio::*;
+use std::fs::File;
+use std::io::BufReader;
+
+/// Somewhere to play sounds. Implemented for `rodio::Sink`, and by
+/// `testing::FakeAudio` so handlers can be tested without a sound card.
+pub trait AudioOutput: Send {
+    /// Play the file at `path`, blocking until it is done playing.
+    fn play_file(&self, path: &str) -> Result<()>;
+}
+
+impl AudioOutput for Sink {
+    fn play_file(&self, path: &str) -> Result<()> {
+        let file = BufReader::new(File::open(path)?);
+        self.append(Decoder::new(file)?);
+        self.sleep_until_end();
+        Ok(())
+    }
+}
 
 pub fn get_output_stream(
     device_name: &str,
@@ -27,7 +46,7 @@ pub fn get_output_stream(
 // ============== //
 
 pub async fn set_audio_status(
-    _obs_conn: &obws::Client,
+    _obs_conn: &dyn OBSOperations,
     _name: &str,
     _status: bool,
 ) -> Result<()> {
@@ -1,72 +1,63 @@
 use anyhow::Result;
 use async_trait::async_trait;
-use events::EventHandler;
-use obws::Client as OBSClient;
-use rodio::Decoder;
-use rodio::*;
-use serde::{Deserialize, Serialize};
+use events::{EventHandler, RestartPolicy, Subscription};
 use server::audio;
+use server::channels;
+use server::cooldowns::Cooldowns;
+use server::journal;
 use server::move_transition;
+use server::obs::OBSOperations;
 use server::obs_combo;
+use server::obs_connection::{ObsConnection, ObsConnectionHandler};
 use server::obs_hotkeys;
 use server::obs_routing;
 use server::obs_source;
-use server::twitch_stream_state;
+use server::permissions::Grants;
+use server::soundboard::SoundHandler;
 use server::uberduck;
-use std::collections::HashSet;
-use std::fs;
-use std::fs::File;
-use std::io::BufReader;
-use std::thread;
+use std::collections::HashMap;
 use std::time;
-use subd_db::get_db_pool;
+use subd_twitch::tokens::TokenStore;
 use subd_types::Event;
-use subd_types::TransformOBSTextRequest;
-use subd_types::UberDuckRequest;
 use tokio::sync::broadcast;
 use tracing_subscriber;
 use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::EnvFilter;
 
-pub struct OBSMessageHandler {
-    obs_client: OBSClient,
-    pool: sqlx::PgPool,
-}
-
 pub struct TriggerHotkeyHandler {
-    obs_client: OBSClient,
+    obs_client: Box<dyn OBSOperations>,
 }
 
 pub struct StreamCharacterHandler {
-    obs_client: OBSClient,
+    obs_client: Box<dyn OBSOperations>,
 }
 
 pub struct SourceVisibilityHandler {
-    obs_client: OBSClient,
+    obs_client: Box<dyn OBSOperations>,
 }
 
 pub struct TransformOBSTextHandler {
-    obs_client: OBSClient,
-}
-
-pub struct SoundHandler {
-    sink: Sink,
-    pool: sqlx::PgPool,
+    obs_client: Box<dyn OBSOperations>,
 }
 
 // ================================================================================================
 
 #[async_trait]
 impl EventHandler for SourceVisibilityHandler {
+    fn subscription(&self) -> Subscription {
+        events::only!(SourceVisibilityRequest)
+    }
+
     async fn handle(
         self: Box<Self>,
         _tx: broadcast::Sender<Event>,
         mut rx: broadcast::Receiver<Event>,
     ) -> Result<()> {
         loop {
-            let event = rx.recv().await?;
+            let event = events::recv(&mut rx).await?;
             let msg = match event {
                 Event::SourceVisibilityRequest(msg) => msg,
+                Event::Shutdown => return Ok(()),
                 _ => continue,
             };
 
@@ -74,7 +65,7 @@ impl EventHandler for SourceVisibilityHandler {
                 &msg.scene,
                 &msg.source,
                 msg.enabled,
-                &self.obs_client,
+                self.obs_client.as_ref(),
             )
             .await;
         }
@@ -83,21 +74,26 @@ impl EventHandler for SourceVisibilityHandler {
 
 #[async_trait]
 impl EventHandler for StreamCharacterHandler {
+    fn subscription(&self) -> Subscription {
+        events::only!(StreamCharacterRequest)
+    }
+
     async fn handle(
         self: Box<Self>,
         _tx: broadcast::Sender<Event>,
         mut rx: broadcast::Receiver<Event>,
     ) -> Result<()> {
         loop {
-            let event = rx.recv().await?;
+            let event = events::recv(&mut rx).await?;
             let msg = match event {
                 Event::StreamCharacterRequest(msg) => msg,
+                Event::Shutdown => return Ok(()),
                 _ => continue,
             };
 
             let _ = obs_combo::trigger_character_filters(
                 &msg.source,
-                &self.obs_client,
+                self.obs_client.as_ref(),
                 msg.enabled,
             )
             .await;
@@ -107,34 +103,45 @@ impl EventHandler for StreamCharacterHandler {
 
 #[async_trait]
 impl EventHandler for TriggerHotkeyHandler {
+    fn subscription(&self) -> Subscription {
+        events::only!(TriggerHotkeyRequest)
+    }
+
     async fn handle(
         self: Box<Self>,
         _tx: broadcast::Sender<Event>,
         mut rx: broadcast::Receiver<Event>,
     ) -> Result<()> {
         loop {
-            let event = rx.recv().await?;
+            let event = events::recv(&mut rx).await?;
             let msg = match event {
                 Event::TriggerHotkeyRequest(msg) => msg,
+                Event::Shutdown => return Ok(()),
                 _ => continue,
             };
 
-            obs_hotkeys::trigger_hotkey(&msg.hotkey, &self.obs_client).await?;
+            obs_hotkeys::trigger_hotkey(&msg.hotkey, self.obs_client.as_ref())
+                .await?;
         }
     }
 }
 
 #[async_trait]
 impl EventHandler for TransformOBSTextHandler {
+    fn subscription(&self) -> Subscription {
+        events::only!(TransformOBSTextRequest)
+    }
+
     async fn handle(
         self: Box<Self>,
         _tx: broadcast::Sender<Event>,
         mut rx: broadcast::Receiver<Event>,
     ) -> Result<()> {
         loop {
-            let event = rx.recv().await?;
+            let event = events::recv(&mut rx).await?;
             let msg = match event {
                 Event::TransformOBSTextRequest(msg) => msg,
+                Event::Shutdown => return Ok(()),
                 _ => continue,
             };
 
@@ -143,198 +150,24 @@ impl EventHandler for TransformOBSTextHandler {
                 &msg.text_source,
                 &filter_name,
                 &msg.message,
-                &self.obs_client,
+                self.obs_client.as_ref(),
             )
             .await;
         }
     }
 }
 
-// ================================================================================================
-
-#[derive(Serialize, Deserialize, Default, Debug)]
-pub struct Character {
-    pub voice: Option<String>,
-    pub source: Option<String>,
-}
-
-// Looks through raw-text to either play TTS or play soundeffects
-#[async_trait]
-impl EventHandler for SoundHandler {
-    async fn handle(
-        self: Box<Self>,
-        tx: broadcast::Sender<Event>,
-        mut rx: broadcast::Receiver<Event>,
-    ) -> Result<()> {
-        let paths = fs::read_dir("./MP3s").unwrap();
-        let mut mp3s: HashSet<String> = vec![].into_iter().collect();
-        for path in paths {
-            mp3s.insert(path.unwrap().path().display().to_string());
-        }
-
-        loop {
-            let event = rx.recv().await?;
-            let msg = match event {
-                Event::UserMessage(msg) => {
-                    // TODO: Add a list here
-                    if msg.user_name == "Nightbot" {
-                        continue;
-                    }
-                    msg
-                }
-                _ => continue,
-            };
-            let spoken_string = msg.contents.clone();
-            let voice_text = msg.contents.to_string();
-            let speech_bubble_text = uberduck::chop_text(spoken_string);
-
-            // Anything less than 3 words we don't use
-            let split = voice_text.split(" ");
-            let vec = split.collect::<Vec<&str>>();
-            if vec.len() < 2 {
-                continue;
-            };
-
-            let stream_character =
-                uberduck::build_stream_character(&self.pool, &msg.user_name)
-                    .await?;
-
-            let state =
-                twitch_stream_state::get_twitch_state(&self.pool).await?;
-
-            let mut character = Character {
-                ..Default::default()
-            };
-
-            // This is all about how to respond to messages from various
-            // types of users
-            if msg.roles.is_twitch_staff() {
-                character.voice =
-                    Some(server::obs::TWITCH_STAFF_OBS_SOURCE.to_string());
-                character.source =
-                    Some(server::obs::TWITCH_STAFF_VOICE.to_string());
-            } else if msg.roles.is_twitch_mod() {
-                character.voice =
-                    Some(server::obs::TWITCH_MOD_DEFAULT_VOICE.to_string());
-            } else if msg.roles.is_twitch_sub() {
-                character.voice = Some(stream_character.voice.clone());
-            } else if !state.sub_only_tts {
-                // This is what everyone get's to speak with
-                // if we are allowing non-subs to speak
-                character.voice = Some(stream_character.voice.clone());
-            }
-
-            // If we have a voice assigned, then we fire off an UberDuck Request
-            match character.voice {
-                Some(voice) => {
-                    let _ = tx.send(Event::UberDuckRequest(UberDuckRequest {
-                        voice,
-                        message: speech_bubble_text,
-                        voice_text,
-                        username: msg.user_name,
-                        source: character.source,
-                    }));
-                }
-                None => {}
-            }
-
-            // If we have the implicit_soundeffects enabled
-            // we go past this!
-            if !state.implicit_soundeffects {
-                continue;
-            }
-
-            let splitmsg = msg
-                .contents
-                .split(" ")
-                .map(|s| s.to_string())
-                .collect::<Vec<String>>();
-
-            let text_source =
-                server::obs::SOUNDBOARD_TEXT_SOURCE_NAME.to_string();
-
-            for word in splitmsg {
-                let sanitized_word = word.as_str().to_lowercase();
-                let full_name = format!("./MP3s/{}.mp3", sanitized_word);
-
-                if mp3s.contains(&full_name) {
-                    let _ = tx.send(Event::TransformOBSTextRequest(
-                        TransformOBSTextRequest {
-                            message: sanitized_word.clone(),
-                            text_source: text_source.to_string(),
-                        },
-                    ));
-
-                    let file = BufReader::new(
-                        File::open(format!("./MP3s/{}.mp3", sanitized_word))
-                            .unwrap(),
-                    );
-
-                    self.sink
-                        .append(Decoder::new(BufReader::new(file)).unwrap());
-
-                    self.sink.sleep_until_end();
-
-                    // TODO: Look into using these!
-                    // self.sink.volume()
-                    // self.sink.set_volume()
-                    // self.sink.len()
-
-                    // We need this so we can allow to trigger the next word in OBS
-                    // TODO: We should abstract
-                    // and figure out a better way of determine the time
-                    let sleep_time = time::Duration::from_millis(100);
-                    thread::sleep(sleep_time);
-                }
-            }
-
-            // This clears the OBS Text
-            let _ = tx.send(Event::TransformOBSTextRequest(
-                TransformOBSTextRequest {
-                    message: "".to_string(),
-                    text_source: text_source.to_string(),
-                },
-            ));
-        }
-    }
-}
-
-#[async_trait]
-impl EventHandler for OBSMessageHandler {
-    async fn handle(
-        self: Box<Self>,
-        tx: broadcast::Sender<Event>,
-        mut rx: broadcast::Receiver<Event>,
-    ) -> Result<()> {
-        loop {
-            let event = rx.recv().await?;
-            let msg = match event {
-                Event::UserMessage(msg) => msg,
-                _ => continue,
-            };
-            let splitmsg = msg
-                .contents
-                .split(" ")
-                .map(|s| s.to_string())
-                .collect::<Vec<String>>();
-
-            match obs_routing::handle_obs_commands(
-                &tx,
-                &self.obs_client,
-                &self.pool,
-                splitmsg,
-                msg,
-            )
-            .await
-            {
-                Ok(_) => {}
-                Err(err) => {
-                    eprintln!("Error: {err}");
-                    continue;
-                }
-            }
-        }
-    }
+/// The channels' OBS connections, as the handlers take them.
+fn channel_clients(
+    connections: &HashMap<String, ObsConnection>,
+) -> HashMap<String, Box<dyn OBSOperations>> {
+    connections
+        .iter()
+        .map(|(login, connection)| {
+            let client: Box<dyn OBSOperations> = Box::new(connection.clone());
+            (login.clone(), client)
+        })
+        .collect()
 }
 
 // ==== //
@@ -372,6 +205,9 @@ async fn main() -> Result<()> {
     // Create 1 Event Loop
     // Push handles onto the loop
     // those handlers are things like twitch-chat, twitch-sub, github-sponsor etc.
+    //
+    // Handlers are built from factories, so if one dies mid-stream (OBS
+    // restarted, a DB query blew up) the loop builds a new one and keeps going.
     let mut event_loop = events::EventLoop::new();
 
     // You can clone this
@@ -380,58 +216,306 @@ async fn main() -> Result<()> {
     // This is useful because you need no lifetimes
     let pool = subd_db::get_db_pool().await;
 
+    // Record everything that goes over the bus, see the replay binary
+    let session = journal::Session::new();
+    println!("Journaling events for session {}", session.id);
+    let p = pool.clone();
+    event_loop
+        .supervise("journal", RestartPolicy::default(), move || {
+            let pool = p.clone();
+            async move { Ok(journal::JournalHandler::new(pool, session)) }
+        })
+        .with_queue(1024);
+
+    // Twitch tokens live in the DB from here on, and get refreshed before
+    // they expire. Say up front what won't work with the scopes we have.
+    let tokens = TokenStore::new(pool.clone());
+    server::twitch_tokens::check_scopes(&tokens).await;
+    let t = tokens.clone();
+    event_loop.supervise(
+        "twitch_tokens",
+        RestartPolicy::default(),
+        move || {
+            let tokens = t.clone();
+            async move {
+                Ok(server::twitch_tokens::TokenRefreshHandler::new(tokens))
+            }
+        },
+    );
+
+    let joined = channels::load_channels(channels::CHANNELS_FILE)?;
+    println!("Joining {}", channels::logins(&joined).join(", "));
+
     // Turns twitch IRC things into our message events
-    event_loop.push(twitch_chat::TwitchChat::new(
-        pool.clone(),
-        "beginbot".to_string(),
-    )?);
+    let (p, t, logins) =
+        (pool.clone(), tokens.clone(), channels::logins(&joined));
+    event_loop.supervise("twitch_chat", RestartPolicy::default(), move || {
+        let (pool, tokens, logins) = (p.clone(), t.clone(), logins.clone());
+        async move {
+            Ok(twitch_chat::TwitchChat::new(pool, logins, tokens)?
+                .emotes(twitch_chat::emotes::EmoteMap::with_defaults()))
+        }
+    });
 
     // Does stuff with twitch messages
-    event_loop.push(twitch_chat::TwitchMessageHandler::new(
-        pool.clone(),
-        twitch_service::Service::new(
-            pool.clone(),
-            user_service::Service::new(pool.clone()).await,
-        )
-        .await,
-    ));
-
-    let obs_client = server::obs::create_obs_client().await?;
-    event_loop.push(OBSMessageHandler {
-        obs_client,
-        pool: pool.clone(),
+    let p = pool.clone();
+    event_loop.supervise(
+        "twitch_messages",
+        RestartPolicy::default(),
+        move || {
+            let pool = p.clone();
+            async move {
+                Ok(twitch_chat::TwitchMessageHandler::new(
+                    pool.clone(),
+                    twitch_service::Service::new(
+                        pool.clone(),
+                        user_service::Service::new(pool.clone()).await,
+                    )
+                    .await,
+                ))
+            }
+        },
+    );
+
+    // Subs and channel point redemptions
+    let t = tokens.clone();
+    event_loop.supervise(
+        "twitch_notifications",
+        RestartPolicy::default(),
+        move || {
+            let tokens = t.clone();
+            async move {
+                Ok(twitch_notifications::TwitchNotifications::new(tokens))
+            }
+        },
+    );
+
+    // Answers RequestTwitchSubCount, and keeps track of the sub goal
+    let (p, t) = (pool.clone(), tokens.clone());
+    event_loop.supervise("sub_count", RestartPolicy::default(), move || {
+        let (pool, tokens) = (p.clone(), t.clone());
+        async move {
+            Ok(server::sub_count::SubCountHandler::new(
+                Box::new(server::sub_count::HelixSubCounter::new(tokens)),
+                server::sub_count::load_goal(&pool).await?,
+            ))
+        }
+    });
+
+    // Deletes, warns and times out, following moderation_rules
+    let (p, t) = (pool.clone(), tokens.clone());
+    event_loop.supervise("moderation", RestartPolicy::default(), move || {
+        let (pool, tokens) = (p.clone(), t.clone());
+        async move {
+            Ok(server::moderation::ModerationHandler::new(
+                Box::new(server::moderation::TwitchModerator::new(
+                    pool.clone(),
+                    tokens,
+                )),
+                server::moderation::ModerationRules::load(&pool).await?,
+            ))
+        }
+    });
+
+    // One OBS connection that every handler shares, and one for each channel
+    // with its own OBS. They connect again whenever OBS restarts.
+    let obs = ObsConnection::new(channels::ObsConfig::ours()?);
+    let channel_obs = channels::obs_connections(&joined);
+    let connections = channel_obs
+        .iter()
+        .map(|(login, connection)| (Some(login.clone()), connection.clone()))
+        .chain([(None, obs.clone())]);
+    for (channel, connection) in connections {
+        let name = match &channel {
+            Some(login) => format!("obs_{}", login),
+            None => "obs".to_string(),
+        };
+        event_loop.supervise(name, RestartPolicy::default(), move || {
+            let (connection, channel) = (connection.clone(), channel.clone());
+            async move { Ok(ObsConnectionHandler::new(connection, channel)) }
+        });
+    }
+
+    let (p, o, c) = (pool.clone(), obs.clone(), channel_obs.clone());
+    event_loop.supervise("obs_messages", RestartPolicy::default(), move || {
+        let (pool, obs, channel_obs) = (p.clone(), o.clone(), c.clone());
+        async move {
+            Ok(obs_routing::OBSMessageHandler {
+                obs_client: Box::new(obs),
+                channel_obs: channel_clients(&channel_obs),
+                cooldowns: Cooldowns::load(&pool).await?,
+                grants: Grants::load(&pool).await?,
+                store: Box::new(pool),
+            })
+        }
     });
 
     // Works for Arch Linux
     let (_stream, stream_handle) = audio::get_output_stream("pulse");
     // Works for Mac
     // let (_stream, handle) = rodio::OutputStream::try_default().unwrap();
-    let sink = rodio::Sink::try_new(&stream_handle).unwrap();
     // This should be abstracted
 
-    event_loop.push(SoundHandler {
-        sink,
-        pool: pool.clone(),
+    // The audio handlers block until each clip is done playing, so they get
+    // their own bigger queues instead of lagging behind on the shared bus,
+    // and more time to finish the current clip when we shut down.
+    let clip_timeout = time::Duration::from_secs(30);
+
+    let (p, handle) = (pool.clone(), stream_handle.clone());
+    event_loop
+        .supervise("sound", RestartPolicy::default(), move || {
+            let (pool, handle) = (p.clone(), handle.clone());
+            async move {
+                Ok(SoundHandler {
+                    sink: Box::new(rodio::Sink::try_new(&handle)?),
+                    pool,
+                })
+            }
+        })
+        .with_queue(1024)
+        .shutdown_timeout(clip_timeout);
+
+    let (p, handle) = (pool.clone(), stream_handle.clone());
+    event_loop
+        .supervise("uberduck", RestartPolicy::default(), move || {
+            let (pool, handle) = (p.clone(), handle.clone());
+            async move {
+                Ok(uberduck::UberDuckHandler {
+                    sink: Box::new(rodio::Sink::try_new(&handle)?),
+                    pool,
+                })
+            }
+        })
+        .with_queue(1024)
+        .shutdown_timeout(clip_timeout);
+
+    let (p, o, c, handle) = (
+        pool.clone(),
+        obs.clone(),
+        channel_obs.clone(),
+        stream_handle.clone(),
+    );
+    event_loop
+        .supervise("cheers", RestartPolicy::default(), move || {
+            let (pool, obs, channel_obs, handle) =
+                (p.clone(), o.clone(), c.clone(), handle.clone());
+            async move {
+                Ok(server::cheers::CheerHandler {
+                    obs_client: Box::new(obs),
+                    channel_obs: channel_clients(&channel_obs),
+                    sink: Box::new(rodio::Sink::try_new(&handle)?),
+                    tiers: server::cheers::CheerTiers::load(&pool).await?,
+                })
+            }
+        })
+        .shutdown_timeout(clip_timeout);
+
+    let (p, t, o, handle) = (
+        pool.clone(),
+        tokens.clone(),
+        obs.clone(),
+        stream_handle.clone(),
+    );
+    event_loop
+        .supervise("rewards", RestartPolicy::default(), move || {
+            let (pool, tokens, obs, handle) =
+                (p.clone(), t.clone(), o.clone(), handle.clone());
+            async move {
+                Ok(server::rewards::RewardHandler {
+                    obs_client: Box::new(obs),
+                    sink: Box::new(rodio::Sink::try_new(&handle)?),
+                    routes: server::rewards::RewardRoutes::load(&pool).await?,
+                    ledger: Box::new(server::rewards::TwitchLedger::new(
+                        pool, tokens,
+                    )),
+                    pending_for: server::rewards::PENDING_FOR,
+                })
+            }
+        })
+        .shutdown_timeout(clip_timeout);
+
+    let o = obs.clone();
+    event_loop.supervise("scenes", RestartPolicy::default(), move || {
+        let obs = o.clone();
+        async move {
+            Ok(server::obs_scenes::SceneHandler {
+                obs_client: Box::new(obs),
+            })
+        }
     });
 
-    let sink = rodio::Sink::try_new(&stream_handle).unwrap();
-    let pool = get_db_pool().await;
-    event_loop.push(uberduck::UberDuckHandler { pool, sink });
+    let t = tokens.clone();
+    event_loop.supervise("reward_modes", RestartPolicy::default(), move || {
+        let tokens = t.clone();
+        async move { server::rewards::RewardModeHandler::new(tokens) }
+    });
 
-    let obs_client = server::obs::create_obs_client().await?;
-    event_loop.push(TriggerHotkeyHandler { obs_client });
+    let (t, o, c) = (tokens.clone(), obs.clone(), channel_obs.clone());
+    event_loop.supervise("raids", RestartPolicy::default(), move || {
+        let (tokens, obs, channel_obs) = (t.clone(), o.clone(), c.clone());
+        async move {
+            Ok(server::raids::RaidHandler {
+                obs_client: Box::new(obs),
+                channel_obs: channel_clients(&channel_obs),
+                channels: Box::new(server::raids::HelixChannels::new(tokens)),
+                combo: subd_types::consts::get_raid_obs_combo(),
+            })
+        }
+    });
 
-    let obs_client = server::obs::create_obs_client().await?;
-    event_loop.push(TransformOBSTextHandler { obs_client });
+    let o = obs.clone();
+    event_loop.supervise("hotkeys", RestartPolicy::default(), move || {
+        let obs = o.clone();
+        async move {
+            Ok(TriggerHotkeyHandler {
+                obs_client: Box::new(obs),
+            })
+        }
+    });
 
-    let obs_client = server::obs::create_obs_client().await?;
-    event_loop.push(StreamCharacterHandler { obs_client });
+    let o = obs.clone();
+    event_loop.supervise("obs_text", RestartPolicy::default(), move || {
+        let obs = o.clone();
+        async move {
+            Ok(TransformOBSTextHandler {
+                obs_client: Box::new(obs),
+            })
+        }
+    });
 
-    let obs_client = server::obs::create_obs_client().await?;
-    event_loop.push(SourceVisibilityHandler { obs_client });
+    let o = obs.clone();
+    event_loop.supervise(
+        "stream_characters",
+        RestartPolicy::default(),
+        move || {
+            let obs = o.clone();
+            async move {
+                Ok(StreamCharacterHandler {
+                    obs_client: Box::new(obs),
+                })
+            }
+        },
+    );
+
+    event_loop.supervise(
+        "source_visibility",
+        RestartPolicy::default(),
+        move || {
+            let obs = obs.clone();
+            async move {
+                Ok(SourceVisibilityHandler {
+                    obs_client: Box::new(obs),
+                })
+            }
+        },
+    );
 
+    // Ctrl-C or a mod running !shutdown stops the loop
     println!("\n\n\t\tLet's Start this Loop Up!");
-    event_loop.run().await?;
+    let report = event_loop.run().await?;
+
+    pool.close().await;
+    println!("{}", report);
 
     Ok(())
 }
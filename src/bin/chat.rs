@@ -317,13 +317,16 @@ async fn yew_inner_loop(
 
     println!("Looping new yew inner loop");
     loop {
-        let event = rx.recv().await?;
+        let event = events::recv(&mut rx).await?;
         match event {
             Event::TwitchChatMessage(_)
             | Event::ThemesongDownload(_)
             | Event::TwitchSubscriptionCount(_)
+            | Event::TwitchSubGoal(_)
             | Event::LunchBytesStatus(_)
             | Event::RaffleStatus(_)
+            | Event::TwitchConnectionStatus(_)
+            | Event::TwitchUserNotice(_)
             | Event::TwitchSubscription(_) => {
                 ws_stream
                     .send(tungstenite::Message::Text(serde_json::to_string(
@@ -370,13 +373,7 @@ async fn handle_obs_stuff(
 ) -> Result<()> {
     let mut _conn = subd_db::get_db_pool().await;
 
-    let obs_websocket_port = subd_types::consts::get_obs_websocket_port()
-        .parse::<u16>()
-        .unwrap();
-    let obs_websocket_address = subd_types::consts::get_obs_websocket_address();
-    let obs_client =
-        OBSClient::connect(obs_websocket_address, obs_websocket_port, Some(""))
-            .await?;
+    let obs_client = server::obs::create_obs_client().await?;
 
     let version = obs_client.general().version().await?;
     println!("OBS version: {:?}", version);
@@ -651,10 +648,22 @@ async fn main() -> Result<()> {
     let pool = subd_db::get_db_pool().await;
 
     // Turns twitch IRC things into our message events
-    event_loop.push(twitch_chat::TwitchChat::new(
-        pool.clone(),
-        "teej_dv".to_string(),
-    )?);
+    event_loop.push(
+        twitch_chat::TwitchChat::new(
+            pool.clone(),
+            vec!["teej_dv".to_string()],
+            subd_twitch::tokens::TokenStore::new(pool.clone()),
+        )?
+        .emotes(twitch_chat::emotes::EmoteMap::with_defaults()),
+    );
+
+    // Answers RequestTwitchSubCount, for the overlay
+    event_loop.push(server::sub_count::SubCountHandler::new(
+        Box::new(server::sub_count::HelixSubCounter::new(
+            subd_twitch::tokens::TokenStore::new(pool.clone()),
+        )),
+        server::sub_count::load_goal(&pool).await?,
+    ));
 
     // Does stuff with twitch messages
     event_loop.push(twitch_chat::TwitchMessageHandler::new(
@@ -1,68 +1,60 @@
+use anyhow::Result;
+use clap::Parser;
 use reqwest::Client as ReqwestClient;
-use twitch_api2::{
-    helix::points::{
-        CreateCustomRewardBody, CreateCustomRewardRequest,
-        GetCustomRewardRequest, UpdateCustomRewardBody,
-        UpdateCustomRewardRequest,
-    },
-    twitch_oauth2::UserToken,
-    HelixClient,
-};
+use subd_twitch::rewards::{self, RewardChange, RewardManager};
+use subd_twitch::tokens::{TokenKind, TokenStore};
+use twitch_api2::HelixClient;
+
+/// Sync the channel point rewards on Twitch with the ones declared in
+/// data/rewards.json.
+///
+/// Declared rewards are created or updated to match, and rewards we created
+/// before that are no longer declared get disabled.
+#[derive(Parser, Debug)]
+#[clap(name = "chat_rewards")]
+struct Args {
+    /// Where the rewards are declared.
+    #[clap(long, default_value = rewards::REWARDS_FILE)]
+    file: String,
+
+    /// Only print what would change.
+    #[clap(long)]
+    dry_run: bool,
+
+    /// Also turn rewards on or off for this OBS scene or stream mode.
+    #[clap(long)]
+    mode: Option<String>,
+}
 
 #[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    println!("Running rust stuff");
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    let declared = rewards::load_rewards(&args.file)?;
 
     let helix: HelixClient<ReqwestClient> = HelixClient::default();
 
-    let reqwest_client = helix.clone_client();
-    let token = UserToken::from_existing(
-        &reqwest_client,
-        subd_types::consts::get_twitch_broadcaster_oauth(),
-        subd_types::consts::get_twitch_broadcaster_refresh(),
-        None, // Client Secret
-    )
-    .await
-    .unwrap();
-
-    let create = false;
-    let update = true;
-    if create {
-        let req = CreateCustomRewardRequest::builder()
-            .broadcaster_id(token.user_id.clone())
-            .build();
-
-        let body = CreateCustomRewardBody::builder()
-            .title("Created from cli")
-            .cost(1000000)
-            .build();
-
-        let sent = helix
-            .req_post(req, body, &token)
-            .await
-            .expect("created reward");
-        dbg!(sent);
-    } else if update {
-        let id = "74447400-1357-4623-b458-5cff7fa8af67";
-        let req = UpdateCustomRewardRequest::builder()
-            .broadcaster_id(token.user_id.clone())
-            .id(id)
-            .build();
+    let token = TokenStore::new(subd_db::get_db_pool().await)
+        .user_token(TokenKind::Broadcaster)
+        .await?;
 
-        let body = UpdateCustomRewardBody::builder()
-            .is_enabled(Some(true))
-            .build();
-
-        let to_send =
-            helix.req_patch(req, body, &token).await.expect("sent good");
-        dbg!(to_send);
-    } else {
-        let req = GetCustomRewardRequest::builder()
-            .broadcaster_id(token.user_id.clone())
-            .build();
+    let manager = RewardManager::new(&helix, &token);
+    let changes = manager.sync(&declared, args.dry_run).await?;
+    if changes.is_empty() {
+        println!("Rewards are already in sync");
+    }
+    for change in changes {
+        match change {
+            RewardChange::Create(reward) => println!("+ {}", reward.title),
+            RewardChange::Update { reward, .. } => {
+                println!("~ {}", reward.title)
+            }
+            RewardChange::Disable { title, .. } => println!("- {}", title),
+        }
+    }
 
-        let response = helix.req_get(req, &token).await.expect("yayayaya");
-        dbg!(response);
+    if let Some(mode) = args.mode.filter(|_| !args.dry_run) {
+        manager.apply_mode(&declared, &mode).await?;
+        println!("Rewards set for {}", mode);
     }
 
     Ok(())
@@ -0,0 +1,21 @@
+//! Regenerates the command docs in the README from the command registry in
+//! `obs_routing`. Run it after adding or changing a command:
+//!
+//! ```text
+//! cargo run --bin command_docs
+//! ```
+
+use anyhow::Result;
+use server::commands::registry;
+use server::obs_routing;
+
+const README: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/README.md");
+
+fn main() -> Result<()> {
+    let readme = std::fs::read_to_string(README)?;
+    let docs = obs_routing::obs_commands().markdown();
+
+    std::fs::write(README, registry::replace_readme_section(&readme, &docs)?)?;
+    println!("Updated the commands in {}", README);
+    Ok(())
+}
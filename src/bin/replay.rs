@@ -0,0 +1,237 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use clap::Parser;
+use events::{EventHandler, RestartPolicy};
+use server::cooldowns::Cooldowns;
+use server::journal::{self, JournalEntry};
+use server::permissions::Grants;
+use server::testing::{FakeAudio, FakeLedger, FakeOBS, FakeSubCounter};
+use server::user_messages;
+use server::{cheers, obs_routing, rewards, soundboard, sub_count};
+use std::collections::HashMap;
+use std::time::Duration;
+use subd_types::Event;
+use tokio::sync::broadcast;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+use uuid::Uuid;
+
+/// Feed a recorded session from the event journal back into an event loop.
+///
+/// The chat, sound, cheer and reward handlers run against a fake OBS, fake
+/// speakers and a fake Twitch, so a session that broke something on stream
+/// can be replayed safely off stream. Everything they asked OBS to do is
+/// printed at the end.
+#[derive(Parser, Debug)]
+#[clap(name = "replay")]
+struct Args {
+    /// Session to replay. Defaults to the most recent one.
+    #[clap(long)]
+    session: Option<Uuid>,
+
+    /// How much faster than real time to replay. 0 replays as fast as
+    /// possible.
+    #[clap(long, default_value = "1.0")]
+    speed: f64,
+
+    /// Only replay these event types, like UserMessage. Useful to skip
+    /// events that handlers will send again on their own.
+    #[clap(long)]
+    only: Vec<String>,
+
+    /// List the recorded sessions and exit.
+    #[clap(long)]
+    list: bool,
+}
+
+/// Sends the recorded events back out on the bus, keeping their original
+/// spacing (scaled by `speed`).
+struct ReplayHandler {
+    entries: Vec<JournalEntry>,
+    speed: f64,
+}
+
+#[async_trait]
+impl EventHandler for ReplayHandler {
+    async fn handle(
+        self: Box<Self>,
+        tx: broadcast::Sender<Event>,
+        _: broadcast::Receiver<Event>,
+    ) -> Result<()> {
+        let mut last_offset = self.entries.first().map_or(0, |e| e.offset_ms);
+
+        for entry in self.entries {
+            let gap = (entry.offset_ms - last_offset).max(0) as f64;
+            last_offset = entry.offset_ms;
+
+            if self.speed > 0.0 {
+                let wait = Duration::from_secs_f64(gap / 1000.0 / self.speed);
+                tokio::time::sleep(wait).await;
+            }
+
+            println!("[{:>8}ms] {}", entry.offset_ms, entry.event_type);
+            let done = matches!(entry.event, Event::Shutdown);
+            tx.send(entry.event)?;
+            if done {
+                return Ok(());
+            }
+        }
+
+        println!("Replay finished");
+        tx.send(Event::Shutdown)?;
+        Ok(())
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::new(
+            "replay=debug,server=debug,events=info",
+        ))
+        .without_time()
+        .with_target(false)
+        .finish()
+        .init();
+
+    let args = Args::parse();
+    let pool = subd_db::get_db_pool().await;
+
+    let sessions = journal::list_sessions(&pool).await?;
+    if args.list {
+        for session in sessions {
+            println!(
+                "{}  {}  {} events over {}s",
+                session.session_id,
+                session.started,
+                session.events,
+                session.duration_ms / 1000
+            );
+        }
+        return Ok(());
+    }
+
+    let session_id = match args.session {
+        Some(session_id) => session_id,
+        None => {
+            sessions
+                .first()
+                .ok_or_else(|| anyhow!("No sessions have been recorded"))?
+                .session_id
+        }
+    };
+
+    let mut entries = journal::read_session(&pool, session_id).await?;
+    if !args.only.is_empty() {
+        entries.retain(|e| {
+            args.only.contains(&e.event_type)
+                || matches!(e.event, Event::Shutdown)
+        });
+    }
+    println!(
+        "Replaying {} events from session {} at {}x",
+        entries.len(),
+        session_id,
+        args.speed
+    );
+
+    let mut event_loop = events::EventLoop::new();
+    event_loop.push(ReplayHandler {
+        entries,
+        speed: args.speed,
+    });
+    event_loop.supervise("user_messages", RestartPolicy::default(), || async {
+        Ok(user_messages::UserMessageHandler {})
+    });
+
+    let (obs, audio, ledger) = (
+        FakeOBS::default(),
+        FakeAudio::default(),
+        FakeLedger::default(),
+    );
+
+    let (p, o) = (pool.clone(), obs.clone());
+    event_loop.supervise("obs_messages", RestartPolicy::default(), move || {
+        let (pool, obs) = (p.clone(), o.clone());
+        async move {
+            Ok(obs_routing::OBSMessageHandler {
+                obs_client: Box::new(obs),
+                channel_obs: HashMap::new(),
+                cooldowns: Cooldowns::load(&pool).await?,
+                grants: Grants::load(&pool).await?,
+                store: Box::new(pool),
+            })
+        }
+    });
+
+    let (p, a) = (pool.clone(), audio.clone());
+    event_loop.supervise("sound", RestartPolicy::default(), move || {
+        let (pool, audio) = (p.clone(), a.clone());
+        async move {
+            Ok(soundboard::SoundHandler {
+                sink: Box::new(audio),
+                pool,
+            })
+        }
+    });
+
+    let (p, o, a) = (pool.clone(), obs.clone(), audio.clone());
+    event_loop.supervise("cheers", RestartPolicy::default(), move || {
+        let (pool, obs, audio) = (p.clone(), o.clone(), a.clone());
+        async move {
+            Ok(cheers::CheerHandler {
+                obs_client: Box::new(obs),
+                channel_obs: HashMap::new(),
+                sink: Box::new(audio),
+                tiers: cheers::CheerTiers::load(&pool).await?,
+            })
+        }
+    });
+
+    let (p, o, a, l) =
+        (pool.clone(), obs.clone(), audio.clone(), ledger.clone());
+    event_loop.supervise("rewards", RestartPolicy::default(), move || {
+        let (pool, obs, audio, ledger) =
+            (p.clone(), o.clone(), a.clone(), l.clone());
+        async move {
+            Ok(rewards::RewardHandler {
+                obs_client: Box::new(obs),
+                sink: Box::new(audio),
+                routes: rewards::RewardRoutes::load(&pool).await?,
+                ledger: Box::new(ledger),
+                pending_for: rewards::PENDING_FOR,
+            })
+        }
+    });
+
+    let p = pool.clone();
+    event_loop.supervise("sub_count", RestartPolicy::default(), move || {
+        let pool = p.clone();
+        async move {
+            Ok(sub_count::SubCountHandler::new(
+                Box::new(FakeSubCounter(0)),
+                sub_count::load_goal(&pool).await?,
+            ))
+        }
+    });
+
+    let report = event_loop.run().await?;
+
+    println!("\nOBS was asked to:");
+    for call in obs.calls() {
+        println!("  {:?}", call);
+    }
+    println!("\nPlayed:");
+    for played in audio.played() {
+        println!("  {}", played);
+    }
+    println!("\nRedemptions:");
+    for (redemption_id, result) in ledger.finished() {
+        println!("  {} {:?}", redemption_id, result);
+    }
+
+    pool.close().await;
+    println!("{}", report);
+
+    Ok(())
+}
@@ -0,0 +1,107 @@
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use subd_twitch::tokens::{self, TokenKind, TokenStore};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// Make a new Twitch token with every scope subd needs, and store it.
+///
+/// Add http://localhost:<port>/auth as an OAuth Redirect URL of the app on
+/// the Twitch developer console first. Needs SUBD_TWITCH_CLIENT_ID and
+/// SUBD_TWITCH_CLIENT_SECRET.
+#[derive(Parser, Debug)]
+#[clap(name = "twitch_auth")]
+struct Args {
+    /// broadcaster or bot. Log in to Twitch as that account.
+    #[clap(long, default_value = "broadcaster")]
+    kind: TokenKind,
+
+    /// Where to wait for Twitch to redirect back to.
+    #[clap(long, default_value = "3000")]
+    port: u16,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    let kind = args.kind;
+    let store = TokenStore::new(subd_db::get_db_pool().await);
+
+    let redirect_url = format!("http://localhost:{}/auth", args.port);
+    let listener = TcpListener::bind(("127.0.0.1", args.port)).await?;
+
+    let token =
+        tokens::authorize(&store, kind, &redirect_url, |url| async move {
+            println!("Open this and log in as the {}:", kind.as_str());
+            println!("{}", url);
+            wait_for_code(&listener).await
+        })
+        .await?;
+
+    println!(
+        "Stored the {} token for {} with {}",
+        kind.as_str(),
+        token.login,
+        token
+            .scopes()
+            .iter()
+            .map(|scope| scope.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+
+    Ok(())
+}
+
+/// Takes the one request Twitch redirects the browser to, and returns the
+/// `state` and `code` from it.
+async fn wait_for_code(listener: &TcpListener) -> Result<(String, String)> {
+    let (mut socket, _) = listener.accept().await?;
+
+    let mut buf = vec![0; 4096];
+    let read = socket.read(&mut buf).await?;
+    let request = String::from_utf8_lossy(&buf[..read]);
+
+    // GET /auth?code=...&scope=...&state=... HTTP/1.1
+    let path = request
+        .lines()
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .ok_or_else(|| anyhow!("that wasn't an HTTP request"))?;
+    let url = reqwest::Url::parse(&format!("http://localhost{}", path))?;
+
+    let (mut state, mut code, mut error) = (None, None, None);
+    for (key, value) in url.query_pairs() {
+        match key.as_ref() {
+            "state" => state = Some(value.to_string()),
+            "code" => code = Some(value.to_string()),
+            "error_description" => error = Some(value.to_string()),
+            _ => {}
+        }
+    }
+
+    let body = match error {
+        Some(_) => "Twitch said no, check the terminal.",
+        None => "All done, you can close this tab.",
+    };
+    socket
+        .write_all(
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n\
+                 Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .as_bytes(),
+        )
+        .await?;
+
+    if let Some(error) = error {
+        return Err(anyhow!("Twitch didn't authorize us: {}", error));
+    }
+
+    match (state, code) {
+        (Some(state), Some(code)) => Ok((state, code)),
+        _ => Err(anyhow!("Twitch didn't send back a code")),
+    }
+}
@@ -1,10 +1,9 @@
 use crate::move_transition;
 use crate::move_transition_bootstrap;
+use crate::obs::OBSOperations;
 use crate::sdf_effects;
 use crate::stream_fx;
 use anyhow::Result;
-use obws;
-use obws::Client as OBSClient;
 
 const DEFAULT_SCENE: &str = "Primary";
 const DEFAULT_SOURCE: &str = "begin";
@@ -23,14 +22,13 @@ const BLUR_FILTER_NAME: &str = "Blur";
 
 pub async fn create_outline_filter(
     source: &str,
-    obs_client: &OBSClient,
+    obs_client: &dyn OBSOperations,
 ) -> Result<()> {
     let stream_fx_filter_name = "Move_Outline";
 
     // We look up Begin's Outline Settings
     let filter_details = match obs_client
-        .filters()
-        .get(DEFAULT_SOURCE, SDF_EFFECTS_FILTER_NAME)
+        .get_filter(DEFAULT_SOURCE, SDF_EFFECTS_FILTER_NAME)
         .await
     {
         Ok(val) => val,
@@ -45,13 +43,14 @@ pub async fn create_outline_filter(
         )
         .unwrap();
 
-    let new_filter = obws::requests::filters::Create {
-        source,
-        filter: SDF_EFFECTS_FILTER_NAME,
-        kind: "streamfx-filter-sdf-effects",
-        settings: Some(new_settings),
-    };
-    obs_client.filters().create(new_filter).await?;
+    obs_client
+        .create_filter(
+            source,
+            SDF_EFFECTS_FILTER_NAME,
+            "streamfx-filter-sdf-effects",
+            serde_json::to_value(&new_settings)?,
+        )
+        .await?;
 
     // I think this is fucking shit up
     // Create Move-Value for 3D Transform Filter
@@ -61,33 +60,35 @@ pub async fn create_outline_filter(
         duration: Some(7000),
         ..Default::default()
     };
-    let new_filter = obws::requests::filters::Create {
-        source,
-        filter: stream_fx_filter_name,
-        kind: "move_value_filter",
-        settings: Some(new_settings),
-    };
-    obs_client.filters().create(new_filter).await?;
+    obs_client
+        .create_filter(
+            source,
+            stream_fx_filter_name,
+            "move_value_filter",
+            serde_json::to_value(&new_settings)?,
+        )
+        .await?;
 
     Ok(())
 }
 
 pub async fn create_blur_filters(
     source: &str,
-    obs_client: &OBSClient,
+    obs_client: &dyn OBSOperations,
 ) -> Result<()> {
     let stream_fx_filter_name = "Move_Blur";
 
     let stream_fx_settings = stream_fx::StreamFXSettings {
         ..Default::default()
     };
-    let new_filter = obws::requests::filters::Create {
-        source,
-        filter: BLUR_FILTER_NAME,
-        kind: "streamfx-filter-blur",
-        settings: Some(stream_fx_settings),
-    };
-    obs_client.filters().create(new_filter).await?;
+    obs_client
+        .create_filter(
+            source,
+            BLUR_FILTER_NAME,
+            "streamfx-filter-blur",
+            serde_json::to_value(&stream_fx_settings)?,
+        )
+        .await?;
 
     // Create Move-Value for 3D Transform Filter
     let new_settings = move_transition::MoveSingleValueSetting {
@@ -96,33 +97,35 @@ pub async fn create_blur_filters(
         duration: Some(7000),
         ..Default::default()
     };
-    let new_filter = obws::requests::filters::Create {
-        source,
-        filter: stream_fx_filter_name,
-        kind: "move_value_filter",
-        settings: Some(new_settings),
-    };
-    obs_client.filters().create(new_filter).await?;
+    obs_client
+        .create_filter(
+            source,
+            stream_fx_filter_name,
+            "move_value_filter",
+            serde_json::to_value(&new_settings)?,
+        )
+        .await?;
 
     Ok(())
 }
 
 pub async fn create_scroll_filters(
     source: &str,
-    obs_client: &OBSClient,
+    obs_client: &dyn OBSOperations,
 ) -> Result<()> {
     let stream_fx_filter_name = "Move_Scroll";
 
     let stream_fx_settings = stream_fx::StreamFXSettings {
         ..Default::default()
     };
-    let new_filter = obws::requests::filters::Create {
-        source,
-        filter: "Scroll",
-        kind: "scroll_filter",
-        settings: Some(stream_fx_settings),
-    };
-    obs_client.filters().create(new_filter).await?;
+    obs_client
+        .create_filter(
+            source,
+            "Scroll",
+            "scroll_filter",
+            serde_json::to_value(&stream_fx_settings)?,
+        )
+        .await?;
 
     // Create Move-Value for 3D Transform Filter
     let new_settings = move_transition::MoveSingleValueSetting {
@@ -131,20 +134,21 @@ pub async fn create_scroll_filters(
         duration: Some(7000),
         ..Default::default()
     };
-    let new_filter = obws::requests::filters::Create {
-        source,
-        filter: stream_fx_filter_name,
-        kind: "move_value_filter",
-        settings: Some(new_settings),
-    };
-    obs_client.filters().create(new_filter).await?;
+    obs_client
+        .create_filter(
+            source,
+            stream_fx_filter_name,
+            "move_value_filter",
+            serde_json::to_value(&new_settings)?,
+        )
+        .await?;
 
     Ok(())
 }
 
 pub async fn create_split_3d_transform_filters(
     source: &str,
-    obs_client: &OBSClient,
+    obs_client: &dyn OBSOperations,
 ) -> Result<()> {
     let camera_types = vec!["Orthographic", "Perspective", "CornerPin"];
 
@@ -154,13 +158,14 @@ pub async fn create_split_3d_transform_filters(
             camera_mode: Some(i as i32),
             ..Default::default()
         };
-        let new_filter = obws::requests::filters::Create {
-            source,
-            filter: &filter_name,
-            kind: STREAM_FX_INTERNAL_FILTER_NAME,
-            settings: Some(stream_fx_settings),
-        };
-        obs_client.filters().create(new_filter).await?;
+        obs_client
+            .create_filter(
+                source,
+                &filter_name,
+                STREAM_FX_INTERNAL_FILTER_NAME,
+                serde_json::to_value(&stream_fx_settings)?,
+            )
+            .await?;
 
         let stream_fx_filter_name = format!("Move_3D_{}", camera_type);
 
@@ -170,13 +175,14 @@ pub async fn create_split_3d_transform_filters(
             duration: Some(7000),
             ..Default::default()
         };
-        let new_filter = obws::requests::filters::Create {
-            source,
-            filter: &stream_fx_filter_name,
-            kind: MOVE_VALUE_INTERNAL_FILTER_NAME,
-            settings: Some(new_settings),
-        };
-        obs_client.filters().create(new_filter).await?;
+        obs_client
+            .create_filter(
+                source,
+                &stream_fx_filter_name,
+                MOVE_VALUE_INTERNAL_FILTER_NAME,
+                serde_json::to_value(&new_settings)?,
+            )
+            .await?;
 
         // Create Default Move-Value for 3D Transform Filter
         let stream_fx_filter_name = format!("Move_3D_{}", camera_type);
@@ -188,33 +194,35 @@ pub async fn create_split_3d_transform_filters(
             duration: Some(3000),
             ..Default::default()
         };
-        let new_filter = obws::requests::filters::Create {
-            source,
-            filter: &stream_fx_filter_name,
-            kind: MOVE_VALUE_INTERNAL_FILTER_NAME,
-            settings: Some(new_settings),
-        };
-        obs_client.filters().create(new_filter).await?;
+        obs_client
+            .create_filter(
+                source,
+                &stream_fx_filter_name,
+                MOVE_VALUE_INTERNAL_FILTER_NAME,
+                serde_json::to_value(&new_settings)?,
+            )
+            .await?;
     }
 
     Ok(())
 }
 pub async fn create_3d_transform_filters(
     source: &str,
-    obs_client: &OBSClient,
+    obs_client: &dyn OBSOperations,
 ) -> Result<()> {
     let stream_fx_filter_name = "Move_Stream_FX";
 
     let stream_fx_settings = stream_fx::StreamFXSettings {
         ..Default::default()
     };
-    let new_filter = obws::requests::filters::Create {
-        source,
-        filter: THE_3D_TRANSFORM_FILTER_NAME,
-        kind: "streamfx-filter-transform",
-        settings: Some(stream_fx_settings),
-    };
-    obs_client.filters().create(new_filter).await?;
+    obs_client
+        .create_filter(
+            source,
+            THE_3D_TRANSFORM_FILTER_NAME,
+            "streamfx-filter-transform",
+            serde_json::to_value(&stream_fx_settings)?,
+        )
+        .await?;
 
     // Create Move-Value for 3D Transform Filter
     let new_settings = move_transition::MoveSingleValueSetting {
@@ -223,24 +231,25 @@ pub async fn create_3d_transform_filters(
         duration: Some(7000),
         ..Default::default()
     };
-    let new_filter = obws::requests::filters::Create {
-        source,
-        filter: stream_fx_filter_name,
-        kind: "move_value_filter",
-        settings: Some(new_settings),
-    };
-    obs_client.filters().create(new_filter).await?;
+    obs_client
+        .create_filter(
+            source,
+            stream_fx_filter_name,
+            "move_value_filter",
+            serde_json::to_value(&new_settings)?,
+        )
+        .await?;
 
     Ok(())
 }
 
 pub async fn create_filters_for_source(
     source: &str,
-    obs_client: &OBSClient,
+    obs_client: &dyn OBSOperations,
 ) -> Result<()> {
     println!("Creating Filters for Source: {}", source);
 
-    let filters = match obs_client.filters().list(source).await {
+    let filters = match obs_client.list_filters(source).await {
         Ok(val) => val,
         Err(_) => return Ok(()),
     };
@@ -251,8 +260,7 @@ pub async fn create_filters_for_source(
 
     for filter in filters {
         obs_client
-            .filters()
-            .remove(&source, &filter.name)
+            .remove_filter(source, &filter.name)
             .await
             .expect("Error Deleting Filter");
     }
@@ -262,16 +270,16 @@ pub async fn create_filters_for_source(
         DEFAULT_SCENE,
         &source,
         &filter_name,
-        &obs_client,
+        obs_client,
     )
     .await?;
 
     // We should seperate to it's own !chat command
-    // create_split_3d_transform_filters(source, &obs_client).await?;
-    create_3d_transform_filters(source, &obs_client).await?;
-    create_scroll_filters(source, &obs_client).await?;
-    create_blur_filters(source, &obs_client).await?;
-    create_outline_filter(source, &obs_client).await?;
+    // create_split_3d_transform_filters(source, obs_client).await?;
+    create_3d_transform_filters(source, obs_client).await?;
+    create_scroll_filters(source, obs_client).await?;
+    create_blur_filters(source, obs_client).await?;
+    create_outline_filter(source, obs_client).await?;
 
     let new_settings = move_transition::MoveSingleValueSetting {
         move_value_type: Some(1),
@@ -279,13 +287,14 @@ pub async fn create_filters_for_source(
         duration: Some(7000),
         ..Default::default()
     };
-    let new_filter = obws::requests::filters::Create {
-        source,
-        filter: DEFAULT_STREAM_FX_FILTER_NAME,
-        kind: "move_value_filter",
-        settings: Some(new_settings),
-    };
-    obs_client.filters().create(new_filter).await?;
+    obs_client
+        .create_filter(
+            source,
+            DEFAULT_STREAM_FX_FILTER_NAME,
+            "move_value_filter",
+            serde_json::to_value(&new_settings)?,
+        )
+        .await?;
 
     // This is For Scroll
     let new_settings = move_transition::MoveSingleValueSetting {
@@ -294,13 +303,14 @@ pub async fn create_filters_for_source(
         duration: Some(7000),
         ..Default::default()
     };
-    let new_filter = obws::requests::filters::Create {
-        source,
-        filter: DEFAULT_SCROLL_FILTER_NAME,
-        kind: "move_value_filter",
-        settings: Some(new_settings),
-    };
-    obs_client.filters().create(new_filter).await?;
+    obs_client
+        .create_filter(
+            source,
+            DEFAULT_SCROLL_FILTER_NAME,
+            "move_value_filter",
+            serde_json::to_value(&new_settings)?,
+        )
+        .await?;
 
     // This is For Blur
     let new_settings = move_transition::MoveSingleValueSetting {
@@ -311,14 +321,14 @@ pub async fn create_filters_for_source(
         duration: Some(7000),
         ..Default::default()
     };
-    let new_filter = obws::requests::filters::Create {
-        source,
-        filter: DEFAULT_BLUR_FILTER_NAME,
-        kind: "move_value_filter",
-
-        settings: Some(new_settings),
-    };
-    obs_client.filters().create(new_filter).await?;
+    obs_client
+        .create_filter(
+            source,
+            DEFAULT_BLUR_FILTER_NAME,
+            "move_value_filter",
+            serde_json::to_value(&new_settings)?,
+        )
+        .await?;
 
     // This is for SDF Effects
     let new_settings = move_transition::MoveSingleValueSetting {
@@ -332,13 +342,14 @@ pub async fn create_filters_for_source(
         outline: Some(false),
         ..Default::default()
     };
-    let new_filter = obws::requests::filters::Create {
-        source,
-        filter: DEFAULT_SDF_EFFECTS_FILTER_NAME,
-        kind: "move_value_filter",
-        settings: Some(new_settings),
-    };
-    obs_client.filters().create(new_filter).await?;
+    obs_client
+        .create_filter(
+            source,
+            DEFAULT_SDF_EFFECTS_FILTER_NAME,
+            "move_value_filter",
+            serde_json::to_value(&new_settings)?,
+        )
+        .await?;
 
     let filter_name = format!("Move_Source_{}", source);
 
@@ -346,7 +357,7 @@ pub async fn create_filters_for_source(
         DEFAULT_SCENE,
         &source,
         &filter_name,
-        &obs_client,
+        obs_client,
     )
     .await?;
 
@@ -0,0 +1,95 @@
+//! The Twitch channels the bot joins.
+//!
+//! Channels are listed in data/channels.json, the main one first. Each can
+//! point at its own OBS, which its chat's OBS commands then go to. Without
+//! the file only the broadcaster's channel is joined.
+
+use crate::obs_connection::ObsConnection;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+pub const CHANNELS_FILE: &str = "data/channels.json";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChannelConfig {
+    /// The channel's login, like "beginbot".
+    pub login: String,
+
+    /// The channel's own OBS. Channels without one share ours.
+    #[serde(default)]
+    pub obs: Option<ObsConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ObsConfig {
+    pub address: String,
+    pub port: u16,
+
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+impl ObsConfig {
+    /// Our own OBS, from `SUBD_OBS_WEBSOCKET_*`.
+    pub fn ours() -> Result<Self> {
+        let port = subd_types::consts::get_obs_websocket_port();
+        Ok(Self {
+            address: subd_types::consts::get_obs_websocket_address(),
+            port: port.parse().with_context(|| {
+                format!("SUBD_OBS_WEBSOCKET_PORT {:?} isn't a port", port)
+            })?,
+            password: subd_types::consts::get_obs_websocket_password(),
+        })
+    }
+}
+
+pub fn load_channels(path: &str) -> Result<Vec<ChannelConfig>> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(vec![ChannelConfig {
+                login: subd_types::consts::get_twitch_broadcaster_username(),
+                obs: None,
+            }])
+        }
+        Err(err) => {
+            return Err(err)
+                .with_context(|| format!("can't read channels from {}", path))
+        }
+    };
+
+    let channels: Vec<ChannelConfig> = serde_json::from_str(&contents)?;
+    if channels.is_empty() {
+        bail!("{} doesn't list any channels", path);
+    }
+
+    Ok(channels
+        .into_iter()
+        .map(|channel| ChannelConfig {
+            login: channel.login.to_lowercase(),
+            ..channel
+        })
+        .collect())
+}
+
+pub fn logins(channels: &[ChannelConfig]) -> Vec<String> {
+    channels
+        .iter()
+        .map(|channel| channel.login.clone())
+        .collect()
+}
+
+/// A connection to the OBS of every channel that has its own, by login.
+/// Each has to be kept up by an `ObsConnectionHandler`.
+pub fn obs_connections(
+    channels: &[ChannelConfig],
+) -> HashMap<String, ObsConnection> {
+    channels
+        .iter()
+        .filter_map(|channel| {
+            let config = channel.obs.clone()?;
+            Some((channel.login.clone(), ObsConnection::new(config)))
+        })
+        .collect()
+}
@@ -0,0 +1,306 @@
+//! Reactions to bits, and who has cheered the most.
+//!
+//! Each row of `cheer_tiers` says what happens once a cheer reaches
+//! `min_bits`: a sound, an OBS combo, and a voice to read the message out in.
+//! Only the biggest tier a cheer reaches is used, and its combo runs on the
+//! OBS of the channel it was cheered in.
+
+use crate::audio::AudioOutput;
+use crate::obs;
+use crate::obs::OBSOperations;
+use crate::obs_combo;
+use crate::uberduck;
+use anyhow::Result;
+use async_trait::async_trait;
+use events::{EventHandler, Subscription};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use subd_types::{Event, TwitchCheer, UberDuckRequest};
+use tokio::sync::broadcast;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CheerTier {
+    pub min_bits: u64,
+    pub sound: Option<String>,
+    pub obs_combo: Option<String>,
+    pub voice: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CheerTiers(Vec<CheerTier>);
+
+impl CheerTiers {
+    pub fn new(mut tiers: Vec<CheerTier>) -> Self {
+        tiers.sort_by_key(|tier| tier.min_bits);
+        Self(tiers)
+    }
+
+    pub async fn load(pool: &PgPool) -> Result<Self> {
+        let tiers = sqlx::query!(
+            "SELECT min_bits, sound, obs_combo, voice FROM cheer_tiers"
+        )
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|row| CheerTier {
+            min_bits: row.min_bits.max(0) as u64,
+            sound: row.sound,
+            obs_combo: row.obs_combo,
+            voice: row.voice,
+        })
+        .collect();
+
+        Ok(Self::new(tiers))
+    }
+
+    /// The biggest tier `bits` reaches, if any.
+    pub fn for_bits(&self, bits: u64) -> Option<&CheerTier> {
+        self.0.iter().rev().find(|tier| bits >= tier.min_bits)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheerTotal {
+    pub user_name: String,
+    pub bits: i64,
+}
+
+/// The `limit` users who have cheered the most bits in `channel`, ever.
+pub async fn leaderboard(
+    pool: &PgPool,
+    channel: &str,
+    limit: i64,
+) -> Result<Vec<CheerTotal>> {
+    let rows = sqlx::query!(
+        r#"SELECT twitch_users.display_name, SUM(twitch_cheers.bits) AS "bits!"
+           FROM twitch_cheers
+           JOIN twitch_users ON twitch_users.user_id = twitch_cheers.user_id
+           WHERE twitch_cheers.channel = $1
+           GROUP BY twitch_users.display_name
+           ORDER BY 2 DESC
+           LIMIT $2"#,
+        channel,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| CheerTotal {
+            user_name: row.display_name,
+            bits: row.bits,
+        })
+        .collect())
+}
+
+/// Every bit `user_name` has cheered in `channel`.
+pub async fn total_for(
+    pool: &PgPool,
+    channel: &str,
+    user_name: &str,
+) -> Result<i64> {
+    let row = sqlx::query!(
+        r#"SELECT COALESCE(SUM(twitch_cheers.bits), 0)::BIGINT AS "bits!"
+           FROM twitch_cheers
+           JOIN twitch_users ON twitch_users.user_id = twitch_cheers.user_id
+           WHERE twitch_cheers.channel = $1 AND twitch_users.login = $2"#,
+        channel,
+        user_name.trim_start_matches('@').to_lowercase()
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(row.bits)
+}
+
+/// Drops the cheermotes, so "Cheer100 hello" is read out as "hello".
+pub fn strip_cheermotes(message: &str) -> String {
+    message
+        .split_whitespace()
+        .filter(|word| {
+            let prefix = word.trim_end_matches(|c: char| c.is_ascii_digit());
+            prefix.len() == word.len()
+                || prefix.is_empty()
+                || !prefix.chars().all(|c| c.is_ascii_alphabetic())
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub struct CheerHandler {
+    pub obs_client: Box<dyn OBSOperations>,
+
+    /// OBS of the channels that have their own, by login. Cheers in every
+    /// other channel go to `obs_client`.
+    pub channel_obs: HashMap<String, Box<dyn OBSOperations>>,
+    pub sink: Box<dyn AudioOutput>,
+    pub tiers: CheerTiers,
+}
+
+#[async_trait]
+impl EventHandler for CheerHandler {
+    fn subscription(&self) -> Subscription {
+        events::only!(TwitchCheer)
+    }
+
+    async fn handle(
+        self: Box<Self>,
+        tx: broadcast::Sender<Event>,
+        mut rx: broadcast::Receiver<Event>,
+    ) -> Result<()> {
+        loop {
+            let event = events::recv(&mut rx).await?;
+            let cheer = match event {
+                Event::TwitchCheer(cheer) => cheer,
+                Event::Shutdown => return Ok(()),
+                _ => continue,
+            };
+
+            let tier = match self.tiers.for_bits(cheer.bits) {
+                Some(tier) => tier,
+                None => continue,
+            };
+            println!(
+                "{} cheered {} bits, reached the {} tier",
+                cheer.user_name, cheer.bits, tier.min_bits
+            );
+
+            let obs_client = match self.channel_obs.get(&cheer.channel) {
+                Some(client) => client.as_ref(),
+                None => self.obs_client.as_ref(),
+            };
+            react(&tx, obs_client, self.sink.as_ref(), tier, cheer).await;
+        }
+    }
+}
+
+/// Everything `tier` does. One reaction failing doesn't stop the others.
+async fn react(
+    tx: &broadcast::Sender<Event>,
+    obs_client: &dyn OBSOperations,
+    sink: &dyn AudioOutput,
+    tier: &CheerTier,
+    cheer: TwitchCheer,
+) {
+    if let Some(combo) = &tier.obs_combo {
+        if let Err(err) =
+            obs_combo::trigger(combo, obs::DEFAULT_SOURCE, obs_client).await
+        {
+            println!("Error running cheer combo {}: {:?}", combo, err);
+        }
+    }
+
+    if let Some(sound) = &tier.sound {
+        let path = format!("./MP3s/{}.mp3", sound);
+        if let Err(err) = sink.play_file(&path) {
+            println!("Error playing cheer sound {}: {:?}", path, err);
+        }
+    }
+
+    if let Some(voice) = &tier.voice {
+        let voice_text = strip_cheermotes(&cheer.message);
+        if voice_text.is_empty() {
+            return;
+        }
+
+        let _ = tx.send(Event::UberDuckRequest(UberDuckRequest {
+            voice: voice.clone(),
+            message: uberduck::chop_text(voice_text.clone()),
+            voice_text,
+            username: cheer.user_name,
+            source: None,
+            redemption_id: None,
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{FakeAudio, FakeOBS, OBSCall};
+    use subd_types::UserID;
+
+    fn tiers() -> CheerTiers {
+        CheerTiers::new(vec![
+            CheerTier {
+                min_bits: 1000,
+                voice: Some("Randall".to_string()),
+                ..Default::default()
+            },
+            CheerTier {
+                min_bits: 100,
+                sound: Some("bits".to_string()),
+                ..Default::default()
+            },
+            CheerTier {
+                min_bits: 500,
+                obs_combo: Some("blur".to_string()),
+                ..Default::default()
+            },
+        ])
+    }
+
+    fn cheer(bits: u64, message: &str) -> Event {
+        Event::TwitchCheer(TwitchCheer {
+            channel: "beginbot".to_string(),
+            user_id: UserID(uuid::Uuid::new_v4()),
+            user_name: "nyxkrage".to_string(),
+            bits,
+            message: message.to_string(),
+        })
+    }
+
+    #[test]
+    fn the_biggest_tier_reached_wins() {
+        let tiers = tiers();
+
+        assert_eq!(tiers.for_bits(99), None);
+        assert_eq!(tiers.for_bits(100).unwrap().min_bits, 100);
+        assert_eq!(tiers.for_bits(999).unwrap().min_bits, 500);
+        assert_eq!(tiers.for_bits(5000).unwrap().min_bits, 1000);
+    }
+
+    #[test]
+    fn cheermotes_are_not_read_out() {
+        assert_eq!(strip_cheermotes("Cheer100 great stream"), "great stream");
+        assert_eq!(
+            strip_cheermotes("BibleThump50 uwu 2 Kappa123 h4x"),
+            "uwu 2 h4x"
+        );
+    }
+
+    #[tokio::test]
+    async fn each_tier_reacts_its_own_way() {
+        let (obs, audio) = (FakeOBS::default(), FakeAudio::default());
+        let handler = CheerHandler {
+            obs_client: Box::new(obs.clone()),
+            channel_obs: HashMap::new(),
+            sink: Box::new(audio.clone()),
+            tiers: tiers(),
+        };
+
+        let sent = events::testing::run_handler(
+            handler,
+            vec![
+                cheer(100, "Cheer100"),
+                cheer(500, "Cheer500"),
+                cheer(1000, "Cheer1000 read this"),
+            ],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(audio.played(), vec!["./MP3s/bits.mp3".to_string()]);
+        assert!(obs.calls().contains(&OBSCall::SetFilterEnabled {
+            source: obs::DEFAULT_SOURCE.to_string(),
+            filter: obs::MOVE_BLUR_FILTER_NAME.to_string(),
+            enabled: true,
+        }));
+        assert!(matches!(
+            &sent[..],
+            [Event::UberDuckRequest(request)]
+                if request.voice == "Randall" && request.voice_text == "read this"
+        ));
+    }
+}
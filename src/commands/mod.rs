@@ -1,3 +1,5 @@
+pub mod registry;
+
 use clap::Parser;
 
 #[derive(Parser, Debug)]
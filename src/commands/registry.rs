@@ -0,0 +1,556 @@
+//! Chat commands described as data.
+//!
+//! Each `Command` says what arguments it takes, in what order, what they
+//! default to, and who is allowed to run it. The handler looks the command
+//! up, gets back typed `Args`, and only has to do the actual work. The same
+//! descriptions produce `!help` and the command docs in the README.
+//!
+//! ```ignore
+//! Command::new("!blur", "Blur a source, an AMOUNT of 0 unblurs it")
+//!     .arg(Arg::text("source").default(obs::DEFAULT_SOURCE))
+//!     .arg(Arg::float("amount").default(100.0))
+//!     .arg(Arg::int("duration").default(3000))
+//!     .example("!blur begin 50")
+//! ```
+
+use anyhow::{anyhow, bail, Result};
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+use subd_types::UserMessage;
+
+pub const README_START: &str = "<!-- commands:start -->";
+pub const README_END: &str = "<!-- commands:end -->";
+
+/// Something wrong with what chat asked for, like an unknown source. Only
+/// these are said back in chat, every other error just gets logged.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandError(pub String);
+
+impl Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgKind {
+    Text,
+    Float,
+    Int,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgValue {
+    Text(String),
+    Float(f32),
+    Int(u32),
+}
+
+impl Display for ArgValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArgValue::Text(text) => write!(f, "{}", text),
+            ArgValue::Float(value) => write!(f, "{}", value),
+            ArgValue::Int(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl From<&str> for ArgValue {
+    fn from(value: &str) -> Self {
+        ArgValue::Text(value.to_string())
+    }
+}
+
+impl From<f32> for ArgValue {
+    fn from(value: f32) -> Self {
+        ArgValue::Float(value)
+    }
+}
+
+impl From<u32> for ArgValue {
+    fn from(value: u32) -> Self {
+        ArgValue::Int(value)
+    }
+}
+
+/// One positional argument of a command.
+#[derive(Debug, Clone)]
+pub struct Arg {
+    pub name: &'static str,
+    pub kind: ArgKind,
+    pub default: Option<ArgValue>,
+    pub optional: bool,
+}
+
+impl Arg {
+    pub fn text(name: &'static str) -> Self {
+        Self::new(name, ArgKind::Text)
+    }
+
+    pub fn float(name: &'static str) -> Self {
+        Self::new(name, ArgKind::Float)
+    }
+
+    pub fn int(name: &'static str) -> Self {
+        Self::new(name, ArgKind::Int)
+    }
+
+    fn new(name: &'static str, kind: ArgKind) -> Self {
+        Self {
+            name,
+            kind,
+            default: None,
+            optional: false,
+        }
+    }
+
+    /// Used when chat leaves the argument off.
+    pub fn default(mut self, value: impl Into<ArgValue>) -> Self {
+        self.default = Some(value.into());
+        self
+    }
+
+    /// Can be left off, and there is no default to fall back to.
+    pub fn optional(mut self) -> Self {
+        self.optional = true;
+        self
+    }
+
+    fn parse(&self, raw: &str) -> Result<ArgValue> {
+        let raw = raw.trim();
+        let value = match self.kind {
+            ArgKind::Text => ArgValue::Text(raw.to_string()),
+            // "inf" and "NaN" parse, but aren't numbers anyone means
+            ArgKind::Float => ArgValue::Float(
+                raw.parse::<f32>()
+                    .ok()
+                    .filter(|value| value.is_finite())
+                    .ok_or_else(|| anyhow!("{} must be a number", self))?,
+            ),
+            ArgKind::Int => ArgValue::Int(
+                raw.parse()
+                    .map_err(|_| anyhow!("{} must be a whole number", self))?,
+            ),
+        };
+        Ok(value)
+    }
+
+    /// How the argument shows up in usage lines.
+    pub fn usage(&self) -> String {
+        match &self.default {
+            Some(default) => format!("[{}={}]", self, default),
+            None if self.optional => format!("[{}]", self),
+            None => self.to_string(),
+        }
+    }
+}
+
+impl Display for Arg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name.to_uppercase())
+    }
+}
+
+/// Who is allowed to run a command. Anyone further down the list can run
+/// everything further up. GitHub sponsors count as subs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Permission {
+    Everyone,
+    Subscriber,
+    Vip,
+    Moderator,
+    Broadcaster,
+}
+
+impl Permission {
+    pub fn allows(self, msg: &UserMessage) -> bool {
+        self == Permission::Everyone
+            || Self::from_roles(msg) >= self
+            || is_broadcaster(msg)
+    }
+
+    /// The most `msg`'s sender is allowed to do.
+    pub fn of(msg: &UserMessage) -> Self {
+        if is_broadcaster(msg) {
+            Permission::Broadcaster
+        } else {
+            Self::from_roles(msg)
+        }
+    }
+
+    fn from_roles(msg: &UserMessage) -> Self {
+        let roles = &msg.roles;
+        if roles.is_moderator() {
+            Permission::Moderator
+        } else if roles.is_twitch_vip() {
+            Permission::Vip
+        } else if roles.is_twitch_sub()
+            || roles.is_twitch_founder()
+            || roles.is_github_sponsor()
+        {
+            Permission::Subscriber
+        } else {
+            Permission::Everyone
+        }
+    }
+
+    /// How the permission is stored in the database.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::Everyone => "everyone",
+            Permission::Subscriber => "subscriber",
+            Permission::Vip => "vip",
+            Permission::Moderator => "moderator",
+            Permission::Broadcaster => "broadcaster",
+        }
+    }
+}
+
+impl FromStr for Permission {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let permission = match s.to_lowercase().trim_end_matches('s') {
+            "everyone" => Permission::Everyone,
+            "subscriber" | "sub" | "sponsor" => Permission::Subscriber,
+            "vip" => Permission::Vip,
+            "moderator" | "mod" => Permission::Moderator,
+            "broadcaster" => Permission::Broadcaster,
+            _ => bail!(CommandError(format!("{} is not a role", s))),
+        };
+        Ok(permission)
+    }
+}
+
+impl Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let who = match self {
+            Permission::Everyone => "everyone",
+            Permission::Subscriber => "subs and sponsors",
+            Permission::Vip => "VIPs",
+            Permission::Moderator => "mods",
+            Permission::Broadcaster => "the broadcaster",
+        };
+        write!(f, "{}", who)
+    }
+}
+
+/// Whether `msg` was sent by the owner of the channel it was sent in.
+pub fn is_broadcaster(msg: &UserMessage) -> bool {
+    msg.user_login
+        .as_deref()
+        .is_some_and(|login| login.eq_ignore_ascii_case(&msg.channel))
+}
+
+/// The arguments a command was called with, already parsed and with the
+/// defaults filled in.
+#[derive(Debug, Clone, Default)]
+pub struct Args {
+    values: HashMap<&'static str, ArgValue>,
+}
+
+impl Args {
+    pub fn text(&self, name: &str) -> Result<&str> {
+        match self.values.get(name) {
+            Some(ArgValue::Text(text)) => Ok(text),
+            _ => bail!("no text argument named {}", name),
+        }
+    }
+
+    pub fn float(&self, name: &str) -> Result<f32> {
+        match self.values.get(name) {
+            Some(ArgValue::Float(value)) => Ok(*value),
+            _ => bail!("no float argument named {}", name),
+        }
+    }
+
+    pub fn int(&self, name: &str) -> Result<u32> {
+        match self.values.get(name) {
+            Some(ArgValue::Int(value)) => Ok(*value),
+            _ => bail!("no int argument named {}", name),
+        }
+    }
+
+    /// For `optional` arguments, `None` when chat left it off.
+    pub fn maybe_text(&self, name: &str) -> Option<&str> {
+        match self.values.get(name) {
+            Some(ArgValue::Text(text)) => Some(text),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Command {
+    pub name: &'static str,
+    pub aliases: Vec<&'static str>,
+    pub args: Vec<Arg>,
+    pub permission: Permission,
+    pub help: &'static str,
+    pub examples: Vec<&'static str>,
+}
+
+impl Command {
+    pub fn new(name: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            aliases: vec![],
+            args: vec![],
+            permission: Permission::Everyone,
+            help,
+            examples: vec![],
+        }
+    }
+
+    pub fn alias(mut self, alias: &'static str) -> Self {
+        self.aliases.push(alias);
+        self
+    }
+
+    pub fn arg(mut self, arg: Arg) -> Self {
+        self.args.push(arg);
+        self
+    }
+
+    pub fn permission(mut self, permission: Permission) -> Self {
+        self.permission = permission;
+        self
+    }
+
+    pub fn example(mut self, example: &'static str) -> Self {
+        self.examples.push(example);
+        self
+    }
+
+    pub fn is_called(&self, name: &str) -> bool {
+        self.name == name || self.aliases.contains(&name)
+    }
+
+    pub fn usage(&self) -> String {
+        let mut usage = vec![self.name.to_string()];
+        usage.extend(self.args.iter().map(Arg::usage));
+        usage.join(" ")
+    }
+
+    /// Parse the words after the command name. Anything past the last
+    /// argument is left alone, some commands read the whole message.
+    pub fn parse(&self, words: &[String]) -> Result<Args> {
+        let mut args = Args::default();
+
+        for (i, arg) in self.args.iter().enumerate() {
+            let value = match (words.get(i), &arg.default) {
+                (Some(raw), _) => arg.parse(raw).map_err(|err| {
+                    anyhow!("{}. Usage: {}", err, self.usage())
+                })?,
+                (None, Some(default)) => default.clone(),
+                (None, None) if arg.optional => continue,
+                (None, None) => {
+                    bail!("{} is missing. Usage: {}", arg, self.usage())
+                }
+            };
+            args.values.insert(arg.name, value);
+        }
+
+        Ok(args)
+    }
+
+    /// A single chat message describing the command.
+    pub fn help_line(&self) -> String {
+        let mut line = format!("{} - {}", self.usage(), self.help);
+        if !self.aliases.is_empty() {
+            line += &format!(" (also {})", self.aliases.join(", "));
+        }
+        if self.permission != Permission::Everyone {
+            line += &format!(" [{} only]", self.permission);
+        }
+        line
+    }
+
+    pub fn markdown(&self) -> String {
+        let mut doc = format!("### {}\n\n{}\n\n", self.name, self.help);
+        doc += &format!("```\n{}\n```\n", self.usage());
+
+        if !self.aliases.is_empty() {
+            let aliases = self
+                .aliases
+                .iter()
+                .map(|alias| format!("`{}`", alias))
+                .collect::<Vec<_>>();
+            doc += &format!("\nAlso: {}\n", aliases.join(", "));
+        }
+
+        if self.permission != Permission::Everyone {
+            doc += &format!("\nOnly for {}.\n", self.permission);
+        }
+
+        if !self.examples.is_empty() {
+            doc += &format!(
+                "\n**Examples:**\n```\n{}\n```\n",
+                self.examples.join("\n")
+            );
+        }
+
+        doc
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Registry {
+    commands: Vec<Command>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn command(mut self, command: Command) -> Self {
+        self.commands.push(command);
+        self
+    }
+
+    pub fn commands(&self) -> &[Command] {
+        &self.commands
+    }
+
+    /// Look up a command by its name or one of its aliases. The `!` is
+    /// optional, so `!help blur` works too.
+    pub fn find(&self, name: &str) -> Option<&Command> {
+        let name = format!("!{}", name.trim_start_matches('!'));
+        self.commands.iter().find(|c| c.is_called(&name))
+    }
+
+    /// What `!help` replies with: every command `msg`'s sender can run, or
+    /// the details of one command.
+    pub fn help(&self, msg: &UserMessage, command: Option<&str>) -> String {
+        if let Some(name) = command {
+            return match self.find(name) {
+                Some(command) => command.help_line(),
+                None => format!("There is no {} command", name),
+            };
+        }
+
+        let names = self
+            .commands
+            .iter()
+            .filter(|c| c.permission.allows(msg))
+            .map(|c| c.name)
+            .collect::<Vec<_>>();
+        format!("Commands: {} | !help COMMAND for more", names.join(" "))
+    }
+
+    /// The command docs that go between the markers in the README.
+    pub fn markdown(&self) -> String {
+        self.commands
+            .iter()
+            .map(Command::markdown)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Swap the generated command docs into `readme`, between `README_START`
+/// and `README_END`.
+pub fn replace_readme_section(readme: &str, docs: &str) -> Result<String> {
+    let start = readme
+        .find(README_START)
+        .ok_or_else(|| anyhow!("README is missing {}", README_START))?;
+    let end = readme
+        .find(README_END)
+        .ok_or_else(|| anyhow!("README is missing {}", README_END))?;
+    if end < start {
+        bail!("{} comes before {} in the README", README_END, README_START);
+    }
+
+    Ok(format!(
+        "{}{}\n\n{}\n{}",
+        &readme[..start],
+        README_START,
+        docs,
+        &readme[end..]
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::user_message;
+
+    fn blur() -> Command {
+        Command::new("!blur", "Blur a source")
+            .alias("!fuzz")
+            .arg(Arg::text("source").default("begin"))
+            .arg(Arg::float("amount").default(100.0))
+            .arg(Arg::int("duration").optional())
+    }
+
+    fn words(words: &[&str]) -> Vec<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_typed_args_with_defaults() {
+        let args = blur().parse(&words(&["primetime", "50"])).unwrap();
+
+        assert_eq!(args.text("source").unwrap(), "primetime");
+        assert_eq!(args.float("amount").unwrap(), 50.0);
+        assert!(args.int("duration").is_err());
+
+        let args = blur().parse(&[]).unwrap();
+        assert_eq!(args.text("source").unwrap(), "begin");
+        assert_eq!(args.float("amount").unwrap(), 100.0);
+    }
+
+    #[test]
+    fn bad_args_explain_the_usage() {
+        let err = blur().parse(&words(&["begin", "lots"])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "AMOUNT must be a number. \
+             Usage: !blur [SOURCE=begin] [AMOUNT=100] [DURATION]"
+        );
+        assert!(blur().parse(&words(&["begin", "inf"])).is_err());
+
+        let command = Command::new("!move", "").arg(Arg::float("x"));
+        let err = command.parse(&[]).unwrap_err();
+        assert_eq!(err.to_string(), "X is missing. Usage: !move X");
+    }
+
+    #[test]
+    fn finds_commands_by_alias() {
+        let registry = Registry::new().command(blur());
+
+        assert_eq!(registry.find("!fuzz").unwrap().name, "!blur");
+        assert_eq!(registry.find("blur").unwrap().name, "!blur");
+        assert!(registry.find("!scale").is_none());
+    }
+
+    #[test]
+    fn help_only_lists_what_you_can_run() {
+        let registry = Registry::new().command(blur()).command(
+            Command::new("!shutdown", "Stop the bot")
+                .permission(Permission::Moderator),
+        );
+
+        let msg = user_message("viewer", "!help");
+        assert_eq!(
+            registry.help(&msg, None),
+            "Commands: !blur | !help COMMAND for more"
+        );
+
+        let msg = user_message("beginbot", "!help");
+        assert_eq!(
+            registry.help(&msg, None),
+            "Commands: !blur !shutdown | !help COMMAND for more"
+        );
+
+        assert_eq!(
+            registry.help(&msg, Some("shutdown")),
+            "!shutdown - Stop the bot [mods only]"
+        );
+    }
+}
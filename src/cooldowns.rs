@@ -0,0 +1,397 @@
+//! Cooldowns for chat commands, so nobody can spam `!spin` or run up the
+//! UberDuck bill.
+//!
+//! Every limit has two windows, both stored in `command_cooldowns`:
+//!
+//! - `window`: time between two uses by anyone
+//! - `user_window`: time between two uses by the same user. Each role gets a
+//!   burst from `cooldown_bursts`, that many uses are allowed within the
+//!   window before the user is throttled.
+//!
+//! The `*` row applies to every command on top of the command's own limit.
+//! Limits are shared by every channel, but each channel's uses are counted on
+//! their own. Mods and the broadcaster are never throttled.
+
+use crate::commands::registry::Permission;
+use anyhow::Result;
+use sqlx::PgPool;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{self, Display};
+use std::time::{Duration, Instant};
+
+/// The command name of the global limit.
+pub const GLOBAL: &str = "*";
+
+/// Don't tell the same user they are throttled more often than this.
+const WARN_EVERY: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limit {
+    pub window: Duration,
+    pub user_window: Duration,
+}
+
+impl Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}s between uses, {}s per user",
+            self.window.as_secs_f32(),
+            self.user_window.as_secs_f32()
+        )
+    }
+}
+
+/// Why a command wasn't allowed, and how long until it will be.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Throttled {
+    Global(Duration),
+    Command(String, Duration),
+    User(String, Duration),
+}
+
+impl Display for Throttled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Throttled::Global(wait) => {
+                write!(f, "slow down! Try again in {}s", seconds(*wait))
+            }
+            Throttled::Command(command, wait) => write!(
+                f,
+                "{} is cooling down, try again in {}s",
+                command,
+                seconds(*wait)
+            ),
+            Throttled::User(command, wait) => write!(
+                f,
+                "you've used {} too much, try again in {}s",
+                command,
+                seconds(*wait)
+            ),
+        }
+    }
+}
+
+fn seconds(wait: Duration) -> u128 {
+    wait.as_millis().div_ceil(1000)
+}
+
+#[derive(Debug, Default)]
+pub struct Cooldowns {
+    limits: HashMap<String, Limit>,
+    bursts: HashMap<Permission, u32>,
+
+    // (channel, command or GLOBAL) -> when anyone last used it
+    last_used: HashMap<(String, String), Instant>,
+    // (channel, user, command or GLOBAL) -> recent uses, oldest first
+    user_uses: HashMap<(String, String, String), VecDeque<Instant>>,
+    warned: HashMap<String, Instant>,
+}
+
+impl Cooldowns {
+    /// Load the limits and bursts from the database.
+    pub async fn load(pool: &PgPool) -> Result<Self> {
+        let mut cooldowns = Self::default();
+
+        let limits = sqlx::query!(
+            "SELECT command, window_ms, user_window_ms FROM command_cooldowns"
+        )
+        .fetch_all(pool)
+        .await?;
+        for row in limits {
+            cooldowns.set_limit(
+                &row.command,
+                Limit {
+                    window: millis(row.window_ms),
+                    user_window: millis(row.user_window_ms),
+                },
+            );
+        }
+
+        let bursts = sqlx::query!("SELECT role, burst FROM cooldown_bursts")
+            .fetch_all(pool)
+            .await?;
+        for row in bursts {
+            match row.role.parse() {
+                Ok(permission) => {
+                    cooldowns.set_burst(permission, row.burst.max(0) as u32)
+                }
+                Err(err) => println!("Skipping cooldown burst: {}", err),
+            }
+        }
+
+        Ok(cooldowns)
+    }
+
+    pub fn limit(&self, command: &str) -> Limit {
+        self.limits.get(command).copied().unwrap_or_default()
+    }
+
+    pub fn set_limit(&mut self, command: &str, limit: Limit) {
+        self.limits.insert(command.to_string(), limit);
+    }
+
+    /// How many uses `permission` gets within a user window. At least 1.
+    pub fn burst(&self, permission: Permission) -> u32 {
+        self.bursts.get(&permission).copied().unwrap_or(1).max(1)
+    }
+
+    pub fn set_burst(&mut self, permission: Permission, burst: u32) {
+        self.bursts.insert(permission, burst);
+    }
+
+    /// Count a use of `command` by `user` in `channel`, unless one of the
+    /// limits says they have to wait.
+    pub fn try_use(
+        &mut self,
+        channel: &str,
+        command: &str,
+        user: &str,
+        permission: Permission,
+        now: Instant,
+    ) -> Result<(), Throttled> {
+        if permission >= Permission::Moderator {
+            return Ok(());
+        }
+
+        let burst = self.burst(permission) as usize;
+        for scope in [GLOBAL, command] {
+            let limit = self.limit(scope);
+
+            let key = (channel.to_string(), scope.to_string());
+            if let Some(last) = self.last_used.get(&key) {
+                let since = now.saturating_duration_since(*last);
+                if since < limit.window {
+                    let wait = limit.window - since;
+                    return Err(match scope {
+                        GLOBAL => Throttled::Global(wait),
+                        _ => Throttled::Command(command.to_string(), wait),
+                    });
+                }
+            }
+
+            let key =
+                (channel.to_string(), user.to_string(), scope.to_string());
+            if let Some(uses) = self.user_uses.get(&key) {
+                let recent = uses
+                    .iter()
+                    .filter(|used| {
+                        now.saturating_duration_since(**used)
+                            < limit.user_window
+                    })
+                    .collect::<Vec<_>>();
+                if recent.len() >= burst {
+                    let since = now.saturating_duration_since(*recent[0]);
+                    let wait = limit.user_window - since;
+                    return Err(match scope {
+                        GLOBAL => Throttled::Global(wait),
+                        _ => Throttled::User(command.to_string(), wait),
+                    });
+                }
+            }
+        }
+
+        self.forget_old_uses(now);
+        for scope in [GLOBAL, command] {
+            self.last_used
+                .insert((channel.to_string(), scope.to_string()), now);
+            self.user_uses
+                .entry((
+                    channel.to_string(),
+                    user.to_string(),
+                    scope.to_string(),
+                ))
+                .or_default()
+                .push_back(now);
+        }
+
+        Ok(())
+    }
+
+    /// Drop uses that are out of their user window, and the users that have
+    /// none left, so viewers who stopped chatting don't stay around forever.
+    fn forget_old_uses(&mut self, now: Instant) {
+        let limits = &self.limits;
+        self.user_uses.retain(|(_, _, scope), uses| {
+            let user_window =
+                limits.get(scope).copied().unwrap_or_default().user_window;
+            uses.retain(|used| {
+                now.saturating_duration_since(*used) < user_window
+            });
+            !uses.is_empty()
+        });
+    }
+
+    /// Whether to tell `user` they were throttled. Keeps us from spamming
+    /// chat back at someone who is spamming us.
+    pub fn should_warn(&mut self, user: &str, now: Instant) -> bool {
+        match self.warned.get(user) {
+            Some(last) if now.saturating_duration_since(*last) < WARN_EVERY => {
+                false
+            }
+            _ => {
+                self.warned.insert(user.to_string(), now);
+                true
+            }
+        }
+    }
+}
+
+fn millis(ms: i64) -> Duration {
+    Duration::from_millis(ms.max(0) as u64)
+}
+
+pub async fn save_limit(
+    pool: &PgPool,
+    command: &str,
+    limit: Limit,
+) -> Result<()> {
+    sqlx::query!(
+        r#"INSERT INTO command_cooldowns (command, window_ms, user_window_ms)
+           VALUES ( $1, $2, $3 )
+           ON CONFLICT (command) DO UPDATE
+           SET window_ms = $2, user_window_ms = $3, updated_at = NOW()"#,
+        command,
+        limit.window.as_millis() as i64,
+        limit.user_window.as_millis() as i64,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn save_burst(
+    pool: &PgPool,
+    permission: Permission,
+    burst: u32,
+) -> Result<()> {
+    sqlx::query!(
+        r#"INSERT INTO cooldown_bursts (role, burst)
+           VALUES ( $1, $2 )
+           ON CONFLICT (role) DO UPDATE
+           SET burst = $2, updated_at = NOW()"#,
+        permission.as_str(),
+        burst as i32,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cooldowns() -> Cooldowns {
+        let mut cooldowns = Cooldowns::default();
+        cooldowns.set_limit(
+            "!spin",
+            Limit {
+                window: Duration::from_secs(1),
+                user_window: Duration::from_secs(10),
+            },
+        );
+        cooldowns.set_burst(Permission::Subscriber, 2);
+        cooldowns
+    }
+
+    #[test]
+    fn command_window_applies_to_everyone() {
+        let mut cooldowns = cooldowns();
+        let now = Instant::now();
+
+        assert!(cooldowns
+            .try_use("beginbot", "!spin", "a", Permission::Everyone, now)
+            .is_ok());
+        assert_eq!(
+            cooldowns.try_use(
+                "beginbot",
+                "!spin",
+                "b",
+                Permission::Everyone,
+                now
+            ),
+            Err(Throttled::Command(
+                "!spin".to_string(),
+                Duration::from_secs(1)
+            ))
+        );
+        assert!(cooldowns
+            .try_use("beginbot", "!blur", "b", Permission::Everyone, now)
+            .is_ok());
+    }
+
+    #[test]
+    fn subs_get_a_bigger_burst() {
+        let mut cooldowns = cooldowns();
+        let now = Instant::now();
+        let later = now + Duration::from_secs(2);
+        let even_later = now + Duration::from_secs(4);
+
+        let mut spin = |user, permission, at| {
+            cooldowns
+                .try_use("beginbot", "!spin", user, permission, at)
+                .is_ok()
+        };
+
+        assert!(spin("viewer", Permission::Everyone, now));
+        assert!(!spin("viewer", Permission::Everyone, later));
+
+        assert!(spin(
+            "sub",
+            Permission::Subscriber,
+            now + Duration::from_secs(1)
+        ));
+        assert!(spin("sub", Permission::Subscriber, later));
+        assert!(!spin("sub", Permission::Subscriber, even_later));
+    }
+
+    #[test]
+    fn channels_cool_down_on_their_own() {
+        let mut cooldowns = cooldowns();
+        let now = Instant::now();
+
+        assert!(cooldowns
+            .try_use("beginbot", "!spin", "viewer", Permission::Everyone, now)
+            .is_ok());
+        assert!(cooldowns
+            .try_use("teej_dv", "!spin", "viewer", Permission::Everyone, now)
+            .is_ok());
+        assert!(cooldowns
+            .try_use("teej_dv", "!spin", "other", Permission::Everyone, now)
+            .is_err());
+    }
+
+    #[test]
+    fn users_are_forgotten_once_their_uses_are_old() {
+        let mut cooldowns = cooldowns();
+        let now = Instant::now();
+        let later = now + Duration::from_secs(20);
+
+        assert!(cooldowns
+            .try_use("beginbot", "!spin", "viewer", Permission::Everyone, now)
+            .is_ok());
+        assert!(cooldowns
+            .try_use("beginbot", "!spin", "other", Permission::Everyone, later)
+            .is_ok());
+
+        assert!(cooldowns
+            .user_uses
+            .keys()
+            .all(|(_, user, _)| user == "other"));
+    }
+
+    #[test]
+    fn mods_are_never_throttled() {
+        let mut cooldowns = cooldowns();
+        let now = Instant::now();
+
+        for _ in 0..5 {
+            assert!(cooldowns
+                .try_use("beginbot", "!spin", "mod", Permission::Moderator, now)
+                .is_ok());
+        }
+    }
+}
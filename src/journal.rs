@@ -0,0 +1,186 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use events::EventHandler;
+use std::time::Instant;
+use subd_types::Event;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// One run of the bot. Every event journaled during it shares the id, and
+/// is stamped with how long after the start it happened.
+#[derive(Debug, Clone, Copy)]
+pub struct Session {
+    pub id: Uuid,
+    started: Instant,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            started: Instant::now(),
+        }
+    }
+
+    fn offset_ms(&self) -> i64 {
+        self.started.elapsed().as_millis() as i64
+    }
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Writes every event on the bus to the event_journal table, so a stream can
+/// be replayed later with the `replay` binary.
+pub struct JournalHandler {
+    pool: sqlx::PgPool,
+    session: Session,
+}
+
+impl JournalHandler {
+    pub fn new(pool: sqlx::PgPool, session: Session) -> Self {
+        Self { pool, session }
+    }
+}
+
+#[async_trait]
+impl EventHandler for JournalHandler {
+    async fn handle(
+        self: Box<Self>,
+        _: broadcast::Sender<Event>,
+        mut rx: broadcast::Receiver<Event>,
+    ) -> Result<()> {
+        loop {
+            let event = events::recv(&mut rx).await?;
+
+            // Losing a line in the journal is not worth taking anything
+            // else down for
+            if let Err(err) = record(&self.pool, &self.session, &event).await {
+                eprintln!("Error journaling event: {err}");
+            }
+
+            if let Event::Shutdown = event {
+                return Ok(());
+            }
+        }
+    }
+}
+
+async fn record(
+    pool: &sqlx::PgPool,
+    session: &Session,
+    event: &Event,
+) -> Result<()> {
+    let value = serde_json::to_value(event)?;
+
+    sqlx::query!(
+        r#"INSERT INTO event_journal (session_id, offset_ms, event_type, event)
+           VALUES ( $1, $2, $3, $4::text::jsonb )"#,
+        session.id,
+        session.offset_ms(),
+        event_type(&value),
+        value.to_string(),
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// The name of the `Event` variant, pulled out of its serialized form.
+pub fn event_type(value: &serde_json::Value) -> String {
+    match value {
+        // Unit variants, like Shutdown
+        serde_json::Value::String(name) => name.clone(),
+        serde_json::Value::Object(map) => {
+            map.keys().next().cloned().unwrap_or_default()
+        }
+        _ => String::new(),
+    }
+}
+
+#[derive(Debug)]
+pub struct JournalEntry {
+    pub offset_ms: i64,
+    pub event_type: String,
+    pub event: Event,
+}
+
+#[derive(Debug)]
+pub struct SessionSummary {
+    pub session_id: Uuid,
+    pub started: String,
+    pub events: i64,
+    pub duration_ms: i64,
+}
+
+/// Every event recorded during `session_id`, in the order they happened.
+pub async fn read_session(
+    pool: &sqlx::PgPool,
+    session_id: Uuid,
+) -> Result<Vec<JournalEntry>> {
+    let rows = sqlx::query!(
+        r#"SELECT offset_ms, event_type, event::text AS "event!"
+           FROM event_journal
+           WHERE session_id = $1
+           ORDER BY offset_ms, event_journal_id"#,
+        session_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    rows.into_iter()
+        .map(|row| {
+            Ok(JournalEntry {
+                offset_ms: row.offset_ms,
+                event_type: row.event_type,
+                event: serde_json::from_str(&row.event)?,
+            })
+        })
+        .collect()
+}
+
+/// Recorded sessions, most recent first.
+pub async fn list_sessions(pool: &sqlx::PgPool) -> Result<Vec<SessionSummary>> {
+    let rows = sqlx::query!(
+        r#"SELECT session_id,
+                  MIN(created_at)::text AS "started!",
+                  COUNT(*) AS "events!",
+                  MAX(offset_ms) AS "duration_ms!"
+           FROM event_journal
+           GROUP BY session_id
+           ORDER BY MIN(created_at) DESC"#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| SessionSummary {
+            session_id: row.session_id,
+            started: row.started,
+            events: row.events,
+            duration_ms: row.duration_ms,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_type_is_the_variant_name() {
+        let shutdown = serde_json::to_value(&Event::Shutdown).unwrap();
+        assert_eq!(event_type(&shutdown), "Shutdown");
+
+        let scene = serde_json::to_value(&Event::ObsSetScene {
+            scene: "memes".to_string(),
+        })
+        .unwrap();
+        assert_eq!(event_type(&scene), "ObsSetScene");
+    }
+}
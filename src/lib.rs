@@ -1,21 +1,34 @@
 pub mod audio;
 pub mod bootstrap;
+pub mod channels;
+pub mod cheers;
 pub mod commands;
+pub mod cooldowns;
+pub mod journal;
+pub mod moderation;
 pub mod move_transition;
 pub mod move_transition_bootstrap;
 pub mod move_transition_effects;
 pub mod obs;
 pub mod obs_combo;
+pub mod obs_connection;
 pub mod obs_hotkeys;
 pub mod obs_routing;
 pub mod obs_scenes;
 pub mod obs_source;
+pub mod permissions;
 pub mod raffle;
+pub mod raids;
+pub mod rewards;
 pub mod sdf_effects;
+pub mod soundboard;
 pub mod stream_character;
 pub mod stream_fx;
+pub mod sub_count;
 pub mod themesong;
 pub mod twitch_stream_state;
+pub mod twitch_tokens;
 pub mod uberduck;
 pub mod user_messages;
+pub mod testing;
 pub mod users;
@@ -0,0 +1,635 @@
+//! Keeping chat clean.
+//!
+//! Every chat message is checked for profanity (with rustrict), links, caps
+//! and the same thing sent over and over. What happens then depends on the
+//! sender's role, see `moderation_rules`: a warning, the message deleted or a
+//! timeout. Mods and the broadcaster are left alone.
+//!
+//! Everything done ends up in `moderation_log`, and mods can take it back
+//! with `!pardon @user`.
+
+use crate::commands::registry::Permission;
+use crate::permissions::normalize_user;
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use events::{EventHandler, Subscription};
+use rustrict::CensorStr;
+use sqlx::PgPool;
+use std::collections::{HashMap, VecDeque};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use subd_twitch::moderation::HelixModeration;
+use subd_twitch::tokens::{TokenKind, TokenStore};
+use subd_types::{ChatReply, Event, ModerationPardon, UserMessage};
+use tokio::sync::broadcast;
+
+/// How far back to look for repeated messages.
+const REPEAT_WINDOW: Duration = Duration::from_secs(30);
+
+/// How many times the same message can be sent within `REPEAT_WINDOW`.
+const REPEATS_ALLOWED: usize = 2;
+
+/// Shorter messages can be all caps, so "LUL KEKW" is fine.
+const CAPS_MIN_LETTERS: usize = 20;
+const CAPS_RATIO: f32 = 0.7;
+
+/// Words ending in these count as links, even without http://.
+const LINK_TLDS: &[&str] = &[
+    "app", "co", "com", "dev", "gg", "info", "io", "link", "live", "ly", "me",
+    "net", "org", "ru", "tv", "xyz",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Violation {
+    Profanity,
+    Links,
+    Caps,
+    Repeated,
+}
+
+impl Violation {
+    /// How the violation is stored in the database.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Violation::Profanity => "profanity",
+            Violation::Links => "links",
+            Violation::Caps => "caps",
+            Violation::Repeated => "repeated",
+        }
+    }
+
+    /// What Twitch shows the user.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Violation::Profanity => "Watch your language",
+            Violation::Links => "No links please",
+            Violation::Caps => "Easy on the caps",
+            Violation::Repeated => "Stop repeating yourself",
+        }
+    }
+}
+
+impl FromStr for Violation {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "profanity" => Ok(Violation::Profanity),
+            "links" => Ok(Violation::Links),
+            "caps" => Ok(Violation::Caps),
+            "repeated" => Ok(Violation::Repeated),
+            _ => bail!("{} is not a violation", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Warn,
+    Delete,
+    Timeout { seconds: u32 },
+}
+
+impl Action {
+    /// How the action is stored in the database.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Action::Warn => "warn",
+            Action::Delete => "delete",
+            Action::Timeout { .. } => "timeout",
+        }
+    }
+
+    pub fn from_db(action: &str, timeout_seconds: i32) -> Result<Self> {
+        match action {
+            "warn" => Ok(Action::Warn),
+            "delete" => Ok(Action::Delete),
+            "timeout" => Ok(Action::Timeout {
+                seconds: timeout_seconds.max(1) as u32,
+            }),
+            _ => bail!("{} is not a moderation action", action),
+        }
+    }
+
+    fn timeout_seconds(&self) -> i32 {
+        match self {
+            Action::Timeout { seconds } => *seconds as i32,
+            _ => 0,
+        }
+    }
+}
+
+/// What to do about each violation, by role.
+#[derive(Debug, Default)]
+pub struct ModerationRules(HashMap<(Permission, Violation), Action>);
+
+impl ModerationRules {
+    pub async fn load(pool: &PgPool) -> Result<Self> {
+        let rows = sqlx::query!(
+            "SELECT role, violation, action, timeout_seconds
+             FROM moderation_rules"
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let mut rules = Self::default();
+        for row in rows {
+            rules.set(
+                row.role.parse()?,
+                row.violation.parse()?,
+                Action::from_db(&row.action, row.timeout_seconds)?,
+            );
+        }
+        Ok(rules)
+    }
+
+    pub fn set(
+        &mut self,
+        role: Permission,
+        violation: Violation,
+        action: Action,
+    ) {
+        self.0.insert((role, violation), action);
+    }
+
+    /// What to do when someone with `role` commits `violation`, if anything.
+    pub fn action(
+        &self,
+        role: Permission,
+        violation: Violation,
+    ) -> Option<Action> {
+        self.0.get(&(role, violation)).copied()
+    }
+}
+
+/// What's wrong with `contents`, if anything. The worst problem wins.
+pub fn check_contents(contents: &str) -> Option<Violation> {
+    if contents.is_inappropriate() {
+        Some(Violation::Profanity)
+    } else if contents.split_whitespace().any(is_link) {
+        Some(Violation::Links)
+    } else if is_shouting(contents) {
+        Some(Violation::Caps)
+    } else {
+        None
+    }
+}
+
+fn is_link(word: &str) -> bool {
+    let word = word.to_lowercase();
+    if word.contains("://") || word.starts_with("www.") {
+        return true;
+    }
+
+    let host = word
+        .split('/')
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches(|c: char| !c.is_alphanumeric());
+    match host.rsplit_once('.') {
+        Some((name, tld)) => {
+            !name.is_empty()
+                && !name.starts_with('.')
+                && name
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '-' || c == '.')
+                && LINK_TLDS.contains(&tld)
+        }
+        None => false,
+    }
+}
+
+fn is_shouting(contents: &str) -> bool {
+    let letters: Vec<char> =
+        contents.chars().filter(|c| c.is_alphabetic()).collect();
+    let upper = letters.iter().filter(|c| c.is_uppercase()).count();
+
+    letters.len() >= CAPS_MIN_LETTERS
+        && upper as f32 >= letters.len() as f32 * CAPS_RATIO
+}
+
+/// What everyone said lately, to catch them repeating it.
+#[derive(Debug, Default)]
+pub struct History(HashMap<(String, String), VecDeque<(Instant, String)>>);
+
+impl History {
+    /// Remembers `msg`, and says whether it was sent too often lately.
+    /// Senders are told apart by login, they could change their name.
+    pub fn is_repeat(&mut self, msg: &UserMessage, now: Instant) -> bool {
+        let login = match &msg.user_login {
+            Some(login) => login.to_lowercase(),
+            None => return false,
+        };
+        let text = msg
+            .contents
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase();
+
+        let sent = self.0.entry((msg.channel.clone(), login)).or_default();
+        sent.retain(|(at, _)| now.duration_since(*at) < REPEAT_WINDOW);
+
+        let repeats = sent.iter().filter(|(_, before)| *before == text).count();
+        sent.push_back((now, text));
+        repeats >= REPEATS_ALLOWED
+    }
+
+    pub fn forget(&mut self, channel: &str, user: &str) {
+        self.0.remove(&(channel.to_string(), normalize_user(user)));
+    }
+}
+
+/// Carries out moderation and keeps track of it.
+#[async_trait]
+pub trait ChatModerator: Send + Sync {
+    /// Takes `action` against `msg`'s sender for `violation`.
+    async fn enforce(
+        &self,
+        msg: &UserMessage,
+        violation: Violation,
+        action: Action,
+    ) -> Result<()>;
+
+    /// Takes back what was done to `pardon.user`, as far as that's possible.
+    /// Returns how many actions were pardoned.
+    async fn pardon(&self, pardon: &ModerationPardon) -> Result<u64>;
+}
+
+/// Moderates through Helix, as the broadcaster, and logs everything to
+/// `moderation_log`.
+pub struct TwitchModerator {
+    pool: PgPool,
+    tokens: TokenStore,
+
+    /// Broadcaster ids of the channels, by login.
+    channel_ids: Mutex<HashMap<String, String>>,
+}
+
+impl TwitchModerator {
+    pub fn new(pool: PgPool, tokens: TokenStore) -> Self {
+        Self {
+            pool,
+            tokens,
+            channel_ids: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn helix(&self) -> Result<HelixModeration> {
+        let token = self.tokens.user_token(TokenKind::Broadcaster).await?;
+        Ok(HelixModeration::new(&token))
+    }
+
+    async fn channel_id(
+        &self,
+        helix: &HelixModeration,
+        channel: &str,
+    ) -> Result<String> {
+        let cached = self.channel_ids.lock().unwrap().get(channel).cloned();
+        if let Some(id) = cached {
+            return Ok(id);
+        }
+
+        let id = helix
+            .user_id(channel)
+            .await?
+            .ok_or_else(|| anyhow!("there is no {} channel", channel))?;
+        self.channel_ids
+            .lock()
+            .unwrap()
+            .insert(channel.to_string(), id.clone());
+        Ok(id)
+    }
+
+    async fn act(
+        &self,
+        msg: &UserMessage,
+        violation: Violation,
+        action: Action,
+    ) -> Result<()> {
+        let helix = self.helix().await?;
+        let channel_id = self.channel_id(&helix, &msg.channel).await?;
+        let user_id = msg
+            .platform_user_id
+            .as_deref()
+            .ok_or_else(|| anyhow!("no Twitch id for {}", msg.user_name))?;
+
+        match action {
+            Action::Warn => {
+                helix.warn(&channel_id, user_id, violation.reason()).await
+            }
+            Action::Delete => {
+                let message_id = msg
+                    .message_id
+                    .as_deref()
+                    .ok_or_else(|| anyhow!("the message has no id"))?;
+                helix.delete_message(&channel_id, message_id).await
+            }
+            Action::Timeout { seconds } => {
+                helix
+                    .timeout(&channel_id, user_id, seconds, violation.reason())
+                    .await
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl ChatModerator for TwitchModerator {
+    async fn enforce(
+        &self,
+        msg: &UserMessage,
+        violation: Violation,
+        action: Action,
+    ) -> Result<()> {
+        let login = msg
+            .user_login
+            .as_deref()
+            .ok_or_else(|| anyhow!("no Twitch login for {}", msg.user_name))?;
+        let error = self
+            .act(msg, violation, action)
+            .await
+            .err()
+            .map(|err| format!("{:#}", err));
+
+        sqlx::query!(
+            r#"INSERT INTO moderation_log
+               (channel, user_login, user_id, message_id, message, violation,
+                action, timeout_seconds, error)
+               VALUES ( $1, $2, $3, $4, $5, $6, $7, $8, $9 )"#,
+            msg.channel.to_lowercase(),
+            login.to_lowercase(),
+            msg.platform_user_id,
+            msg.message_id,
+            msg.contents,
+            violation.as_str(),
+            action.as_str(),
+            action.timeout_seconds(),
+            error,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        match error {
+            Some(error) => bail!("{}", error),
+            None => Ok(()),
+        }
+    }
+
+    async fn pardon(&self, pardon: &ModerationPardon) -> Result<u64> {
+        let (channel, user) =
+            (pardon.channel.to_lowercase(), normalize_user(&pardon.user));
+
+        // Deleted messages and warnings can't be taken back, timeouts can
+        let timed_out = sqlx::query!(
+            r#"SELECT user_id FROM moderation_log
+               WHERE channel = $1 AND user_login = $2 AND action = 'timeout'
+                 AND pardoned_at IS NULL AND error IS NULL
+               ORDER BY created_at DESC LIMIT 1"#,
+            channel,
+            user,
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .and_then(|row| row.user_id);
+
+        if let Some(user_id) = timed_out {
+            let helix = self.helix().await?;
+            let channel_id = self.channel_id(&helix, &channel).await?;
+            helix.unban(&channel_id, &user_id).await?;
+        }
+
+        let pardoned = sqlx::query!(
+            r#"UPDATE moderation_log SET pardoned_by = $3, pardoned_at = NOW()
+               WHERE channel = $1 AND user_login = $2 AND pardoned_at IS NULL"#,
+            channel,
+            user,
+            normalize_user(&pardon.by),
+        )
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+
+        Ok(pardoned)
+    }
+}
+
+pub struct ModerationHandler {
+    moderator: Box<dyn ChatModerator>,
+    rules: ModerationRules,
+    history: History,
+}
+
+impl ModerationHandler {
+    pub fn new(
+        moderator: Box<dyn ChatModerator>,
+        rules: ModerationRules,
+    ) -> Self {
+        Self {
+            moderator,
+            rules,
+            history: History::default(),
+        }
+    }
+
+    /// What `msg` did wrong, if anything.
+    fn check(&mut self, msg: &UserMessage, now: Instant) -> Option<Violation> {
+        // Commands get sent over and over on purpose
+        let repeated =
+            !msg.contents.starts_with('!') && self.history.is_repeat(msg, now);
+
+        check_contents(&msg.contents)
+            .or_else(|| repeated.then_some(Violation::Repeated))
+    }
+}
+
+#[async_trait]
+impl EventHandler for ModerationHandler {
+    fn subscription(&self) -> Subscription {
+        events::only!(UserMessage, ModerationPardon)
+    }
+
+    async fn handle(
+        mut self: Box<Self>,
+        tx: broadcast::Sender<Event>,
+        mut rx: broadcast::Receiver<Event>,
+    ) -> Result<()> {
+        loop {
+            let event = events::recv(&mut rx).await?;
+            let msg = match event {
+                Event::UserMessage(msg) => msg,
+                Event::ModerationPardon(pardon) => {
+                    self.history.forget(&pardon.channel, &pardon.user);
+
+                    let user = normalize_user(&pardon.user);
+                    let message = match self.moderator.pardon(&pardon).await {
+                        Ok(0) => format!("@{} has nothing to pardon", user),
+                        Ok(_) => format!("@{} is pardoned", user),
+                        Err(err) => {
+                            eprintln!("Error pardoning {}: {:#}", user, err);
+                            format!("Couldn't pardon @{}", user)
+                        }
+                    };
+                    tx.send(Event::TwitchChatReply(ChatReply {
+                        channel: Some(pardon.channel),
+                        ..ChatReply::new(message)
+                    }))?;
+                    continue;
+                }
+                Event::Shutdown => return Ok(()),
+                _ => continue,
+            };
+
+            let role = Permission::of(&msg);
+            if role >= Permission::Moderator {
+                continue;
+            }
+
+            let violation = match self.check(&msg, Instant::now()) {
+                Some(violation) => violation,
+                None => continue,
+            };
+            let action = match self.rules.action(role, violation) {
+                Some(action) => action,
+                None => continue,
+            };
+
+            println!(
+                "Moderating {} for {}: {}",
+                msg.user_name,
+                violation.as_str(),
+                action.as_str()
+            );
+            if let Err(err) =
+                self.moderator.enforce(&msg, violation, action).await
+            {
+                println!("Error moderating {}: {:#}", msg.user_name, err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::user_message;
+    use std::sync::Arc;
+    use subd_types::Role;
+
+    #[derive(Clone, Default)]
+    struct FakeModerator {
+        enforced: Arc<Mutex<Vec<(String, Violation, Action)>>>,
+    }
+
+    #[async_trait]
+    impl ChatModerator for FakeModerator {
+        async fn enforce(
+            &self,
+            msg: &UserMessage,
+            violation: Violation,
+            action: Action,
+        ) -> Result<()> {
+            self.enforced.lock().unwrap().push((
+                msg.user_name.clone(),
+                violation,
+                action,
+            ));
+            Ok(())
+        }
+
+        async fn pardon(&self, pardon: &ModerationPardon) -> Result<u64> {
+            let mut enforced = self.enforced.lock().unwrap();
+            let before = enforced.len();
+            enforced.retain(|(user, _, _)| normalize_user(user) != pardon.user);
+            Ok((before - enforced.len()) as u64)
+        }
+    }
+
+    #[test]
+    fn contents_are_checked() {
+        assert_eq!(check_contents("hello chat"), None);
+        assert_eq!(check_contents("e.g. this one"), None);
+        assert_eq!(
+            check_contents("cheap followers at bit.ly/xyz"),
+            Some(Violation::Links)
+        );
+        assert_eq!(
+            check_contents("https://example.org"),
+            Some(Violation::Links)
+        );
+        assert_eq!(
+            check_contents("WHY IS NOBODY TALKING ABOUT THIS"),
+            Some(Violation::Caps)
+        );
+        assert_eq!(check_contents("fuck"), Some(Violation::Profanity));
+    }
+
+    #[test]
+    fn repeats_are_counted_by_login() {
+        let mut history = History::default();
+        let now = Instant::now();
+
+        let mut renamed = user_message("Spammer", "buy now");
+        renamed.user_name = "NotASpammer".to_string();
+
+        assert!(!history.is_repeat(&user_message("Spammer", "buy now"), now));
+        assert!(!history.is_repeat(&user_message("Spammer", "buy now"), now));
+        assert!(history.is_repeat(&renamed, now));
+    }
+
+    #[tokio::test]
+    async fn rules_follow_the_role() {
+        let mut rules = ModerationRules::default();
+        rules.set(Permission::Everyone, Violation::Links, Action::Delete);
+        rules.set(
+            Permission::Everyone,
+            Violation::Repeated,
+            Action::Timeout { seconds: 60 },
+        );
+
+        let mut modded = user_message("SomeMod", "go to example.com");
+        modded.roles.add_role(Role::TwitchMod);
+
+        let moderator = FakeModerator::default();
+        let sent = events::testing::run_handler(
+            ModerationHandler::new(Box::new(moderator.clone()), rules),
+            vec![
+                Event::UserMessage(user_message("Viewer", "hi")),
+                Event::UserMessage(user_message("Viewer", "go to example.com")),
+                Event::UserMessage(modded),
+                Event::UserMessage(user_message("Spammer", "buy now")),
+                Event::UserMessage(user_message("Spammer", "buy  NOW")),
+                Event::UserMessage(user_message("Spammer", "buy now")),
+                // No rule for caps
+                Event::UserMessage(user_message(
+                    "Viewer",
+                    "WHY IS NOBODY TALKING ABOUT THIS",
+                )),
+                Event::ModerationPardon(ModerationPardon {
+                    channel: "beginbot".to_string(),
+                    user: "viewer".to_string(),
+                    by: "somemod".to_string(),
+                }),
+            ],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            *moderator.enforced.lock().unwrap(),
+            vec![(
+                "Spammer".to_string(),
+                Violation::Repeated,
+                Action::Timeout { seconds: 60 }
+            )]
+        );
+        match &sent[..] {
+            [Event::TwitchChatReply(reply)] => {
+                assert_eq!(reply.message, "@viewer is pardoned");
+                assert_eq!(reply.channel.as_deref(), Some("beginbot"));
+            }
+            other => panic!("unexpected events {:?}", other),
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use crate::obs;
+use crate::obs::OBSOperations;
 use crate::obs_source;
 use anyhow::Result;
-use obws::Client as OBSClient;
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::thread;
@@ -219,7 +219,7 @@ pub async fn update_and_trigger_text_move_filter(
     source: &str,
     filter_name: &str,
     new_text: &String,
-    obs_client: &OBSClient,
+    obs_client: &dyn OBSOperations,
 ) -> Result<()> {
     let mut new_settings: MoveTextFilter = Default::default();
     new_settings.move_value_type = Some(4);
@@ -230,14 +230,14 @@ pub async fn update_and_trigger_text_move_filter(
     new_settings.custom_duration = true;
     new_settings.setting_decimals = Some(1);
 
-    let new_settings = obws::requests::filters::SetSettings {
-        source: &source,
-        filter: &filter_name,
-        settings: new_settings,
-        overlay: None,
-    };
-
-    obs_client.filters().set_settings(new_settings).await?;
+    obs_client
+        .set_filter_settings(
+            source,
+            filter_name,
+            serde_json::to_value(&new_settings)?,
+            None,
+        )
+        .await?;
 
     // This fixes the problem
     // TODO: this should be abstracted into a constant
@@ -245,12 +245,9 @@ pub async fn update_and_trigger_text_move_filter(
 
     thread::sleep(ten_millis);
 
-    let filter_enabled = obws::requests::filters::SetEnabled {
-        source: &source,
-        filter: filter_name,
-        enabled: true,
-    };
-    obs_client.filters().set_enabled(filter_enabled).await?;
+    obs_client
+        .set_filter_enabled(source, filter_name, true)
+        .await?;
     Ok(())
 }
 
@@ -265,22 +262,19 @@ pub async fn update_and_trigger_text_move_filter(
 pub async fn move_with_move_source(
     filter_name: &str,
     new_settings: MoveSourceFilterSettings,
-    obs_client: &obws::Client,
+    obs_client: &dyn OBSOperations,
 ) -> Result<()> {
     update_move_source_filters(
         obs::DEFAULT_SCENE,
         filter_name,
         new_settings,
-        &obs_client,
+        obs_client,
     )
     .await?;
 
-    let filter_enabled = obws::requests::filters::SetEnabled {
-        source: obs::DEFAULT_SCENE,
-        filter: &filter_name,
-        enabled: true,
-    };
-    obs_client.filters().set_enabled(filter_enabled).await?;
+    obs_client
+        .set_filter_enabled(obs::DEFAULT_SCENE, filter_name, true)
+        .await?;
 
     Ok(())
 }
@@ -296,14 +290,10 @@ pub async fn update_and_trigger_move_value_filter(
     filter_value: f32,
     duration: u32,
     value_type: u32,
-    obs_client: &OBSClient,
+    obs_client: &dyn OBSOperations,
 ) -> Result<()> {
     // Fetch the current settings of the filter we are going to update and trigger
-    let filter_details =
-        match obs_client.filters().get(&source, &filter_name).await {
-            Ok(val) => Ok(val),
-            Err(err) => Err(err),
-        }?;
+    let filter_details = obs_client.get_filter(source, filter_name).await?;
 
     // Parse the settings into a MoveSingleValueSetting struct
     let mut new_settings = match serde_json::from_value::<MoveSingleValueSetting>(
@@ -324,27 +314,25 @@ pub async fn update_and_trigger_move_value_filter(
     new_settings.duration = Some(duration);
     new_settings.value_type = value_type;
 
-    // Create a SetSettings struct & use it to update the OBS settings
+    // Update the OBS settings
     // TODO: Should this moved into the update_move_source_filters function?
-    let new_settings = obws::requests::filters::SetSettings {
-        source: &source,
-        filter: &filter_name,
-        settings: new_settings,
-        overlay: None,
-    };
-    obs_client.filters().set_settings(new_settings).await?;
+    obs_client
+        .set_filter_settings(
+            source,
+            filter_name,
+            serde_json::to_value(&new_settings)?,
+            None,
+        )
+        .await?;
 
     // Pause so the settings can take effect before triggering the filter
     // TODO: Extract out into variable
     thread::sleep(Duration::from_millis(400));
 
     // Trigger the filter
-    let filter_enabled = obws::requests::filters::SetEnabled {
-        source: &source,
-        filter: filter_name,
-        enabled: true,
-    };
-    obs_client.filters().set_enabled(filter_enabled).await?;
+    obs_client
+        .set_filter_enabled(source, filter_name, true)
+        .await?;
 
     // We always return Ok, because even if we fail to enable we want to continue our program
     // TODO: this might not be true since await? will bubble up an error?
@@ -360,15 +348,16 @@ async fn update_move_source_filters(
     source: &str,
     filter_name: &str,
     new_settings: MoveSourceFilterSettings,
-    obs_client: &OBSClient,
+    obs_client: &dyn OBSOperations,
 ) -> Result<()> {
-    let new_filter = obws::requests::filters::SetSettings {
-        source,
-        filter: filter_name,
-        settings: Some(new_settings),
-        overlay: Some(false),
-    };
-    obs_client.filters().set_settings(new_filter).await?;
+    obs_client
+        .set_filter_settings(
+            source,
+            filter_name,
+            serde_json::to_value(&new_settings)?,
+            Some(false),
+        )
+        .await?;
 
     Ok(())
 }
@@ -381,9 +370,9 @@ async fn update_move_source_filters(
 pub async fn fetch_source_settings(
     scene: &str,
     source: &str,
-    obs_client: &OBSClient,
+    obs_client: &dyn OBSOperations,
 ) -> Result<MoveSourceFilterSettings> {
-    let id = match obs_source::find_id(scene, source, &obs_client).await {
+    let id = match obs_source::find_id(scene, source, obs_client).await {
         Ok(val) => val,
         Err(_) => {
             return Ok(MoveSourceFilterSettings {
@@ -392,7 +381,7 @@ pub async fn fetch_source_settings(
         }
     };
 
-    let settings = match obs_client.scene_items().transform(scene, id).await {
+    let settings = match obs_client.scene_item_transform(scene, id).await {
         Ok(val) => val,
         Err(err) => {
             println!("Error Fetching Transform Settings: {:?}", err);
@@ -1,12 +1,14 @@
 use crate::move_transition;
 use crate::obs;
+use crate::obs::OBSOperations;
 use anyhow::Result;
-use obws::Client as OBSClient;
 
 const MOVE_SOURCE_FILTER_KIND: &str = "move_source_filter";
 const MOVE_VALUE_FILTER_KIND: &str = "move_value_filter";
 
-pub async fn create_soundboard_text(obs_client: &OBSClient) -> Result<()> {
+pub async fn create_soundboard_text(
+    obs_client: &dyn OBSOperations,
+) -> Result<()> {
     let scene = obs::CHARACTERS_SCENE;
 
     // let font_flags = obws::common::FontFlags{ }
@@ -39,14 +41,13 @@ pub async fn create_soundboard_text(obs_client: &OBSClient) -> Result<()> {
     // TOOD: This should be abstracted out
     let text_source_name = obs::SOUNDBOARD_TEXT_SOURCE_NAME;
     let _ = obs_client
-        .inputs()
-        .create(obws::requests::inputs::Create {
+        .create_input(
             scene,
-            input: &text_source_name,
-            kind: "text_ft2_source_v2",
-            settings: Some(text_settings),
-            enabled: Some(true),
-        })
+            &text_source_name,
+            "text_ft2_source_v2",
+            serde_json::to_value(&text_settings)?,
+            Some(true),
+        )
         .await;
 
     // THIS Is determined by the plugin/OBS
@@ -57,13 +58,15 @@ pub async fn create_soundboard_text(obs_client: &OBSClient) -> Result<()> {
         value_type: 5,
         ..Default::default()
     };
-    let new_filter = obws::requests::filters::Create {
-        source: &text_source_name,
-        filter: &filter_name,
-        kind: MOVE_VALUE_FILTER_KIND,
-        settings: Some(move_text_filter),
-    };
-    if let Err(err) = obs_client.filters().create(new_filter).await {
+    if let Err(err) = obs_client
+        .create_filter(
+            &text_source_name,
+            &filter_name,
+            MOVE_VALUE_FILTER_KIND,
+            serde_json::to_value(&move_text_filter)?,
+        )
+        .await
+    {
         println!("Error Creating Filter: {filter_name} | {:?}", err);
     };
 
@@ -75,19 +78,21 @@ pub async fn create_move_source_filter_from_file(
     source: &str,
     filter_name: &str,
     file_path: &str,
-    obs_client: &OBSClient,
+    obs_client: &dyn OBSOperations,
 ) -> Result<()> {
     let mut filter = move_transition::parse_json_into_struct(file_path);
 
     filter.source = Some(source.to_string());
 
-    let new_filter = obws::requests::filters::Create {
-        source: scene,
-        filter: filter_name,
-        kind: MOVE_SOURCE_FILTER_KIND,
-        settings: Some(filter),
-    };
-    if let Err(err) = obs_client.filters().create(new_filter).await {
+    if let Err(err) = obs_client
+        .create_filter(
+            scene,
+            filter_name,
+            MOVE_SOURCE_FILTER_KIND,
+            serde_json::to_value(&filter)?,
+        )
+        .await
+    {
         println!("Error Creating Filter: {filter_name} | {:?}", err);
     };
 
@@ -127,19 +132,21 @@ pub async fn create_move_text_value_filter(
     source: &str,
     scene_item: &str,
     filter_name: &str,
-    obs_client: &OBSClient,
+    obs_client: &dyn OBSOperations,
 ) -> Result<()> {
     let base_settings = create_move_source_filter_settings(scene_item);
     let new_settings =
         move_transition::custom_filter_settings(base_settings, 1662.0, 13.0);
 
-    let new_filter = obws::requests::filters::Create {
-        source,
-        filter: filter_name,
-        kind: MOVE_SOURCE_FILTER_KIND,
-        settings: Some(new_settings),
-    };
-    if let Err(err) = obs_client.filters().create(new_filter).await {
+    if let Err(err) = obs_client
+        .create_filter(
+            source,
+            filter_name,
+            MOVE_SOURCE_FILTER_KIND,
+            serde_json::to_value(&new_settings)?,
+        )
+        .await
+    {
         println!("Error Creating Filter: {filter_name} | {:?}", err);
     };
 
@@ -150,19 +157,21 @@ pub async fn create_move_source_filters(
     source: &str,
     scene_item: &str,
     filter_name: &str,
-    obs_client: &OBSClient,
+    obs_client: &dyn OBSOperations,
 ) -> Result<()> {
     let base_settings = create_move_source_filter_settings(scene_item);
     let new_settings =
         move_transition::custom_filter_settings(base_settings, 1662.0, 13.0);
 
-    let new_filter = obws::requests::filters::Create {
-        source,
-        filter: filter_name,
-        kind: MOVE_SOURCE_FILTER_KIND,
-        settings: Some(new_settings),
-    };
-    if let Err(err) = obs_client.filters().create(new_filter).await {
+    if let Err(err) = obs_client
+        .create_filter(
+            source,
+            filter_name,
+            MOVE_SOURCE_FILTER_KIND,
+            serde_json::to_value(&new_settings)?,
+        )
+        .await
+    {
         println!("Error Creating Filter: {filter_name} | {:?}", err);
     };
 
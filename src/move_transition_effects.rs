@@ -1,15 +1,17 @@
 use crate::move_transition;
 use crate::obs;
+use crate::obs::{Filter, OBSOperations};
 use crate::stream_fx;
 use anyhow::Result;
-use obws::responses::filters::SourceFilter;
-use obws::Client as OBSClient;
 
-pub async fn top_right(scene_item: &str, obs_client: &OBSClient) -> Result<()> {
+pub async fn top_right(
+    scene_item: &str,
+    obs_client: &dyn OBSOperations,
+) -> Result<()> {
     let base_settings = move_transition::fetch_source_settings(
         obs::DEFAULT_SCENE,
         &scene_item,
-        &obs_client,
+        obs_client,
     )
     .await?;
 
@@ -19,19 +21,19 @@ pub async fn top_right(scene_item: &str, obs_client: &OBSClient) -> Result<()> {
     move_transition::move_with_move_source(
         &filter_name,
         new_settings,
-        &obs_client,
+        obs_client,
     )
     .await
 }
 
 pub async fn bottom_right(
     scene_item: &str,
-    obs_client: &OBSClient,
+    obs_client: &dyn OBSOperations,
 ) -> Result<()> {
     let settings = move_transition::fetch_source_settings(
         obs::DEFAULT_SCENE,
         &scene_item,
-        &obs_client,
+        obs_client,
     )
     .await?;
 
@@ -41,7 +43,7 @@ pub async fn bottom_right(
     move_transition::move_with_move_source(
         &filter_name,
         new_settings,
-        &obs_client,
+        obs_client,
     )
     .await
 }
@@ -53,7 +55,7 @@ pub async fn spin(
     filter_setting_name: &str,
     filter_value: f32,
     duration: u32,
-    obs_client: &OBSClient,
+    obs_client: &dyn OBSOperations,
 ) -> Result<()> {
     // This feels like it belongs somewhere higher-up in the code
     let setting_name = match filter_setting_name {
@@ -71,7 +73,7 @@ pub async fn spin(
         duration,
         2, // not sure if this is the right value
         // THIS NEEDS TO BE ABSTRACTEDDDDDD
-        &obs_client,
+        obs_client,
     )
     .await
     {
@@ -91,18 +93,17 @@ pub async fn trigger_3d(
     filter_setting_name: &str,
     filter_value: f32,
     duration: u32,
-    obs_client: &OBSClient,
+    obs_client: &dyn OBSOperations,
 ) -> Result<()> {
     let camera_types_per_filter = stream_fx::camera_type_config();
 
     let camera_number = camera_types_per_filter[&filter_setting_name];
 
     let filter_details = obs_client
-        .filters()
-        .get(&source, obs::THE_3D_TRANSFORM_FILTER_NAME)
+        .get_filter(source, obs::THE_3D_TRANSFORM_FILTER_NAME)
         .await;
 
-    let filt: SourceFilter = match filter_details {
+    let filt: Filter = match filter_details {
         Ok(val) => val,
         Err(_) => return Ok(()),
     };
@@ -123,13 +124,14 @@ pub async fn trigger_3d(
     // Eesetting this Camera Mode
     new_settings.camera_mode = Some(camera_number);
 
-    let new_settings = obws::requests::filters::SetSettings {
-        source: &source,
-        filter: obs::THE_3D_TRANSFORM_FILTER_NAME,
-        settings: new_settings,
-        overlay: None,
-    };
-    obs_client.filters().set_settings(new_settings).await?;
+    obs_client
+        .set_filter_settings(
+            source,
+            obs::THE_3D_TRANSFORM_FILTER_NAME,
+            serde_json::to_value(&new_settings)?,
+            None,
+        )
+        .await?;
 
     move_transition::update_and_trigger_move_value_filter(
         source,
@@ -138,7 +140,7 @@ pub async fn trigger_3d(
         filter_value,
         duration,
         obs::SINGLE_SETTING_VALUE_TYPE,
-        &obs_client,
+        obs_client,
     )
     .await
 }
@@ -149,13 +151,13 @@ pub async fn trigger_ortho(
     filter_setting_name: &str,
     filter_value: f32,
     duration: u32,
-    obs_client: &OBSClient,
+    obs_client: &dyn OBSOperations,
 ) -> Result<()> {
     let move_transition_filter_name = format!("Move_{}", filter_name);
 
-    let filter_details = obs_client.filters().get(&source, &filter_name).await;
+    let filter_details = obs_client.get_filter(source, filter_name).await;
 
-    let filt: SourceFilter = match filter_details {
+    let filt: Filter = match filter_details {
         Ok(val) => val,
         Err(_) => return Ok(()),
     };
@@ -172,13 +174,14 @@ pub async fn trigger_ortho(
         }
     };
 
-    let new_settings = obws::requests::filters::SetSettings {
-        source: &source,
-        filter: filter_name,
-        settings: new_settings,
-        overlay: None,
-    };
-    obs_client.filters().set_settings(new_settings).await?;
+    obs_client
+        .set_filter_settings(
+            source,
+            filter_name,
+            serde_json::to_value(&new_settings)?,
+            None,
+        )
+        .await?;
 
     _ = move_transition::update_and_trigger_move_value_filter(
         source,
@@ -187,7 +190,7 @@ pub async fn trigger_ortho(
         filter_value,
         duration,
         obs::SINGLE_SETTING_VALUE_TYPE,
-        &obs_client,
+        obs_client,
     )
     .await;
     Ok(())
@@ -1,6 +1,12 @@
 use anyhow::Result;
+use async_trait::async_trait;
 use obws;
+use obws::requests::hotkeys::KeyModifiers;
+use obws::requests::scene_items::SceneItemTransform;
+use obws::responses::scene_items::SceneItemTransform as SceneItemTransformInfo;
+use obws::responses::StatusCode;
 use obws::Client as OBSClient;
+use serde_json::Value;
 
 // TODO: We need to audit the name all of these
 
@@ -40,28 +46,329 @@ pub const SDF_EFFECTS_FILTER_NAME: &str = "Outline";
 // Filter Constant
 pub const SINGLE_SETTING_VALUE_TYPE: u32 = 0;
 
-pub async fn create_obs_client() -> Result<OBSClient, obws::Error> {
-    let obs_websocket_port = subd_types::consts::get_obs_websocket_port()
-        .parse::<u16>()
-        .unwrap();
-    let obs_websocket_address = subd_types::consts::get_obs_websocket_address();
-    OBSClient::connect(
-        obs_websocket_address.clone(),
-        obs_websocket_port,
-        Some(""),
+/// A client of its own for our OBS. Handlers that run for the whole stream
+/// should share an `obs_connection::ObsConnection` instead, it reconnects.
+pub async fn create_obs_client() -> Result<OBSClient> {
+    let config = crate::channels::ObsConfig::ours()?;
+    Ok(connect_obs_client(
+        &config.address,
+        config.port,
+        config.password.as_deref(),
     )
-    .await
+    .await?)
+}
+
+pub async fn connect_obs_client(
+    address: &str,
+    port: u16,
+    password: Option<&str>,
+) -> Result<OBSClient, obws::Error> {
+    OBSClient::connect(address, port, password).await
+}
+
+// ========================= //
+// Talking to OBS            //
+// ========================= //
+
+/// A filter on a source, as OBS reports it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filter {
+    pub name: String,
+    pub settings: Value,
+}
+
+/// A source placed in a scene.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SceneItem {
+    pub source_name: String,
+}
+
+/// Everything we ask OBS to do.
+///
+/// Implemented for `obws::Client`, and by `testing::FakeOBS` so the commands
+/// can be tested without OBS running. Filter and input settings are passed as
+/// JSON, use `serde_json::to_value` on the typed settings structs.
+#[async_trait]
+pub trait OBSOperations: Send + Sync {
+    // Filters
+    async fn list_filters(&self, source: &str) -> Result<Vec<Filter>>;
+    async fn get_filter(&self, source: &str, filter: &str) -> Result<Filter>;
+    async fn create_filter(
+        &self,
+        source: &str,
+        filter: &str,
+        kind: &str,
+        settings: Value,
+    ) -> Result<()>;
+    async fn remove_filter(&self, source: &str, filter: &str) -> Result<()>;
+    async fn set_filter_settings(
+        &self,
+        source: &str,
+        filter: &str,
+        settings: Value,
+        overlay: Option<bool>,
+    ) -> Result<()>;
+    async fn set_filter_enabled(
+        &self,
+        source: &str,
+        filter: &str,
+        enabled: bool,
+    ) -> Result<()>;
+
+    // Scene Items
+    /// The id of `source` in `scene`, `None` if OBS says it isn't there.
+    async fn find_scene_item(
+        &self,
+        scene: &str,
+        source: &str,
+    ) -> Result<Option<i64>>;
+    async fn list_scene_items(&self, scene: &str) -> Result<Vec<SceneItem>>;
+    async fn create_scene_item(
+        &self,
+        scene: &str,
+        source: &str,
+        enabled: Option<bool>,
+    ) -> Result<()>;
+    async fn scene_item_transform(
+        &self,
+        scene: &str,
+        item_id: i64,
+    ) -> Result<SceneItemTransformInfo>;
+    async fn set_scene_item_transform(
+        &self,
+        scene: &str,
+        item_id: i64,
+        transform: SceneItemTransform,
+    ) -> Result<()>;
+    async fn set_scene_item_enabled(
+        &self,
+        scene: &str,
+        item_id: i64,
+        enabled: bool,
+    ) -> Result<()>;
+
+    // Scenes
+    async fn set_current_scene(&self, scene: &str) -> Result<()>;
+
+    // Inputs
+    async fn create_input(
+        &self,
+        scene: &str,
+        input: &str,
+        kind: &str,
+        settings: Value,
+        enabled: Option<bool>,
+    ) -> Result<()>;
+
+    // Hotkeys
+    async fn trigger_hotkey(
+        &self,
+        key: &str,
+        modifiers: KeyModifiers,
+    ) -> Result<()>;
+}
+
+#[async_trait]
+impl OBSOperations for OBSClient {
+    async fn list_filters(&self, source: &str) -> Result<Vec<Filter>> {
+        let filters = self.filters().list(source).await?;
+        Ok(filters
+            .into_iter()
+            .map(|f| Filter {
+                name: f.name,
+                settings: f.settings,
+            })
+            .collect())
+    }
+
+    async fn get_filter(&self, source: &str, filter: &str) -> Result<Filter> {
+        let details = self.filters().get(source, filter).await?;
+        Ok(Filter {
+            name: filter.to_string(),
+            settings: details.settings,
+        })
+    }
+
+    async fn create_filter(
+        &self,
+        source: &str,
+        filter: &str,
+        kind: &str,
+        settings: Value,
+    ) -> Result<()> {
+        let new_filter = obws::requests::filters::Create {
+            source,
+            filter,
+            kind,
+            settings: Some(settings),
+        };
+        self.filters().create(new_filter).await?;
+        Ok(())
+    }
+
+    async fn remove_filter(&self, source: &str, filter: &str) -> Result<()> {
+        self.filters().remove(source, filter).await?;
+        Ok(())
+    }
+
+    async fn set_filter_settings(
+        &self,
+        source: &str,
+        filter: &str,
+        settings: Value,
+        overlay: Option<bool>,
+    ) -> Result<()> {
+        let new_settings = obws::requests::filters::SetSettings {
+            source,
+            filter,
+            settings,
+            overlay,
+        };
+        self.filters().set_settings(new_settings).await?;
+        Ok(())
+    }
+
+    async fn set_filter_enabled(
+        &self,
+        source: &str,
+        filter: &str,
+        enabled: bool,
+    ) -> Result<()> {
+        let filter_enabled = obws::requests::filters::SetEnabled {
+            source,
+            filter,
+            enabled,
+        };
+        self.filters().set_enabled(filter_enabled).await?;
+        Ok(())
+    }
+
+    async fn find_scene_item(
+        &self,
+        scene: &str,
+        source: &str,
+    ) -> Result<Option<i64>> {
+        let id_search = obws::requests::scene_items::Id {
+            scene,
+            source,
+            ..Default::default()
+        };
+        match self.scene_items().id(id_search).await {
+            Ok(id) => Ok(Some(id)),
+            Err(obws::Error::Api {
+                code: StatusCode::ResourceNotFound,
+                ..
+            }) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn list_scene_items(&self, scene: &str) -> Result<Vec<SceneItem>> {
+        let items = self.scene_items().list(scene).await?;
+        Ok(items
+            .into_iter()
+            .map(|item| SceneItem {
+                source_name: item.source_name,
+            })
+            .collect())
+    }
+
+    async fn create_scene_item(
+        &self,
+        scene: &str,
+        source: &str,
+        enabled: Option<bool>,
+    ) -> Result<()> {
+        let new_scene = obws::requests::scene_items::CreateSceneItem {
+            scene,
+            source,
+            enabled,
+        };
+        self.scene_items().create(new_scene).await?;
+        Ok(())
+    }
+
+    async fn scene_item_transform(
+        &self,
+        scene: &str,
+        item_id: i64,
+    ) -> Result<SceneItemTransformInfo> {
+        Ok(self.scene_items().transform(scene, item_id).await?)
+    }
+
+    async fn set_scene_item_transform(
+        &self,
+        scene: &str,
+        item_id: i64,
+        transform: SceneItemTransform,
+    ) -> Result<()> {
+        let set_transform = obws::requests::scene_items::SetTransform {
+            scene,
+            item_id,
+            transform,
+        };
+        self.scene_items().set_transform(set_transform).await?;
+        Ok(())
+    }
+
+    async fn set_scene_item_enabled(
+        &self,
+        scene: &str,
+        item_id: i64,
+        enabled: bool,
+    ) -> Result<()> {
+        let set_enabled = obws::requests::scene_items::SetEnabled {
+            scene,
+            item_id,
+            enabled,
+        };
+        self.scene_items().set_enabled(set_enabled).await?;
+        Ok(())
+    }
+
+    async fn set_current_scene(&self, scene: &str) -> Result<()> {
+        self.scenes().set_current_program_scene(scene).await?;
+        Ok(())
+    }
+
+    async fn create_input(
+        &self,
+        scene: &str,
+        input: &str,
+        kind: &str,
+        settings: Value,
+        enabled: Option<bool>,
+    ) -> Result<()> {
+        let new_input = obws::requests::inputs::Create {
+            scene,
+            input,
+            kind,
+            settings: Some(settings),
+            enabled,
+        };
+        self.inputs().create(new_input).await?;
+        Ok(())
+    }
+
+    async fn trigger_hotkey(
+        &self,
+        key: &str,
+        modifiers: KeyModifiers,
+    ) -> Result<()> {
+        self.hotkeys().trigger_by_sequence(key, modifiers).await?;
+        Ok(())
+    }
 }
 
 // TODO: Find the proper home for this
 pub async fn print_filter_info(
     source: &str,
     words: &str,
-    obs_client: &OBSClient,
+    obs_client: &dyn OBSOperations,
 ) -> Result<String> {
     println!("Finding Filter Details {:?}", words);
 
-    let filter_details = match obs_client.filters().get(source, words).await {
+    let filter_details = match obs_client.get_filter(source, words).await {
         Ok(details) => details,
         Err(_) => {
             println!("Error Fetching Filter Details: {:?}", words);
@@ -1,15 +1,49 @@
+use crate::commands::registry::CommandError;
 use crate::move_transition;
+use crate::move_transition_effects;
 use crate::obs;
+use crate::obs::OBSOperations;
 use crate::obs_hotkeys;
 use crate::obs_source;
 use anyhow::Result;
 use obws;
-use obws::requests::scene_items::{Scale, SceneItemTransform, SetTransform};
-use obws::Client as OBSClient;
+use obws::requests::scene_items::{Scale, SceneItemTransform};
+
+/// Combos that can be set off by name, like from the cheer tiers.
+pub const COMBOS: &[&str] = &["blur", "norm", "spin", "staff"];
+
+/// Run the combo called `name` on `source`.
+pub async fn trigger(
+    name: &str,
+    source: &str,
+    obs_client: &dyn OBSOperations,
+) -> Result<()> {
+    match name {
+        "blur" => {
+            move_transition::update_and_trigger_move_value_filter(
+                source,
+                obs::MOVE_BLUR_FILTER_NAME,
+                "Filter.Blur.Size",
+                100.0,
+                3000,
+                0,
+                obs_client,
+            )
+            .await
+        }
+        "norm" => norm(source, obs_client).await,
+        "spin" => {
+            move_transition_effects::spin(source, "z", 360.0, 3000, obs_client)
+                .await
+        }
+        "staff" => staff(source, obs_client).await,
+        _ => Err(CommandError(format!("unknown combo '{}'", name)).into()),
+    }
+}
 
 pub async fn trigger_character_filters(
     base_source: &str,
-    obs_client: &OBSClient,
+    obs_client: &dyn OBSOperations,
     enabled: bool,
 ) -> Result<()> {
     let scene = "Characters";
@@ -20,67 +54,52 @@ pub async fn trigger_character_filters(
     };
 
     let filter_name = format!("{}{}", filter_name_modifier, base_source);
-    let filter_enabled = obws::requests::filters::SetEnabled {
-        source: scene,
-        filter: &filter_name,
-        enabled: true,
-    };
-    obs_client.filters().set_enabled(filter_enabled).await?;
+    obs_client
+        .set_filter_enabled(scene, &filter_name, true)
+        .await?;
 
     let filter_name = format!("{}{}-text", filter_name_modifier, base_source);
-    let filter_enabled = obws::requests::filters::SetEnabled {
-        source: scene,
-        filter: &filter_name,
-        enabled: true,
-    };
-    obs_client.filters().set_enabled(filter_enabled).await?;
+    obs_client
+        .set_filter_enabled(scene, &filter_name, true)
+        .await?;
 
     let filter_name =
         format!("{}{}-speech_bubble", filter_name_modifier, base_source);
-    let filter_enabled = obws::requests::filters::SetEnabled {
-        source: scene,
-        filter: &filter_name,
-        enabled: true,
-    };
-    obs_client.filters().set_enabled(filter_enabled).await?;
+    obs_client
+        .set_filter_enabled(scene, &filter_name, true)
+        .await?;
 
     Ok(())
 }
 
-pub async fn norm(source: &str, obs_client: &OBSClient) -> Result<()> {
+pub async fn norm(source: &str, obs_client: &dyn OBSOperations) -> Result<()> {
     println!("Attempting to Make: {source} normal!");
 
-    let filter_enabled = obws::requests::filters::SetEnabled {
-        source: &source,
-        filter: &obs::DEFAULT_STREAM_FX_FILTER_NAME,
-        enabled: true,
-    };
-    match obs_client.filters().set_enabled(filter_enabled).await {
+    match obs_client
+        .set_filter_enabled(source, obs::DEFAULT_STREAM_FX_FILTER_NAME, true)
+        .await
+    {
         Ok(_) => {}
         Err(_) => return Ok(()),
     }
-    let filter_enabled = obws::requests::filters::SetEnabled {
-        source: &source,
-        filter: &obs::DEFAULT_SCROLL_FILTER_NAME,
-        enabled: true,
-    };
     // This is not the way
-    match obs_client.filters().set_enabled(filter_enabled).await {
+    match obs_client
+        .set_filter_enabled(source, obs::DEFAULT_SCROLL_FILTER_NAME, true)
+        .await
+    {
         Ok(_) => {}
         Err(_) => return Ok(()),
     }
-    let filter_enabled = obws::requests::filters::SetEnabled {
-        source: &source,
-        filter: &obs::DEFAULT_BLUR_FILTER_NAME,
-        enabled: true,
-    };
-    match obs_client.filters().set_enabled(filter_enabled).await {
+    match obs_client
+        .set_filter_enabled(source, obs::DEFAULT_BLUR_FILTER_NAME, true)
+        .await
+    {
         Ok(_) => {}
         Err(_) => return Ok(()),
     }
 
     let id =
-        match obs_source::find_id(obs::MEME_SCENE, source, &obs_client).await {
+        match obs_source::find_id(obs::MEME_SCENE, source, obs_client).await {
             Ok(val) => val,
             Err(_) => return Ok(()),
         };
@@ -89,7 +108,7 @@ pub async fn norm(source: &str, obs_client: &OBSClient) -> Result<()> {
         x: Some(1.0),
         y: Some(1.0),
     };
-    match scale(id, new_scale, &obs_client).await {
+    match scale(id, new_scale, obs_client).await {
         Ok(_) => {}
         Err(e) => println!("{:?}", e),
     }
@@ -105,28 +124,22 @@ pub async fn norm(source: &str, obs_client: &OBSClient) -> Result<()> {
 pub async fn scale(
     id: i64,
     new_scale: Scale,
-    obs_client: &OBSClient,
+    obs_client: &dyn OBSOperations,
 ) -> Result<()> {
     let scene_transform = SceneItemTransform {
         scale: Some(new_scale),
         ..Default::default()
     };
 
-    let set_transform = SetTransform {
-        scene: obs::DEFAULT_SCENE,
-        item_id: id,
-        transform: scene_transform,
-    };
     obs_client
-        .scene_items()
-        .set_transform(set_transform)
+        .set_scene_item_transform(obs::DEFAULT_SCENE, id, scene_transform)
         .await?;
     Ok(())
 }
 
 // =======================================================================================
 
-pub async fn staff(source: &str, obs_client: &OBSClient) -> Result<()> {
+pub async fn staff(source: &str, obs_client: &dyn OBSOperations) -> Result<()> {
     _ = move_transition::update_and_trigger_move_value_filter(
         source,
         "Move_Blur",
@@ -134,7 +147,7 @@ pub async fn staff(source: &str, obs_client: &OBSClient) -> Result<()> {
         100.0,
         5000,
         2,
-        &obs_client,
+        obs_client,
     )
     .await;
 
@@ -150,14 +163,13 @@ pub async fn staff(source: &str, obs_client: &OBSClient) -> Result<()> {
         filter_value,
         duration,
         2,
-        &obs_client,
+        obs_client,
     )
     .await?;
 
     // TODO: This should triggered from a fucntion
     obs_client
-        .hotkeys()
-        .trigger_by_sequence("OBS_KEY_U", obs_hotkeys::SUPER_KEY)
+        .trigger_hotkey("OBS_KEY_U", obs_hotkeys::SUPER_KEY)
         .await?;
     Ok(())
 }
@@ -167,10 +179,10 @@ pub async fn follow(
     scene: &str,
     source: &str,
     leader: &str,
-    obs_client: &OBSClient,
+    obs_client: &dyn OBSOperations,
 ) -> Result<()> {
     let id =
-        match obs_source::find_id(obs::MEME_SCENE, source, &obs_client).await {
+        match obs_source::find_id(obs::MEME_SCENE, source, obs_client).await {
             Ok(val) => val,
             Err(_) => return Ok(()),
         };
@@ -184,20 +196,20 @@ pub async fn follow(
         "TwitchAlerts".to_string(),
     ];
 
-    let leader_settings =
-        match obs_client.scene_items().transform(scene, id).await {
-            Ok(val) => val,
-            Err(err) => {
-                println!("Error Fetching Transform Settings: {:?}", err);
-                let blank_transform =
-                    obws::responses::scene_items::SceneItemTransform {
-                        ..Default::default()
-                    };
-                blank_transform
-            }
-        };
+    let leader_settings = match obs_client.scene_item_transform(scene, id).await
+    {
+        Ok(val) => val,
+        Err(err) => {
+            println!("Error Fetching Transform Settings: {:?}", err);
+            let blank_transform =
+                obws::responses::scene_items::SceneItemTransform {
+                    ..Default::default()
+                };
+            blank_transform
+        }
+    };
 
-    let sources = obs_client.scene_items().list(obs::DEFAULT_SCENE).await?;
+    let sources = obs_client.list_scene_items(obs::DEFAULT_SCENE).await?;
     for s in sources {
         for bad_source in &untouchable_sources {
             if bad_source == &s.source_name {
@@ -208,7 +220,7 @@ pub async fn follow(
         let base_settings = match move_transition::fetch_source_settings(
             obs::DEFAULT_SCENE,
             &s.source_name,
-            &obs_client,
+            obs_client,
         )
         .await
         {
@@ -228,7 +240,7 @@ pub async fn follow(
             _ = move_transition::move_with_move_source(
                 &filter_name,
                 new_settings,
-                &obs_client,
+                obs_client,
             )
             .await;
         }
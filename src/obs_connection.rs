@@ -0,0 +1,359 @@
+//! One connection to OBS that every handler shares.
+//!
+//! `ObsConnection` is cheap to clone and does everything `OBSOperations`
+//! does. `ObsConnectionHandler` keeps it connected: when OBS goes away it
+//! connects again, waiting a bit longer each time, and sends every change as
+//! `Event::ObsConnectionStatus`. While OBS is gone, requests wait a little
+//! for it to come back, and fail if it doesn't.
+
+use crate::channels::ObsConfig;
+use crate::obs::{self, Filter, OBSOperations, SceneItem};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use events::{Backoff, EventHandler, Subscription};
+use obws::requests::hotkeys::KeyModifiers;
+use obws::requests::scene_items::SceneItemTransform;
+use obws::responses::scene_items::SceneItemTransform as SceneItemTransformInfo;
+use obws::Client as OBSClient;
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration;
+use subd_types::{ConnectionStatus, Event};
+use tokio::sync::{broadcast, watch};
+use tracing::{info, warn};
+
+/// How long requests wait for OBS to come back.
+const WAIT_FOR: Duration = Duration::from_secs(10);
+
+/// How often we check that OBS is still there.
+const HEARTBEAT: Duration = Duration::from_secs(5);
+
+#[derive(Clone)]
+pub struct ObsConnection {
+    config: ObsConfig,
+    wait: Duration,
+
+    /// The client, while we're connected.
+    tx: Arc<watch::Sender<Option<Arc<OBSClient>>>>,
+    rx: watch::Receiver<Option<Arc<OBSClient>>>,
+}
+
+impl ObsConnection {
+    /// Doesn't connect yet, `ObsConnectionHandler` does that.
+    pub fn new(config: ObsConfig) -> Self {
+        let (tx, rx) = watch::channel(None);
+        Self {
+            config,
+            wait: WAIT_FOR,
+            tx: Arc::new(tx),
+            rx,
+        }
+    }
+
+    /// How long requests wait for OBS to come back. With zero they fail
+    /// right away instead.
+    pub fn wait(mut self, wait: Duration) -> Self {
+        self.wait = wait;
+        self
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.rx.borrow().is_some()
+    }
+
+    async fn client(&self) -> Result<Arc<OBSClient>> {
+        let mut rx = self.rx.clone();
+        let connected = async {
+            loop {
+                let client = rx.borrow().clone();
+                if let Some(client) = client {
+                    return Ok::<_, anyhow::Error>(client);
+                }
+                rx.changed().await?;
+            }
+        };
+
+        match tokio::time::timeout(self.wait, connected).await {
+            Ok(client) => client,
+            Err(_) => Err(anyhow!(
+                "OBS at {}:{} isn't connected",
+                self.config.address,
+                self.config.port
+            )),
+        }
+    }
+
+    fn set_client(&self, client: Option<Arc<OBSClient>>) {
+        // We hold a receiver ourselves, so this can't fail
+        let _ = self.tx.send(client);
+    }
+}
+
+#[async_trait]
+impl OBSOperations for ObsConnection {
+    async fn list_filters(&self, source: &str) -> Result<Vec<Filter>> {
+        self.client().await?.list_filters(source).await
+    }
+
+    async fn get_filter(&self, source: &str, filter: &str) -> Result<Filter> {
+        self.client().await?.get_filter(source, filter).await
+    }
+
+    async fn create_filter(
+        &self,
+        source: &str,
+        filter: &str,
+        kind: &str,
+        settings: Value,
+    ) -> Result<()> {
+        self.client()
+            .await?
+            .create_filter(source, filter, kind, settings)
+            .await
+    }
+
+    async fn remove_filter(&self, source: &str, filter: &str) -> Result<()> {
+        self.client().await?.remove_filter(source, filter).await
+    }
+
+    async fn set_filter_settings(
+        &self,
+        source: &str,
+        filter: &str,
+        settings: Value,
+        overlay: Option<bool>,
+    ) -> Result<()> {
+        self.client()
+            .await?
+            .set_filter_settings(source, filter, settings, overlay)
+            .await
+    }
+
+    async fn set_filter_enabled(
+        &self,
+        source: &str,
+        filter: &str,
+        enabled: bool,
+    ) -> Result<()> {
+        self.client()
+            .await?
+            .set_filter_enabled(source, filter, enabled)
+            .await
+    }
+
+    async fn find_scene_item(
+        &self,
+        scene: &str,
+        source: &str,
+    ) -> Result<Option<i64>> {
+        self.client().await?.find_scene_item(scene, source).await
+    }
+
+    async fn list_scene_items(&self, scene: &str) -> Result<Vec<SceneItem>> {
+        self.client().await?.list_scene_items(scene).await
+    }
+
+    async fn create_scene_item(
+        &self,
+        scene: &str,
+        source: &str,
+        enabled: Option<bool>,
+    ) -> Result<()> {
+        self.client()
+            .await?
+            .create_scene_item(scene, source, enabled)
+            .await
+    }
+
+    async fn scene_item_transform(
+        &self,
+        scene: &str,
+        item_id: i64,
+    ) -> Result<SceneItemTransformInfo> {
+        self.client()
+            .await?
+            .scene_item_transform(scene, item_id)
+            .await
+    }
+
+    async fn set_scene_item_transform(
+        &self,
+        scene: &str,
+        item_id: i64,
+        transform: SceneItemTransform,
+    ) -> Result<()> {
+        self.client()
+            .await?
+            .set_scene_item_transform(scene, item_id, transform)
+            .await
+    }
+
+    async fn set_scene_item_enabled(
+        &self,
+        scene: &str,
+        item_id: i64,
+        enabled: bool,
+    ) -> Result<()> {
+        self.client()
+            .await?
+            .set_scene_item_enabled(scene, item_id, enabled)
+            .await
+    }
+
+    async fn set_current_scene(&self, scene: &str) -> Result<()> {
+        self.client().await?.set_current_scene(scene).await
+    }
+
+    async fn create_input(
+        &self,
+        scene: &str,
+        input: &str,
+        kind: &str,
+        settings: Value,
+        enabled: Option<bool>,
+    ) -> Result<()> {
+        self.client()
+            .await?
+            .create_input(scene, input, kind, settings, enabled)
+            .await
+    }
+
+    async fn trigger_hotkey(
+        &self,
+        key: &str,
+        modifiers: KeyModifiers,
+    ) -> Result<()> {
+        self.client().await?.trigger_hotkey(key, modifiers).await
+    }
+}
+
+/// Keeps an `ObsConnection` connected for as long as the bot runs.
+pub struct ObsConnectionHandler {
+    connection: ObsConnection,
+    channel: Option<String>,
+    backoff: Backoff,
+}
+
+impl ObsConnectionHandler {
+    /// `channel` is who the OBS belongs to, `None` for ours.
+    pub fn new(connection: ObsConnection, channel: Option<String>) -> Self {
+        Self {
+            connection,
+            channel,
+            backoff: Backoff::default(),
+        }
+    }
+
+    /// How long to wait between reconnects.
+    pub fn backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    fn status(&self, status: ConnectionStatus) -> Event {
+        Event::ObsConnectionStatus {
+            channel: self.channel.clone(),
+            status,
+        }
+    }
+
+    /// Only returns if the bus is gone.
+    async fn stay_connected(
+        &self,
+        tx: &broadcast::Sender<Event>,
+    ) -> Result<()> {
+        let config = &self.connection.config;
+        let mut attempt = 0;
+        loop {
+            tx.send(self.status(ConnectionStatus::Connecting))?;
+
+            let result = match obs::connect_obs_client(
+                &config.address,
+                config.port,
+                config.password.as_deref(),
+            )
+            .await
+            {
+                Ok(client) => {
+                    attempt = 0;
+                    let client = Arc::new(client);
+                    self.connection.set_client(Some(client.clone()));
+                    tx.send(self.status(ConnectionStatus::Connected))?;
+                    heartbeat(&client).await
+                }
+                Err(err) => Err(err.into()),
+            };
+            self.connection.set_client(None);
+
+            let reason = match result {
+                Ok(()) => "closed by OBS".to_string(),
+                Err(err) => format!("{:#}", err),
+            };
+            let retry_in = self.backoff.delay(attempt);
+            attempt += 1;
+
+            warn!(%reason, ?retry_in, channel = ?self.channel, "lost OBS");
+            tx.send(self.status(ConnectionStatus::Disconnected {
+                reason,
+                retry_in_ms: retry_in.as_millis() as u64,
+            }))?;
+            tokio::time::sleep(retry_in).await;
+        }
+    }
+}
+
+/// Returns once OBS stops answering.
+async fn heartbeat(client: &OBSClient) -> Result<()> {
+    loop {
+        tokio::time::sleep(HEARTBEAT).await;
+        client.general().version().await?;
+    }
+}
+
+#[async_trait]
+impl EventHandler for ObsConnectionHandler {
+    fn subscription(&self) -> Subscription {
+        events::only!(Shutdown)
+    }
+
+    async fn handle(
+        self: Box<Self>,
+        tx: broadcast::Sender<Event>,
+        mut rx: broadcast::Receiver<Event>,
+    ) -> Result<()> {
+        let connection = self.stay_connected(&tx);
+        tokio::pin!(connection);
+
+        loop {
+            tokio::select! {
+                result = &mut connection => return result,
+                event = events::recv(&mut rx) => {
+                    if let Event::Shutdown = event? {
+                        info!("closing the OBS connection");
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn requests_fail_while_obs_is_gone() {
+        let connection = ObsConnection::new(ObsConfig {
+            address: "localhost".to_string(),
+            port: 4455,
+            password: None,
+        })
+        .wait(Duration::from_millis(10));
+
+        let err = connection.set_current_scene("Primary").await.unwrap_err();
+
+        assert!(!connection.is_connected());
+        assert_eq!(err.to_string(), "OBS at localhost:4455 isn't connected");
+    }
+}
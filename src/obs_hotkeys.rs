@@ -1,6 +1,6 @@
+use crate::obs::OBSOperations;
 use anyhow::Result;
 use obws;
-use obws::Client as OBSClient;
 
 pub const SUPER_KEY: obws::requests::hotkeys::KeyModifiers =
     obws::requests::hotkeys::KeyModifiers {
@@ -10,10 +10,10 @@ pub const SUPER_KEY: obws::requests::hotkeys::KeyModifiers =
         command: true,
     };
 
-pub async fn trigger_hotkey(key: &str, obs_client: &OBSClient) -> Result<()> {
-    _ = obs_client
-        .hotkeys()
-        .trigger_by_sequence(key, SUPER_KEY)
-        .await;
+pub async fn trigger_hotkey(
+    key: &str,
+    obs_client: &dyn OBSOperations,
+) -> Result<()> {
+    _ = obs_client.trigger_hotkey(key, SUPER_KEY).await;
     Ok(())
 }
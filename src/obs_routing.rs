@@ -1,73 +1,860 @@
 use crate::bootstrap;
+use crate::cheers::{self, CheerTotal};
+use crate::commands::registry::{
+    Arg, Command, CommandError, Permission, Registry,
+};
+use crate::cooldowns::{self, Cooldowns, Limit};
 use crate::move_transition;
 use crate::move_transition_bootstrap;
 use crate::move_transition_effects;
 use crate::obs;
+use crate::obs::OBSOperations;
 use crate::obs_combo;
 use crate::obs_hotkeys;
 use crate::obs_scenes;
 use crate::obs_source;
+use crate::permissions::{self, Grants};
 use crate::sdf_effects;
 use crate::stream_character;
 use crate::stream_fx;
+use crate::sub_count;
 use crate::twitch_stream_state;
 use crate::uberduck;
 use anyhow::{bail, Result};
-use obws;
+use async_trait::async_trait;
+use events::{EventHandler, Subscription};
 use obws::requests::scene_items::Scale;
-use obws::Client as OBSClient;
-use subd_types::{Event, UserMessage};
+use once_cell::sync::OnceCell;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use subd_types::{ChatReply, Event, ModerationPardon, UserMessage};
 use tokio::sync::broadcast;
 
+pub struct OBSMessageHandler {
+    pub obs_client: Box<dyn OBSOperations>,
+
+    /// OBS of the channels that have their own, by login. Commands from
+    /// every other channel go to `obs_client`.
+    pub channel_obs: HashMap<String, Box<dyn OBSOperations>>,
+
+    pub store: Box<dyn CommandStore>,
+    pub cooldowns: Cooldowns,
+    pub grants: Grants,
+}
+
+/// Everything the OBS commands read from or save to the database.
+#[async_trait]
+pub trait CommandStore: Send + Sync {
+    async fn save_limit(&self, command: &str, limit: Limit) -> Result<()>;
+    async fn save_burst(
+        &self,
+        permission: Permission,
+        burst: u32,
+    ) -> Result<()>;
+    async fn save_grant(
+        &self,
+        channel: &str,
+        user: &str,
+        command: &str,
+        granted_by: &str,
+    ) -> Result<()>;
+    async fn delete_grant(
+        &self,
+        channel: &str,
+        user: &str,
+        command: &str,
+    ) -> Result<()>;
+    async fn record_refusal(
+        &self,
+        channel: &str,
+        user: &str,
+        command: &str,
+        required: Permission,
+    ) -> Result<()>;
+    async fn set_implicit_soundeffects(
+        &self,
+        channel: &str,
+        soundeffects: bool,
+    ) -> Result<()>;
+    async fn save_goal(
+        &self,
+        title: &str,
+        target: usize,
+        set_by: &str,
+    ) -> Result<()>;
+    async fn cheer_total(&self, channel: &str, user_name: &str) -> Result<i64>;
+    async fn cheer_leaderboard(
+        &self,
+        channel: &str,
+        limit: i64,
+    ) -> Result<Vec<CheerTotal>>;
+    async fn set_voice(
+        &self,
+        voice: String,
+        username: String,
+        channel: &str,
+    ) -> Result<()>;
+}
+
+#[async_trait]
+impl CommandStore for PgPool {
+    async fn save_limit(&self, command: &str, limit: Limit) -> Result<()> {
+        cooldowns::save_limit(self, command, limit).await
+    }
+
+    async fn save_burst(
+        &self,
+        permission: Permission,
+        burst: u32,
+    ) -> Result<()> {
+        cooldowns::save_burst(self, permission, burst).await
+    }
+
+    async fn save_grant(
+        &self,
+        channel: &str,
+        user: &str,
+        command: &str,
+        granted_by: &str,
+    ) -> Result<()> {
+        permissions::save_grant(self, channel, user, command, granted_by).await
+    }
+
+    async fn delete_grant(
+        &self,
+        channel: &str,
+        user: &str,
+        command: &str,
+    ) -> Result<()> {
+        permissions::delete_grant(self, channel, user, command).await
+    }
+
+    async fn record_refusal(
+        &self,
+        channel: &str,
+        user: &str,
+        command: &str,
+        required: Permission,
+    ) -> Result<()> {
+        permissions::record_refusal(self, channel, user, command, required)
+            .await
+    }
+
+    async fn set_implicit_soundeffects(
+        &self,
+        channel: &str,
+        soundeffects: bool,
+    ) -> Result<()> {
+        twitch_stream_state::update_implicit_soundeffects(
+            channel,
+            soundeffects,
+            self,
+        )
+        .await
+    }
+
+    async fn save_goal(
+        &self,
+        title: &str,
+        target: usize,
+        set_by: &str,
+    ) -> Result<()> {
+        sub_count::save_goal(self, title, target, set_by).await
+    }
+
+    async fn cheer_total(&self, channel: &str, user_name: &str) -> Result<i64> {
+        cheers::total_for(self, channel, user_name).await
+    }
+
+    async fn cheer_leaderboard(
+        &self,
+        channel: &str,
+        limit: i64,
+    ) -> Result<Vec<CheerTotal>> {
+        cheers::leaderboard(self, channel, limit).await
+    }
+
+    async fn set_voice(
+        &self,
+        voice: String,
+        username: String,
+        channel: &str,
+    ) -> Result<()> {
+        uberduck::set_voice(voice, username, channel, self).await
+    }
+}
+
+#[async_trait]
+impl EventHandler for OBSMessageHandler {
+    fn subscription(&self) -> Subscription {
+        events::only!(UserMessage)
+    }
+
+    async fn handle(
+        self: Box<Self>,
+        tx: broadcast::Sender<Event>,
+        mut rx: broadcast::Receiver<Event>,
+    ) -> Result<()> {
+        let Self {
+            obs_client,
+            channel_obs,
+            store,
+            mut cooldowns,
+            mut grants,
+        } = *self;
+
+        loop {
+            let event = events::recv(&mut rx).await?;
+            let msg = match event {
+                Event::UserMessage(msg) => msg,
+                Event::Shutdown => return Ok(()),
+                _ => continue,
+            };
+            let splitmsg = msg
+                .contents
+                .split(" ")
+                .map(|s| s.to_string())
+                .collect::<Vec<String>>();
+
+            let channel_client = match channel_obs.get(&msg.channel) {
+                Some(client) => client.as_ref(),
+                None => obs_client.as_ref(),
+            };
+
+            match handle_obs_commands(
+                &tx,
+                channel_client,
+                store.as_ref(),
+                &mut cooldowns,
+                &mut grants,
+                splitmsg,
+                msg.clone(),
+            )
+            .await
+            {
+                Ok(_) => {}
+                Err(err) => match err.downcast_ref::<CommandError>() {
+                    Some(err) => reply(&tx, &msg, err.to_string())?,
+                    None => {
+                        eprintln!("Error running {}: {:#}", msg.contents, err)
+                    }
+                },
+            }
+        }
+    }
+}
+
+/// Every command `OBSMessageHandler` answers to.
+pub fn obs_commands() -> &'static Registry {
+    static OBS_COMMANDS: OnceCell<Registry> = OnceCell::new();
+    OBS_COMMANDS.get_or_init(build_obs_commands)
+}
+
+fn source() -> Arg {
+    Arg::text("source").default(obs::DEFAULT_SOURCE)
+}
+
+fn duration() -> Arg {
+    Arg::int("duration").default(3000)
+}
+
+/// Reply to `msg` in chat.
+fn reply(
+    tx: &broadcast::Sender<Event>,
+    msg: &UserMessage,
+    message: impl Into<String>,
+) -> Result<()> {
+    tx.send(Event::TwitchChatReply(ChatReply::to(msg, message)))?;
+    Ok(())
+}
+
+fn build_obs_commands() -> Registry {
+    Registry::new()
+        .command(
+            Command::new("!help", "List the commands, or explain one")
+                .arg(Arg::text("command").optional())
+                .example("!help")
+                .example("!help blur"),
+        )
+        // ===========================================
+        // == Stream State
+        // ===========================================
+        .command(
+            Command::new(
+                "!shutdown",
+                "Shut the bot down cleanly. Every handler gets a few seconds \
+                 to finish what it is doing (TTS gets to finish its clip), \
+                 then the database pool is closed and a summary of the \
+                 handlers is printed. Ctrl-C does the same.",
+            )
+            .permission(Permission::Moderator),
+        )
+        .command(
+            Command::new(
+                "!cooldown",
+                "Show or change how long before a command can be used again, \
+                 by anyone and by the same user. * is the limit shared by \
+                 every command.",
+            )
+            .arg(Arg::text("command"))
+            .arg(Arg::float("seconds").optional())
+            .arg(Arg::float("user_seconds").optional())
+            .permission(Permission::Moderator)
+            .example("!cooldown spin")
+            .example("!cooldown !random 5 30")
+            .example("!cooldown * 0 2"),
+        )
+        .command(
+            Command::new(
+                "!burst",
+                "Show or change how many times a role can use a command \
+                 before its per user cooldown kicks in",
+            )
+            .arg(Arg::text("role"))
+            .arg(Arg::int("burst").optional())
+            .permission(Permission::Moderator)
+            .example("!burst subs 2"),
+        )
+        .command(
+            Command::new(
+                "!grant",
+                "Let a user run a command their roles don't allow",
+            )
+            .arg(Arg::text("user"))
+            .arg(Arg::text("command"))
+            .permission(Permission::Broadcaster)
+            .example("!grant @some_viewer create_source"),
+        )
+        .command(
+            Command::new("!revoke", "Take back a command given with !grant")
+                .arg(Arg::text("user"))
+                .arg(Arg::text("command"))
+                .permission(Permission::Broadcaster)
+                .example("!revoke @some_viewer create_source"),
+        )
+        .command(
+            Command::new(
+                "!pardon",
+                "Take back what moderation did to a user, lifting any timeout",
+            )
+            .arg(Arg::text("user"))
+            .permission(Permission::Moderator)
+            .example("!pardon @some_viewer"),
+        )
+        .command(
+            Command::new("!implicit", "Turn implicit sound effects on")
+                .permission(Permission::Moderator),
+        )
+        .command(
+            Command::new(
+                "!mode",
+                "Switch the stream mode, turning channel point rewards on or \
+                 off to match",
+            )
+            .arg(Arg::text("mode"))
+            .permission(Permission::Broadcaster)
+            .example("!mode work"),
+        )
+        .command(
+            Command::new(
+                "!subgoal",
+                "Set the sub goal shown on the overlay, the rest of the \
+                 message is its title. A TARGET of 0 takes it down",
+            )
+            .arg(Arg::int("target"))
+            .arg(Arg::text("title").optional())
+            .permission(Permission::Broadcaster)
+            .example("!subgoal 50 Crab rave at 50 subs"),
+        )
+        .command(
+            Command::new(
+                "!cheers",
+                "Show who has cheered the most bits, or how many USER has",
+            )
+            .arg(Arg::text("user").optional())
+            .example("!cheers")
+            .example("!cheers @nyxkrage"),
+        )
+        // ===========================================
+        // == Voices & Characters
+        // ===========================================
+        .command(
+            Command::new("!random", "Say your message in a random voice")
+                .example("!random hello chat"),
+        )
+        .command(
+            Command::new("!set_voice", "Pick the voice your messages use")
+                .arg(Arg::text("voice").default("brock_samson")),
+        )
+        .command(
+            Command::new("!voice", "Say your message in VOICE")
+                .arg(Arg::text("voice").default("slj"))
+                .example("!voice slj hello chat"),
+        )
+        .command(
+            Command::new(
+                "!soundboard_text",
+                "Create the soundboard text source",
+            )
+            .permission(Permission::Moderator),
+        )
+        .command(
+            Command::new("!character", "Create a new stream character")
+                .permission(Permission::Vip),
+        )
+        // ===========================================
+        // == Scrolling
+        // ===========================================
+        .command(
+            Command::new(
+                "!scroll",
+                "Scroll a source along x or y, taking DURATION milliseconds \
+                 to reach SPEED. A SPEED of 0 stops it.",
+            )
+            .arg(source())
+            .arg(Arg::text("direction").default("x"))
+            .arg(Arg::float("speed").default(0.0))
+            .arg(duration())
+            .example("!scroll begin x 500 10000")
+            .example("!scroll begin y 50 3000")
+            .example("!scroll begin x 0"),
+        )
+        // ===========================================
+        // == Blur
+        // ===========================================
+        .command(
+            Command::new(
+                "!blur",
+                "Blur a source, taking DURATION milliseconds to reach AMOUNT",
+            )
+            .arg(source())
+            .arg(Arg::float("amount").default(100.0))
+            .arg(duration())
+            .example("!blur begin 50")
+            .example("!blur primetime 100 5000"),
+        )
+        .command(
+            Command::new("!noblur", "Unblur a source")
+                .alias("!unblur")
+                .arg(source())
+                .example("!unblur begin"),
+        )
+        // ===========================================
+        // == Scaling Sources
+        // ===========================================
+        .command(
+            Command::new(
+                "!grow",
+                "Scale a source, 1 is its original size and 0.5 is half",
+            )
+            .alias("!scale")
+            .arg(source())
+            .arg(Arg::float("x").default(1.0))
+            .arg(Arg::float("y").default(1.0))
+            .example("!scale begin 0.5 0.5")
+            .example("!scale begin 1 1"),
+        )
+        // ===========================================
+        // == Moving Sources
+        // ===========================================
+        .command(
+            Command::new("!move", "Move a source to X and Y")
+                .arg(source())
+                .arg(Arg::float("x"))
+                .arg(Arg::float("y"))
+                .example("!move begin 500 500"),
+        )
+        // TODO: I'd like one-for every corner
+        .command(
+            Command::new("!tr", "Move a source to the top right").arg(source()),
+        )
+        .command(
+            Command::new("!bl", "Move a source to the bottom right")
+                .arg(source()),
+        )
+        // ===========================================
+        // == Showing/Hiding Sources & Scenes
+        // ===========================================
+        .command(
+            Command::new("!memes", "Show the memes scene")
+                .permission(Permission::Moderator),
+        )
+        .command(
+            Command::new("!nomemes", "Hide the memes scene")
+                .alias("!nojokes")
+                .alias("!work")
+                .permission(Permission::Moderator),
+        )
+        .command(
+            Command::new("!chat", "Switch to the scene for reading chat")
+                .permission(Permission::Moderator),
+        )
+        .command(
+            Command::new("!code", "Switch to the scene for reading code")
+                .permission(Permission::Moderator),
+        )
+        .command(
+            Command::new("!hide", "Hide every source in the memes scene")
+                .permission(Permission::Moderator),
+        )
+        .command(
+            Command::new("!show", "Show a source in the memes scene")
+                .arg(source()),
+        )
+        // ===========================================
+        // == Creating Scenes & Filters
+        // ===========================================
+        .command(
+            Command::new(
+                "!create_source",
+                "Add a source that already exists to the primary scene",
+            )
+            .arg(source())
+            .example("!create_source garfield")
+            .permission(Permission::Moderator),
+        )
+        .command(
+            Command::new("!split", "Create the split 3D transform filters")
+                .arg(source())
+                .permission(Permission::Moderator),
+        )
+        .command(
+            Command::new(
+                "!create_filters_for_source",
+                "Create the filters for manipulating a source, using \
+                 StreamFX, SDF Effects and others",
+            )
+            .arg(source())
+            .example("!create_filters_for_source garfield")
+            .permission(Permission::Moderator),
+        )
+        // ===========================================
+        // == Debug Info
+        // ===========================================
+        .command(
+            Command::new("!source", "Print information about a source")
+                .arg(source())
+                .permission(Permission::Vip),
+        )
+        .command(
+            Command::new("!outline", "Print the outline filter of a source")
+                .arg(source())
+                .permission(Permission::Vip),
+        )
+        // ===========================================
+        // == Compound Effects
+        // ===========================================
+        .command(
+            Command::new(
+                "!norm",
+                "Return a source to normal: reset its filters, size and \
+                 position",
+            )
+            .arg(source())
+            .example("!norm begin"),
+        )
+        .command(
+            Command::new(
+                "!follow",
+                "Have every source added by viewers follow LEADER",
+            )
+            .arg(Arg::text("leader").default(obs::DEFAULT_SOURCE))
+            .example("!follow garfield")
+            .permission(Permission::Vip),
+        )
+        .command(
+            Command::new(
+                "!staff",
+                "Make the changes needed to not get banned by Staff in the \
+                 chat",
+            )
+            .permission(Permission::Vip),
+        )
+        // ===========================================
+        // == 3D Transforms
+        // ===========================================
+        .command(
+            Command::new("!spin", "Spin a source around an axis")
+                .alias("!spinx")
+                .alias("!spiny")
+                .arg(source())
+                .arg(Arg::text("axis").default("z"))
+                .arg(Arg::float("value").default(0.0))
+                .arg(duration()),
+        )
+        .command(
+            Command::new(
+                "!def_ortho",
+                "Reset the orthographic filter of a source",
+            )
+            .arg(source())
+            .arg(duration())
+            .example("!def_ortho frog"),
+        )
+        .command(
+            Command::new(
+                "!ortho",
+                "Change a setting of the orthographic filter",
+            )
+            .arg(source())
+            .arg(Arg::text("setting"))
+            .arg(Arg::float("value").default(0.0))
+            .arg(duration())
+            .example("!ortho frog Scale.Y 1000")
+            .example("!ortho frog Position.X 0 3000")
+            .example("!ortho frog Rotation.Z 3600"),
+        )
+        .command(
+            Command::new("!perp", "Change a setting of the perspective filter")
+                .arg(source())
+                .arg(Arg::text("setting"))
+                .arg(Arg::float("value").default(0.0))
+                .arg(duration())
+                .example("!perp frog Camera.FieldOfView 1000")
+                .example("!perp frog Rotation.X 360"),
+        )
+        .command(
+            Command::new("!corner", "Move a corner of the corner pin filter")
+                .arg(source())
+                .arg(Arg::text("setting"))
+                .arg(Arg::float("value").default(0.0))
+                .arg(duration())
+                .example("!corner frog Corners.BottomLeft.X 100")
+                .example("!corner frog Corners.TopRight.Y 10"),
+        )
+        .command(
+            Command::new(
+                "!3d",
+                "Change a setting of the 3D Transform filter. Requires the \
+                 [Stream FX Plugin](https://github.com/Xaymar/obs-StreamFX)",
+            )
+            .arg(source())
+            .arg(Arg::text("setting"))
+            .arg(Arg::float("value").default(0.0))
+            .arg(duration())
+            .example("!3d begin Scale.X 400")
+            .example("!3d begin Rotation.Z 3600 3000")
+            .example("!3d begin Position.Y 0 20000"),
+        )
+}
+
 pub async fn handle_obs_commands(
     tx: &broadcast::Sender<Event>,
-    obs_client: &OBSClient,
-    pool: &sqlx::PgPool,
+    obs_client: &dyn OBSOperations,
+    store: &dyn CommandStore,
+    cooldowns: &mut Cooldowns,
+    grants: &mut Grants,
     splitmsg: Vec<String>,
     msg: UserMessage,
 ) -> Result<()> {
-    let default_source = obs::DEFAULT_SOURCE.to_string();
-
-    // We try and do some parsing on every command here
-    // These may not always be what we want, but they are sensible
-    // defaults used by many commands
-    let source: &str = splitmsg.get(1).unwrap_or(&default_source);
+    let command = match obs_commands().find(&splitmsg[0]) {
+        Some(command) if command.is_called(&splitmsg[0]) => command,
+        _ => return Ok(()),
+    };
 
-    let duration: u32 = splitmsg
-        .get(4)
-        .map_or(3000, |x| x.trim().parse().unwrap_or(3000));
+    if !grants.allows(command, &msg) {
+        println!(
+            "Refused {} for {}: only for {}",
+            command.name, msg.user_name, command.permission
+        );
+        if let Err(err) = store
+            .record_refusal(
+                &msg.channel,
+                &msg.user_name,
+                command.name,
+                command.permission,
+            )
+            .await
+        {
+            eprintln!("Error recording the refusal: {:#}", err);
+        }
 
-    let filter_value = splitmsg
-        .get(3)
-        .map_or(0.0, |x| x.trim().parse().unwrap_or(0.0));
+        if cooldowns.should_warn(&msg.user_name, Instant::now()) {
+            reply(
+                tx,
+                &msg,
+                format!("{} is only for {}", command.name, command.permission),
+            )?;
+        }
+        return Ok(());
+    }
 
-    let scene = match obs_scenes::find_scene(source).await {
-        Ok(scene) => scene.to_string(),
-        Err(_) => obs::MEME_SCENE.to_string(),
+    let args = match command.parse(&splitmsg[1..]) {
+        Ok(args) => args,
+        Err(err) => {
+            reply(tx, &msg, err.to_string())?;
+            return Ok(());
+        }
     };
 
-    // NOTE: If we want to extract values like filter_setting_name and filter_value
-    //       we need to figure a way to look up the defaults per command
-    //       because they could be different types
+    let now = Instant::now();
+    let permission = Permission::of(&msg);
+    if let Err(throttled) = cooldowns.try_use(
+        &msg.channel,
+        command.name,
+        &msg.user_name,
+        permission,
+        now,
+    ) {
+        println!("Throttled {}: {}", msg.user_name, throttled);
+        if cooldowns.should_warn(&msg.user_name, now) {
+            reply(tx, &msg, throttled.to_string())?;
+        }
+        return Ok(());
+    }
 
-    match splitmsg[0].as_str() {
-        // ===========================================
-        // == Test Area
-        // ===========================================
-        "!durf" => {
-            // Put any code you want to experiment w/ the chat with here
-            Ok(())
+    match command.name {
+        "!help" => {
+            let help = obs_commands().help(&msg, args.maybe_text("command"));
+            reply(tx, &msg, help)
         }
 
         // ===========================================
         // == Stream State
         // ===========================================
+        "!shutdown" => {
+            println!("{} is shutting us down", msg.user_name);
+            tx.send(Event::Shutdown)?;
+            Ok(())
+        }
+
+        "!cooldown" => {
+            let name = args.text("command")?;
+            let name = match obs_commands().find(name) {
+                Some(command) => command.name,
+                None if name == cooldowns::GLOBAL => cooldowns::GLOBAL,
+                None => {
+                    bail!(CommandError(format!("There is no {} command", name)))
+                }
+            };
+
+            let mut limit = cooldowns.limit(name);
+            let window = |seconds: f32| {
+                Duration::try_from_secs_f32(seconds.max(0.0)).map_err(|_| {
+                    CommandError(format!("{} seconds is too long", seconds))
+                })
+            };
+            if let Ok(seconds) = args.float("seconds") {
+                limit.window = window(seconds)?;
+                if let Ok(seconds) = args.float("user_seconds") {
+                    limit.user_window = window(seconds)?;
+                }
+
+                store.save_limit(name, limit).await?;
+                cooldowns.set_limit(name, limit);
+            }
+
+            reply(tx, &msg, format!("{}: {}", name, limit))
+        }
+
+        "!burst" => {
+            let role: Permission = args.text("role")?.parse()?;
+            if let Ok(burst) = args.int("burst") {
+                store.save_burst(role, burst).await?;
+                cooldowns.set_burst(role, burst);
+            }
+
+            let burst = cooldowns.burst(role);
+            reply(
+                tx,
+                &msg,
+                format!("{} get {} uses per cooldown", role, burst),
+            )
+        }
+
+        "!grant" | "!revoke" => {
+            let user = permissions::normalize_user(args.text("user")?);
+            let name = match obs_commands().find(args.text("command")?) {
+                Some(command) => command.name,
+                None => bail!(CommandError(format!(
+                    "There is no {} command",
+                    args.text("command")?
+                ))),
+            };
+
+            let channel = &msg.channel;
+            let message = if command.name == "!grant" {
+                store
+                    .save_grant(channel, &user, name, &msg.user_name)
+                    .await?;
+                grants.grant(channel, &user, name);
+                format!("@{} can now use {}", user, name)
+            } else {
+                store.delete_grant(channel, &user, name).await?;
+                grants.revoke(channel, &user, name);
+                format!("@{} can no longer use {}", user, name)
+            };
+
+            reply(tx, &msg, message)
+        }
+
+        "!pardon" => {
+            tx.send(Event::ModerationPardon(ModerationPardon {
+                channel: msg.channel.clone(),
+                user: permissions::normalize_user(args.text("user")?),
+                by: msg.user_name.clone(),
+            }))?;
+            Ok(())
+        }
+
         "!implicit" => {
-            twitch_stream_state::update_implicit_soundeffects(true, &pool)
-                .await?;
+            store.set_implicit_soundeffects(&msg.channel, true).await?;
             Ok(())
         }
 
+        "!mode" => {
+            let mode = args.text("mode")?.to_string();
+            tx.send(Event::StreamMode { mode: mode.clone() })?;
+            reply(tx, &msg, format!("Switched to {} mode", mode))
+        }
+
+        "!subgoal" => {
+            let target = args.int("target")? as usize;
+            let title = match splitmsg[2..].join(" ").trim() {
+                "" => sub_count::DEFAULT_GOAL_TITLE.to_string(),
+                title => title.to_string(),
+            };
+
+            store.save_goal(&title, target, &msg.user_name).await?;
+            tx.send(Event::SetTwitchSubGoal {
+                title: title.clone(),
+                target,
+            })?;
+
+            let message = match target {
+                0 => "The sub goal is down".to_string(),
+                _ => format!("New sub goal: {} at {} subs", title, target),
+            };
+            reply(tx, &msg, message)
+        }
+
+        "!cheers" => {
+            let message = match args.maybe_text("user") {
+                Some(user) => format!(
+                    "{} has cheered {} bits",
+                    permissions::normalize_user(user),
+                    store.cheer_total(&msg.channel, user).await?
+                ),
+                None => {
+                    let top = store.cheer_leaderboard(&msg.channel, 5).await?;
+                    if top.is_empty() {
+                        "Nobody has cheered yet".to_string()
+                    } else {
+                        let top = top
+                            .iter()
+                            .map(|total| {
+                                format!("{} ({})", total.user_name, total.bits)
+                            })
+                            .collect::<Vec<_>>();
+                        format!("Top cheerers: {}", top.join(", "))
+                    }
+                }
+            };
+
+            reply(tx, &msg, message)
+        }
+
         // ===========================================
         // == Voices & Characters
         // ===========================================
@@ -78,24 +865,19 @@ pub async fn handle_obs_commands(
         }
 
         "!set_voice" => {
-            // TODO: Abstract this out
-            let default_voice = "brock_samson".to_string();
-            let voice: &str = splitmsg.get(1).unwrap_or(&default_voice);
-            uberduck::set_voice(
-                voice.to_string(),
-                msg.user_name.to_string(),
-                pool,
-            )
-            .await
+            store
+                .set_voice(
+                    args.text("voice")?.to_string(),
+                    msg.user_name.to_string(),
+                    &msg.channel,
+                )
+                .await
         }
 
         "!voice" => {
-            // TODO: Abstract this out
-            let default_voice = "slj".to_string();
-            let voice: &str = splitmsg.get(1).unwrap_or(&default_voice);
             uberduck::talk_in_voice(
                 msg.contents.clone(),
-                voice.to_string(),
+                args.text("voice")?.to_string(),
                 msg.user_name,
                 tx,
             )
@@ -106,8 +888,6 @@ pub async fn handle_obs_commands(
             move_transition_bootstrap::create_soundboard_text(obs_client).await
         }
 
-        "!set_character" => Ok(()),
-
         "!character" => {
             // TODO: Abstract this out
             let base_source = "Randall";
@@ -119,18 +899,11 @@ pub async fn handle_obs_commands(
         // ===========================================
         // == Scrolling
         // ===========================================
-
-        // !scroll SOURCE SCROLL_SETTING SPEED DURATION (in milliseconds)
-        // !scroll begin x 5 300
         "!scroll" => {
-            let default_filter_setting_name = String::from("speed_x");
-            let filter_setting_name =
-                splitmsg.get(2).unwrap_or(&default_filter_setting_name);
-            let filter_setting_name: String = match filter_setting_name.as_str()
-            {
-                "x" => String::from("speed_x"),
-                "y" => String::from("speed_y"),
-                _ => default_filter_setting_name,
+            let source = args.text("source")?;
+            let filter_setting_name = match args.text("direction")? {
+                "y" | "speed_y" => "speed_y",
+                _ => "speed_x",
             };
 
             println!("Starting to Scroll: {} {}", source, filter_setting_name);
@@ -138,11 +911,11 @@ pub async fn handle_obs_commands(
             move_transition::update_and_trigger_move_value_filter(
                 source,
                 obs::MOVE_SCROLL_FILTER_NAME,
-                &filter_setting_name,
-                filter_value,
-                duration,
+                filter_setting_name,
+                args.float("speed")?,
+                args.int("duration")?,
                 2,
-                &obs_client,
+                obs_client,
             )
             .await
         }
@@ -151,33 +924,29 @@ pub async fn handle_obs_commands(
         // == Blur
         // ===========================================
         "!blur" => {
-            let filter_value = splitmsg
-                .get(2)
-                .map_or(100.0, |x| x.trim().parse().unwrap_or(100.0));
-
             move_transition::update_and_trigger_move_value_filter(
-                source,
+                args.text("source")?,
                 obs::MOVE_BLUR_FILTER_NAME,
                 "Filter.Blur.Size",
-                filter_value,
-                duration,
+                args.float("amount")?,
+                args.int("duration")?,
                 0,
-                &obs_client,
+                obs_client,
             )
             .await
         }
 
         // TODO: Update these values to be variables
         //       so we know what they do
-        "!noblur" | "!unblur" => {
+        "!noblur" => {
             move_transition::update_and_trigger_move_value_filter(
-                source,
+                args.text("source")?,
                 obs::DEFAULT_BLUR_FILTER_NAME,
                 "Filter.Blur.Size",
                 0.0,
                 5000,
                 2,
-                &obs_client,
+                obs_client,
             )
             .await
         }
@@ -185,15 +954,11 @@ pub async fn handle_obs_commands(
         // ===========================================
         // == Scaling Sources
         // ===========================================
-        "!grow" | "!scale" => {
-            let x: f32 = splitmsg
-                .get(2)
-                .and_then(|temp_x| temp_x.trim().parse().ok())
-                .unwrap_or(1.0);
-            let y: f32 = splitmsg
-                .get(3)
-                .and_then(|temp_y| temp_y.trim().parse().ok())
-                .unwrap_or(1.0);
+        "!grow" => {
+            let source = args.text("source")?;
+            let scene = obs_scenes::find_scene(source).await?;
+            let x = args.float("x")?;
+            let y = args.float("y")?;
 
             let base_scale = Scale {
                 x: Some(x),
@@ -205,7 +970,7 @@ pub async fn handle_obs_commands(
                 &base_scale,
                 x,
                 y,
-                &obs_client,
+                obs_client,
             )
             .await
         }
@@ -214,23 +979,31 @@ pub async fn handle_obs_commands(
         // == Moving Sources
         // ===========================================
         "!move" => {
+            let source = args.text("source")?;
+            let scene = obs_scenes::find_scene(source).await?;
             println!("!move {} {}", scene, source);
 
-            if splitmsg.len() > 3 {
-                let x: f32 = splitmsg[2].trim().parse().unwrap_or(0.0);
-                let y: f32 = splitmsg[3].trim().parse().unwrap_or(0.0);
-
-                obs_source::move_source(&scene, source, x, y, &obs_client).await
-            } else {
-                Ok(())
-            }
+            obs_source::move_source(
+                &scene,
+                source,
+                args.float("x")?,
+                args.float("y")?,
+                obs_client,
+            )
+            .await
         }
 
-        // TODO: I'd like one-for every corner
-        "!tr" => move_transition_effects::top_right(source, &obs_client).await,
+        "!tr" => {
+            move_transition_effects::top_right(args.text("source")?, obs_client)
+                .await
+        }
 
         "!bl" => {
-            move_transition_effects::bottom_right(source, &obs_client).await
+            move_transition_effects::bottom_right(
+                args.text("source")?,
+                obs_client,
+            )
+            .await
         }
 
         // ===========================================
@@ -241,58 +1014,69 @@ pub async fn handle_obs_commands(
                 obs::DEFAULT_SCENE,
                 obs::MEME_SCENE,
                 true,
-                &obs_client,
+                obs_client,
             )
             .await
         }
 
-        "!nomemes" | "!nojokes" | "!work" => {
+        "!nomemes" => {
             obs_source::set_enabled(
                 obs::DEFAULT_SCENE,
                 obs::MEME_SCENE,
                 false,
-                &obs_client,
+                obs_client,
             )
             .await
         }
 
         // Rename These Commands
-        "!chat" => obs_hotkeys::trigger_hotkey("OBS_KEY_L", &obs_client).await,
+        "!chat" => obs_hotkeys::trigger_hotkey("OBS_KEY_L", obs_client).await,
 
-        "!code" => obs_hotkeys::trigger_hotkey("OBS_KEY_H", &obs_client).await,
+        "!code" => obs_hotkeys::trigger_hotkey("OBS_KEY_H", obs_client).await,
 
-        "!hide" => obs_source::hide_sources(obs::MEME_SCENE, &obs_client).await,
+        "!hide" => obs_source::hide_sources(obs::MEME_SCENE, obs_client).await,
 
         "!show" => {
-            obs_source::set_enabled(obs::MEME_SCENE, source, true, &obs_client)
-                .await
+            obs_source::set_enabled(
+                obs::MEME_SCENE,
+                args.text("source")?,
+                true,
+                obs_client,
+            )
+            .await
         }
 
         // ===========================================
         // == Creating Scenes & Filters
         // ===========================================
         "!create_source" => {
-            let new_scene: obws::requests::scene_items::CreateSceneItem =
-                obws::requests::scene_items::CreateSceneItem {
-                    scene: obs::DEFAULT_SCENE,
-                    source: &source,
-                    enabled: Some(true),
-                };
-
             // TODO: Why is this crashing???
-            obs_client.scene_items().create(new_scene).await?;
+            obs_client
+                .create_scene_item(
+                    obs::DEFAULT_SCENE,
+                    args.text("source")?,
+                    Some(true),
+                )
+                .await?;
             Ok(())
         }
 
         // TEMP: This is for temporary testing!!!!
         "!split" => {
-            bootstrap::create_split_3d_transform_filters(source, &obs_client)
-                .await
+            bootstrap::create_split_3d_transform_filters(
+                args.text("source")?,
+                obs_client,
+            )
+            .await
         }
 
         // This sets up OBS for Begin's current setup
         "!create_filters_for_source" => {
-            bootstrap::create_filters_for_source(source, &obs_client).await
+            bootstrap::create_filters_for_source(
+                args.text("source")?,
+                obs_client,
+            )
+            .await
         }
 
         // ===========================================
@@ -302,136 +1086,237 @@ pub async fn handle_obs_commands(
         // TODO: Take in Scene
         "!source" => {
             obs_source::print_source_info(
-                source,
+                args.text("source")?,
                 obs::DEFAULT_SCENE,
-                &obs_client,
+                obs_client,
             )
             .await
         }
 
         // This doesn't seem like it would just be info
         // ...but it is!
-        "!outline" => sdf_effects::outline(source, &obs_client).await,
+        "!outline" => {
+            sdf_effects::outline(args.text("source")?, obs_client).await
+        }
 
         // ===========================================
         // == Compound Effects
         // ===========================================
-        "!norm" => obs_combo::norm(&source, &obs_client).await,
+        "!norm" => obs_combo::norm(args.text("source")?, obs_client).await,
 
         "!follow" => {
             let scene = obs::DEFAULT_SCENE;
-            let leader = splitmsg.get(1).unwrap_or(&default_source);
+            let leader = args.text("leader")?;
             let source = leader;
 
-            obs_combo::follow(source, scene, leader, &obs_client).await
+            obs_combo::follow(source, scene, leader, obs_client).await
         }
-        "!staff" => obs_combo::staff(obs::DEFAULT_SOURCE, &obs_client).await,
 
-        // ===============================================================================================
-        // ===============================================================================================
-        // ===============================================================================================
-        // ===============================================================================================
-
-        // Perspective
-        // Corner Pin
-        // Orthographic
-        "!spin" | "!spinx" | "spiny" => {
-            let default_filter_setting_name = String::from("z");
-            let filter_setting_name =
-                splitmsg.get(2).unwrap_or(&default_filter_setting_name);
+        "!staff" => obs_combo::staff(obs::DEFAULT_SOURCE, obs_client).await,
 
+        // ===========================================
+        // == 3D Transforms
+        // ===========================================
+        "!spin" => {
             move_transition_effects::spin(
-                source,
-                filter_setting_name,
-                filter_value,
-                duration,
-                &obs_client,
+                args.text("source")?,
+                args.text("axis")?,
+                args.float("value")?,
+                args.int("duration")?,
+                obs_client,
             )
             .await
         }
 
         "!def_ortho" => {
-            stream_fx::default_ortho(source, duration, &obs_client).await
+            stream_fx::default_ortho(
+                args.text("source")?,
+                args.int("duration")?,
+                obs_client,
+            )
+            .await
         }
-        "!ortho" => {
-            if splitmsg.len() < 3 {
-                return Ok(());
-            };
 
-            // TODO: This should be done with unwrap
-            let filter_setting_name = &splitmsg[2];
+        "!ortho" | "!perp" | "!corner" => {
+            let filter_name = match command.name {
+                "!ortho" => "3D_Orthographic",
+                "!perp" => "3D_Perspective",
+                _ => "3D_CornerPin",
+            };
 
             stream_fx::trigger_ortho(
-                source,
-                "3D_Orthographic",
-                filter_setting_name,
-                filter_value,
-                duration,
-                &obs_client,
+                args.text("source")?,
+                filter_name,
+                args.text("setting")?,
+                args.float("value")?,
+                args.int("duration")?,
+                obs_client,
             )
             .await
         }
 
-        "!perp" => {
-            if splitmsg.len() < 3 {
-                return Ok(());
-            };
-
-            // TODO: This should be done with unwrap
-            let filter_setting_name = &splitmsg[2];
-
-            stream_fx::trigger_ortho(
-                source,
-                "3D_Perspective",
-                filter_setting_name,
-                filter_value,
-                duration,
-                &obs_client,
+        // TODO: This is NOT Working!
+        "!3d" => {
+            move_transition_effects::trigger_3d(
+                args.text("source")?,
+                args.text("setting")?,
+                args.float("value")?,
+                args.int("duration")?,
+                obs_client,
             )
             .await
         }
 
-        "!corner" => {
-            if splitmsg.len() < 3 {
-                return Ok(());
-            };
+        name => bail!("{} is registered but not handled", name),
+    }
+}
 
-            // TODO: This should be done with unwrap
-            let filter_setting_name = &splitmsg[2];
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::registry;
+    use crate::testing::{user_message, FakeCommandStore, FakeOBS, OBSCall};
 
-            stream_fx::trigger_ortho(
-                source,
-                "3D_CornerPin",
-                filter_setting_name,
-                filter_value,
-                duration,
-                &obs_client,
-            )
+    async fn run(obs: &FakeOBS, contents: &str) -> Vec<Event> {
+        let msg = user_message("beginbot", contents);
+        run_with(obs, &FakeCommandStore::default(), msg).await
+    }
+
+    async fn run_with(
+        obs: &FakeOBS,
+        store: &FakeCommandStore,
+        msg: UserMessage,
+    ) -> Vec<Event> {
+        let handler = OBSMessageHandler {
+            obs_client: Box::new(obs.clone()),
+            channel_obs: HashMap::new(),
+            store: Box::new(store.clone()),
+            cooldowns: Cooldowns::default(),
+            grants: Grants::default(),
+        };
+
+        events::testing::run_handler(handler, vec![Event::UserMessage(msg)])
             .await
-        }
+            .unwrap()
+    }
 
-        // !3d SOURCE FILTER_NAME FILTER_VALUE DURATION
-        // !3d begin Rotation.Z 3600 5000
-        //
-        // TODO: This is NOT Working!
-        "!3d" => {
-            // If we don't at least have a filter_name, we can't proceed
-            if splitmsg.len() < 3 {
-                bail!("We don't have a filter name, can't proceed");
+    #[tokio::test]
+    async fn blur_updates_and_triggers_the_move_filter() {
+        let obs = FakeOBS::default();
+        run(&obs, "!blur begin 50").await;
+
+        let calls = obs.calls();
+        assert_eq!(calls.len(), 3);
+        assert_eq!(
+            calls[0],
+            OBSCall::GetFilter {
+                source: "begin".to_string(),
+                filter: obs::MOVE_BLUR_FILTER_NAME.to_string(),
             }
+        );
 
-            let filter_setting_name = &splitmsg[2];
+        let settings = match &calls[1] {
+            OBSCall::SetFilterSettings { settings, .. } => settings,
+            call => panic!("expected the filter settings, got {:?}", call),
+        };
+        assert_eq!(settings["setting_name"], "Filter.Blur.Size");
+        assert_eq!(settings["setting_float"], 50.0);
 
-            move_transition_effects::trigger_3d(
-                source,
-                filter_setting_name,
-                filter_value,
-                duration,
-                &obs_client,
-            )
-            .await
-        }
+        assert_eq!(
+            calls[2],
+            OBSCall::SetFilterEnabled {
+                source: "begin".to_string(),
+                filter: obs::MOVE_BLUR_FILTER_NAME.to_string(),
+                enabled: true,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn bad_args_reply_with_the_usage() {
+        let obs = FakeOBS::default();
+        let sent = run(&obs, "!move begin 500").await;
+
+        assert!(obs.calls().is_empty());
+        let usage = "Y is missing. Usage: !move [SOURCE=begin] X Y";
+        assert!(matches!(
+            &sent[..],
+            [Event::TwitchChatReply(reply)]
+                if reply.message == usage && reply.reply_to.is_some()
+        ));
+    }
+
+    #[tokio::test]
+    async fn errors_are_replied_in_chat() {
+        let obs = FakeOBS::default();
+        let sent = run(&obs, "!move snop 100 100").await;
+
+        assert!(matches!(
+            &sent[..],
+            [Event::TwitchChatReply(reply)]
+                if reply.message == "unknown source 'snop'"
+        ));
+    }
+
+    #[tokio::test]
+    async fn disconnected_obs_stays_out_of_chat() {
+        let obs = FakeOBS::default().disconnected();
+        let sent = run(&obs, "!move begin 100 100").await;
+
+        assert!(!obs.calls().is_empty());
+        assert!(sent.is_empty(), "sent {:?}", sent);
+    }
+
+    #[tokio::test]
+    async fn sub_goals_are_saved_and_announced() {
+        let store = FakeCommandStore::default();
+        let msg = user_message("beginbot", "!subgoal 50 Sub-a-thon");
+        let sent = run_with(&FakeOBS::default(), &store, msg).await;
+
+        assert_eq!(store.goals(), vec![("Sub-a-thon".to_string(), 50)]);
+        assert!(matches!(
+            &sent[..],
+            [
+                Event::SetTwitchSubGoal { target: 50, .. },
+                Event::TwitchChatReply(reply),
+            ] if reply.message == "New sub goal: Sub-a-thon at 50 subs"
+        ));
+    }
+
+    #[tokio::test]
+    async fn refusals_are_replied_without_a_database() {
+        let msg = user_message("viewer", "!subgoal 50");
+        let store = FakeCommandStore::down();
+        let sent = run_with(&FakeOBS::default(), &store, msg).await;
+
+        assert!(matches!(
+            &sent[..],
+            [Event::TwitchChatReply(reply)]
+                if reply.message == "!subgoal is only for the broadcaster"
+        ));
+    }
+
+    #[tokio::test]
+    async fn internal_errors_stay_out_of_chat() {
+        // Only the broadcaster can set goals, so this gets as far as saving
+        let msg = user_message("beginbot", "!subgoal 50");
+        assert!(registry::is_broadcaster(&msg));
+
+        let store = FakeCommandStore::down();
+        let sent = run_with(&FakeOBS::default(), &store, msg).await;
+
+        assert!(store.goals().is_empty());
+        assert!(sent.is_empty(), "sent {:?}", sent);
+    }
+
+    #[test]
+    fn readme_commands_are_up_to_date() {
+        let readme = include_str!("../README.md");
+        let docs = obs_commands().markdown();
 
-        _ => Ok(()),
+        assert!(
+            registry::replace_readme_section(readme, &docs).unwrap() == readme,
+            "README commands are stale, run `cargo run --bin command_docs`"
+        );
     }
 }
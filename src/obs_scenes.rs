@@ -1,9 +1,16 @@
 use crate::obs;
+use crate::obs::OBSOperations;
 use anyhow::Result;
-use obws;
+use async_trait::async_trait;
+use events::{EventHandler, Subscription};
+use subd_types::Event;
+use tokio::sync::broadcast;
 
-pub async fn change_scene(obs_client: &obws::Client, name: &str) -> Result<()> {
-    obs_client.scenes().set_current_program_scene(&name).await?;
+pub async fn change_scene(
+    obs_client: &dyn OBSOperations,
+    name: &str,
+) -> Result<()> {
+    obs_client.set_current_scene(name).await?;
     Ok(())
 }
 
@@ -15,3 +22,36 @@ pub async fn find_scene(source: &str) -> Result<String> {
 
     Ok(scene.to_string())
 }
+
+/// Switches scenes for `Event::ObsSetScene`.
+pub struct SceneHandler {
+    pub obs_client: Box<dyn OBSOperations>,
+}
+
+#[async_trait]
+impl EventHandler for SceneHandler {
+    fn subscription(&self) -> Subscription {
+        events::only!(ObsSetScene)
+    }
+
+    async fn handle(
+        self: Box<Self>,
+        _tx: broadcast::Sender<Event>,
+        mut rx: broadcast::Receiver<Event>,
+    ) -> Result<()> {
+        loop {
+            let event = events::recv(&mut rx).await?;
+            let scene = match event {
+                Event::ObsSetScene { scene } => scene,
+                Event::Shutdown => return Ok(()),
+                _ => continue,
+            };
+
+            if let Err(err) =
+                change_scene(self.obs_client.as_ref(), &scene).await
+            {
+                println!("Error switching to scene {}: {:?}", scene, err);
+            }
+        }
+    }
+}
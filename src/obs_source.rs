@@ -1,23 +1,19 @@
+use crate::commands::registry::CommandError;
 use crate::move_transition;
 use crate::obs;
-use anyhow::Result;
-use obws::requests::scene_items::{
-    Position, Scale, SceneItemTransform, SetTransform,
-};
-use obws::Client as OBSClient;
+use crate::obs::OBSOperations;
+use anyhow::{bail, Result};
+use obws::requests::scene_items::{Position, Scale, SceneItemTransform};
 
 pub async fn find_id(
     scene: &str,
     source: &str,
-    obs_client: &OBSClient,
-) -> Result<i64, obws::Error> {
-    let id_search = obws::requests::scene_items::Id {
-        scene,
-        source,
-        ..Default::default()
-    };
-
-    obs_client.scene_items().id(id_search).await
+    obs_client: &dyn OBSOperations,
+) -> Result<i64> {
+    match obs_client.find_scene_item(scene, source).await? {
+        Some(id) => Ok(id),
+        None => bail!(CommandError(format!("unknown source '{}'", source))),
+    }
 }
 
 // =============== //
@@ -29,11 +25,11 @@ pub async fn scale_source(
     source: &str,
     x: f32,
     y: f32,
-    obs_client: &OBSClient,
+    obs_client: &dyn OBSOperations,
 ) -> Result<()> {
     println!("Looking for ID: {} {}", scene, source);
 
-    let id = match find_id(scene, source, &obs_client).await {
+    let id = match find_id(scene, source, obs_client).await {
         Ok(val) => val,
         Err(err) => {
             println!("Error find_id: {:?}", err);
@@ -51,12 +47,10 @@ pub async fn scale_source(
         ..Default::default()
     };
 
-    let set_transform = SetTransform {
-        scene,
-        item_id: id,
-        transform: scene_transform,
-    };
-    match obs_client.scene_items().set_transform(set_transform).await {
+    match obs_client
+        .set_scene_item_transform(scene, id, scene_transform)
+        .await
+    {
         Ok(_) => {}
         Err(err) => {
             println!("Error Set Transform: {:?}", err);
@@ -69,21 +63,15 @@ pub async fn scale_source(
 pub async fn scale(
     id: i64,
     new_scale: Scale,
-    obs_client: &OBSClient,
+    obs_client: &dyn OBSOperations,
 ) -> Result<()> {
     let scene_transform = SceneItemTransform {
         scale: Some(new_scale),
         ..Default::default()
     };
 
-    let set_transform = SetTransform {
-        scene: obs::DEFAULT_SCENE,
-        item_id: id,
-        transform: scene_transform,
-    };
     obs_client
-        .scene_items()
-        .set_transform(set_transform)
+        .set_scene_item_transform(obs::DEFAULT_SCENE, id, scene_transform)
         .await?;
     Ok(())
 }
@@ -98,15 +86,9 @@ pub async fn move_source(
     source: &str,
     x: f32,
     y: f32,
-    obs_client: &OBSClient,
+    obs_client: &dyn OBSOperations,
 ) -> Result<()> {
-    let id = match find_id(scene, source, &obs_client).await {
-        Ok(val) => val,
-        Err(e) => {
-            println!("Error Finding ID {:?} {:?}", source, e);
-            return Ok(());
-        }
-    };
+    let id = find_id(scene, source, obs_client).await?;
 
     let new_position = Position {
         x: Some(x),
@@ -118,12 +100,10 @@ pub async fn move_source(
     };
 
     // TODO: figure out looking up Scene, based on Source
-    let set_transform = SetTransform {
-        scene,
-        item_id: id,
-        transform: scene_transform,
-    };
-    match obs_client.scene_items().set_transform(set_transform).await {
+    match obs_client
+        .set_scene_item_transform(scene, id, scene_transform)
+        .await
+    {
         Ok(_) => {}
         Err(e) => {
             println!("Error Transforming Scene: {:?}", e)
@@ -133,11 +113,14 @@ pub async fn move_source(
     Ok(())
 }
 
-pub async fn top_right(scene_item: &str, obs_client: &OBSClient) -> Result<()> {
+pub async fn top_right(
+    scene_item: &str,
+    obs_client: &dyn OBSOperations,
+) -> Result<()> {
     let base_settings = move_transition::fetch_source_settings(
         obs::DEFAULT_SCENE,
         &scene_item,
-        &obs_client,
+        obs_client,
     )
     .await?;
 
@@ -147,19 +130,19 @@ pub async fn top_right(scene_item: &str, obs_client: &OBSClient) -> Result<()> {
     move_transition::move_with_move_source(
         &filter_name,
         new_settings,
-        &obs_client,
+        obs_client,
     )
     .await
 }
 
 pub async fn bottom_right(
     scene_item: &str,
-    obs_client: &OBSClient,
+    obs_client: &dyn OBSOperations,
 ) -> Result<()> {
     let settings = move_transition::fetch_source_settings(
         obs::DEFAULT_SCENE,
         &scene_item,
-        &obs_client,
+        obs_client,
     )
     .await?;
 
@@ -169,7 +152,7 @@ pub async fn bottom_right(
     move_transition::move_with_move_source(
         &filter_name,
         new_settings,
-        &obs_client,
+        obs_client,
     )
     .await
 }
@@ -181,7 +164,7 @@ pub async fn bottom_right(
 pub async fn show_source(
     scene: &str,
     source: &str,
-    obs_client: &OBSClient,
+    obs_client: &dyn OBSOperations,
 ) -> Result<()> {
     set_enabled(scene, source, true, obs_client).await
 }
@@ -190,46 +173,28 @@ pub async fn set_enabled(
     scene: &str,
     source: &str,
     enabled: bool,
-    obs_client: &OBSClient,
+    obs_client: &dyn OBSOperations,
 ) -> Result<()> {
-    match find_id(scene, source, &obs_client).await {
+    let id = find_id(scene, source, obs_client).await?;
+    match obs_client.set_scene_item_enabled(scene, id, enabled).await {
         Err(e) => {
-            println!("Error finding ID for source {:?} {:?}", source, e)
+            println!("Error Enabling Source: {:?} {:?}", source, e);
         }
-        Ok(id) => {
-            let set_enabled: obws::requests::scene_items::SetEnabled =
-                obws::requests::scene_items::SetEnabled {
-                    enabled,
-                    item_id: id,
-                    scene,
-                };
-
-            match obs_client.scene_items().set_enabled(set_enabled).await {
-                Err(e) => {
-                    println!("Error Enabling Source: {:?} {:?}", source, e);
-                }
-                _ => (),
-            }
-        }
-    };
+        _ => (),
+    }
     Ok(())
 }
 
 async fn set_enabled_on_all_sources(
     scene: &str,
     enabled: bool,
-    obs_client: &OBSClient,
+    obs_client: &dyn OBSOperations,
 ) -> Result<()> {
-    match obs_client.scene_items().list(scene).await {
+    match obs_client.list_scene_items(scene).await {
         Ok(items) => {
             for item in items {
-                match set_enabled(
-                    scene,
-                    &item.source_name,
-                    enabled,
-                    &obs_client,
-                )
-                .await
+                match set_enabled(scene, &item.source_name, enabled, obs_client)
+                    .await
                 {
                     Ok(_) => (),
                     Err(e) => {
@@ -249,8 +214,11 @@ async fn set_enabled_on_all_sources(
     }
 }
 
-pub async fn hide_sources(scene: &str, obs_client: &OBSClient) -> Result<()> {
-    set_enabled_on_all_sources(scene, false, &obs_client).await
+pub async fn hide_sources(
+    scene: &str,
+    obs_client: &dyn OBSOperations,
+) -> Result<()> {
+    set_enabled_on_all_sources(scene, false, obs_client).await
 }
 
 // ===========================================
@@ -262,7 +230,7 @@ pub async fn trigger_grow(
     base_scale: &Scale,
     x: f32,
     y: f32,
-    obs_client: &OBSClient,
+    obs_client: &dyn OBSOperations,
 ) -> Result<()> {
     println!(
         "\n\t~~~ Attempting to Scale: {} | X: {:?} Y: {:?}",
@@ -270,29 +238,26 @@ pub async fn trigger_grow(
     );
 
     if source == "all" {
-        let sources = obs_client.scene_items().list(obs::DEFAULT_SCENE).await?;
+        let sources = obs_client.list_scene_items(obs::DEFAULT_SCENE).await?;
         for source in sources {
             let new_scale = Scale {
                 x: base_scale.x,
                 y: base_scale.y,
             };
-            let id = match find_id(
-                obs::MEME_SCENE,
-                &source.source_name,
-                &obs_client,
-            )
-            .await
-            {
-                Ok(val) => val,
-                Err(_) => return Ok(()),
-            };
+            let id =
+                match find_id(obs::MEME_SCENE, &source.source_name, obs_client)
+                    .await
+                {
+                    Ok(val) => val,
+                    Err(_) => return Ok(()),
+                };
 
-            if let Err(err) = scale(id, new_scale, &obs_client).await {
+            if let Err(err) = scale(id, new_scale, obs_client).await {
                 println!("Error Finding ID: {}", err)
             };
         }
     } else {
-        if let Err(err) = scale_source(&scene, &source, x, y, &obs_client).await
+        if let Err(err) = scale_source(&scene, &source, x, y, obs_client).await
         {
             println!("Error Scaling Source: {}", err)
         };
@@ -303,14 +268,14 @@ pub async fn trigger_grow(
 pub async fn print_source_info(
     source: &str,
     scene: &str,
-    obs_client: &OBSClient,
+    obs_client: &dyn OBSOperations,
 ) -> Result<()> {
-    let id = match find_id(obs::MEME_SCENE, source, &obs_client).await {
+    let id = match find_id(obs::MEME_SCENE, source, obs_client).await {
         Ok(val) => val,
         Err(_) => return Ok(()),
     };
 
-    let settings = match obs_client.scene_items().transform(scene, id).await {
+    let settings = match obs_client.scene_item_transform(scene, id).await {
         Ok(val) => val,
         Err(err) => {
             println!("Error Fetching Transform Settings: {:?}", err);
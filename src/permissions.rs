@@ -0,0 +1,181 @@
+//! Who can run which command, on top of the minimum role each command
+//! declares in the registry.
+//!
+//! The broadcaster can `!grant` a user a command their roles wouldn't
+//! allow, in their own channel. Grants live in `command_permission_grants`,
+//! where an empty channel means every channel, and every refusal is recorded
+//! in `command_refusals`.
+
+use crate::commands::registry::{Command, Permission};
+use anyhow::Result;
+use sqlx::PgPool;
+use std::collections::HashSet;
+use subd_types::UserMessage;
+
+#[derive(Debug, Default)]
+pub struct Grants {
+    // (channel, username, command)
+    grants: HashSet<(String, String, String)>,
+}
+
+impl Grants {
+    pub async fn load(pool: &PgPool) -> Result<Self> {
+        let rows = sqlx::query!(
+            "SELECT channel, username, command FROM command_permission_grants"
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let mut grants = Self::default();
+        for row in rows {
+            grants.grant(&row.channel, &row.username, &row.command);
+        }
+        Ok(grants)
+    }
+
+    /// Whether `user` was granted `command` in `channel`, or everywhere.
+    pub fn is_granted(&self, channel: &str, user: &str, command: &str) -> bool {
+        [channel, ""]
+            .iter()
+            .any(|channel| self.grants.contains(&key(channel, user, command)))
+    }
+
+    pub fn grant(&mut self, channel: &str, user: &str, command: &str) {
+        self.grants.insert(key(channel, user, command));
+    }
+
+    pub fn revoke(&mut self, channel: &str, user: &str, command: &str) {
+        self.grants.remove(&key(channel, user, command));
+    }
+
+    /// Whether `msg`'s sender can run `command`, by role or by grant.
+    pub fn allows(&self, command: &Command, msg: &UserMessage) -> bool {
+        command.permission.allows(msg)
+            || self.is_granted(&msg.channel, &msg.user_name, command.name)
+    }
+}
+
+fn key(channel: &str, user: &str, command: &str) -> (String, String, String) {
+    (
+        channel.to_lowercase(),
+        normalize_user(user),
+        command.to_string(),
+    )
+}
+
+/// Usernames come from chat as `@SomeUser` or `someuser`.
+pub fn normalize_user(user: &str) -> String {
+    user.trim_start_matches('@').to_lowercase()
+}
+
+pub async fn save_grant(
+    pool: &PgPool,
+    channel: &str,
+    user: &str,
+    command: &str,
+    granted_by: &str,
+) -> Result<()> {
+    sqlx::query!(
+        r#"INSERT INTO command_permission_grants
+           (channel, username, command, granted_by)
+           VALUES ( $1, $2, $3, $4 )
+           ON CONFLICT (channel, username, command)
+           DO UPDATE SET granted_by = $4"#,
+        channel.to_lowercase(),
+        normalize_user(user),
+        command,
+        granted_by,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn delete_grant(
+    pool: &PgPool,
+    channel: &str,
+    user: &str,
+    command: &str,
+) -> Result<()> {
+    sqlx::query!(
+        r#"DELETE FROM command_permission_grants
+           WHERE channel = $1 AND username = $2 AND command = $3"#,
+        channel.to_lowercase(),
+        normalize_user(user),
+        command,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn record_refusal(
+    pool: &PgPool,
+    channel: &str,
+    user: &str,
+    command: &str,
+    required: Permission,
+) -> Result<()> {
+    sqlx::query!(
+        r#"INSERT INTO command_refusals (channel, username, command, required)
+           VALUES ( $1, $2, $3, $4 )"#,
+        channel.to_lowercase(),
+        normalize_user(user),
+        command,
+        required.as_str(),
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::user_message;
+
+    #[test]
+    fn grants_let_users_past_their_role() {
+        let command = Command::new("!hide", "Hide everything")
+            .permission(Permission::Moderator);
+        let msg = user_message("Viewer", "!hide");
+
+        let mut grants = Grants::default();
+        assert!(!grants.allows(&command, &msg));
+
+        grants.grant("beginbot", "@viewer", "!hide");
+        assert!(grants.allows(&command, &msg));
+
+        grants.revoke("beginbot", "VIEWER", "!hide");
+        assert!(!grants.allows(&command, &msg));
+    }
+
+    #[test]
+    fn grants_stay_in_their_channel() {
+        let command = Command::new("!hide", "Hide everything")
+            .permission(Permission::Moderator);
+        let mut msg = user_message("viewer", "!hide");
+        msg.channel = "teej_dv".to_string();
+
+        let mut grants = Grants::default();
+        grants.grant("beginbot", "viewer", "!hide");
+        assert!(!grants.allows(&command, &msg));
+
+        grants.grant("", "viewer", "!hide");
+        assert!(grants.allows(&command, &msg));
+
+        // Only their own channel's broadcaster
+        let owner = user_message("beginbot", "!hide");
+        msg.user_login = Some("beginbot".to_string());
+        assert!(Permission::Broadcaster.allows(&owner));
+        assert!(!Permission::Broadcaster.allows(&msg));
+
+        // Only the login counts, not the name they show up with
+        let mut impostor = user_message("viewer", "!hide");
+        impostor.user_name = "beginbot".to_string();
+        assert!(!Permission::Broadcaster.allows(&impostor));
+    }
+}
@@ -0,0 +1,229 @@
+//! Thanking raiders.
+//!
+//! Every raid gets a shoutout in the channel they raided, with whatever the
+//! raider was last streaming, and optionally an OBS combo on that channel's
+//! OBS (see `SUBD_RAID_OBS_COMBO`). The alert itself is shown by the
+//! overlay.
+
+use crate::obs;
+use crate::obs::OBSOperations;
+use crate::obs_combo;
+use anyhow::Result;
+use async_trait::async_trait;
+use events::{EventHandler, Subscription};
+use std::collections::HashMap;
+use subd_twitch::tokens::{TokenKind, TokenStore};
+use subd_types::twitch::{TwitchUser, UserNoticeKind};
+use subd_types::{ChatReply, Event, TwitchUserID};
+use tokio::sync::broadcast;
+use twitch_api2::helix::channels::GetChannelInformationRequest;
+use twitch_api2::HelixClient;
+
+/// Looks up other channels on Twitch.
+#[async_trait]
+pub trait ChannelInfo: Send + Sync {
+    /// The category `user_id` last streamed in, `None` if they never set one.
+    async fn last_category(
+        &self,
+        user_id: &TwitchUserID,
+    ) -> Result<Option<String>>;
+}
+
+pub struct HelixChannels {
+    client: reqwest::Client,
+    tokens: TokenStore,
+}
+
+impl HelixChannels {
+    pub fn new(tokens: TokenStore) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            tokens,
+        }
+    }
+}
+
+#[async_trait]
+impl ChannelInfo for HelixChannels {
+    async fn last_category(
+        &self,
+        user_id: &TwitchUserID,
+    ) -> Result<Option<String>> {
+        let helix: HelixClient<reqwest::Client> =
+            HelixClient::with_client(self.client.clone());
+        let req = GetChannelInformationRequest::builder()
+            .broadcaster_id(user_id.0.clone())
+            .build();
+
+        let token = self.tokens.user_token(TokenKind::Broadcaster).await?;
+        let response = helix.req_get(req, &token).await?;
+        Ok(response
+            .data
+            .into_iter()
+            .next()
+            .map(|channel| channel.game_name.to_string())
+            .filter(|category| !category.is_empty()))
+    }
+}
+
+pub struct RaidHandler {
+    pub obs_client: Box<dyn OBSOperations>,
+
+    /// OBS of the channels that have their own, by login. Raids on every
+    /// other channel go to `obs_client`.
+    pub channel_obs: HashMap<String, Box<dyn OBSOperations>>,
+    pub channels: Box<dyn ChannelInfo>,
+
+    /// One of `obs_combo::COMBOS`, run on every raid.
+    pub combo: Option<String>,
+}
+
+#[async_trait]
+impl EventHandler for RaidHandler {
+    fn subscription(&self) -> Subscription {
+        events::only!(TwitchUserNotice)
+    }
+
+    async fn handle(
+        self: Box<Self>,
+        tx: broadcast::Sender<Event>,
+        mut rx: broadcast::Receiver<Event>,
+    ) -> Result<()> {
+        loop {
+            let event = events::recv(&mut rx).await?;
+            let (channel, raider, viewers) = match event {
+                Event::TwitchUserNotice(notice) => match notice.kind {
+                    UserNoticeKind::Raid { viewers } => {
+                        (notice.channel, notice.user, viewers)
+                    }
+                    _ => continue,
+                },
+                Event::Shutdown => return Ok(()),
+                _ => continue,
+            };
+            println!(
+                "{} raided {} with {} viewers",
+                raider.name, channel, viewers
+            );
+
+            let category = match self.channels.last_category(&raider.id).await {
+                Ok(category) => category,
+                Err(err) => {
+                    println!("Error looking up {}: {:?}", raider.login, err);
+                    None
+                }
+            };
+            tx.send(Event::TwitchChatReply(ChatReply {
+                channel: Some(channel.clone()),
+                ..ChatReply::new(shoutout(
+                    &raider,
+                    viewers,
+                    category.as_deref(),
+                ))
+            }))?;
+
+            if let Some(combo) = &self.combo {
+                let obs_client = match self.channel_obs.get(&channel) {
+                    Some(client) => client.as_ref(),
+                    None => self.obs_client.as_ref(),
+                };
+                if let Err(err) =
+                    obs_combo::trigger(combo, obs::DEFAULT_SOURCE, obs_client)
+                        .await
+                {
+                    println!("Error running raid combo {}: {:?}", combo, err);
+                }
+            }
+        }
+    }
+}
+
+pub fn shoutout(
+    raider: &TwitchUser,
+    viewers: u64,
+    category: Option<&str>,
+) -> String {
+    let mut message = format!(
+        "Thanks for the raid {} and your {} raiders! Go follow them at \
+         https://twitch.tv/{}",
+        raider.name, viewers, raider.login
+    );
+    if let Some(category) = category {
+        message.push_str(&format!(", last seen streaming {}", category));
+    }
+
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{FakeOBS, OBSCall};
+    use subd_types::twitch::TwitchUserNotice;
+
+    struct Categories(Option<&'static str>);
+
+    #[async_trait]
+    impl ChannelInfo for Categories {
+        async fn last_category(
+            &self,
+            _: &TwitchUserID,
+        ) -> Result<Option<String>> {
+            Ok(self.0.map(String::from))
+        }
+    }
+
+    fn notice(channel: &str, kind: UserNoticeKind) -> Event {
+        Event::TwitchUserNotice(TwitchUserNotice {
+            channel: channel.to_string(),
+            user: TwitchUser {
+                id: TwitchUserID("1234".to_string()),
+                login: "nyxkrage".to_string(),
+                name: "NyxKrage".to_string(),
+            },
+            text: None,
+            system_message: String::new(),
+            kind,
+        })
+    }
+
+    #[tokio::test]
+    async fn raids_get_a_shoutout_and_the_combo() {
+        let (obs, theirs) = (FakeOBS::default(), FakeOBS::default());
+        let mut channel_obs: HashMap<String, Box<dyn OBSOperations>> =
+            HashMap::new();
+        channel_obs.insert("nyxkrage".to_string(), Box::new(theirs.clone()));
+        let handler = RaidHandler {
+            obs_client: Box::new(obs.clone()),
+            channel_obs,
+            channels: Box::new(Categories(Some("Science & Technology"))),
+            combo: Some("blur".to_string()),
+        };
+
+        let sent = events::testing::run_handler(
+            handler,
+            vec![
+                notice("beginbot", UserNoticeKind::Announcement),
+                notice("beginbot", UserNoticeKind::Raid { viewers: 12 }),
+            ],
+        )
+        .await
+        .unwrap();
+
+        assert!(matches!(
+            &sent[..],
+            [Event::TwitchChatReply(reply)]
+                if reply.channel.as_deref() == Some("beginbot")
+                && reply.message ==
+                "Thanks for the raid NyxKrage and your 12 raiders! Go follow \
+                 them at https://twitch.tv/nyxkrage, last seen streaming \
+                 Science & Technology"
+        ));
+        assert!(obs.calls().contains(&OBSCall::SetFilterEnabled {
+            source: obs::DEFAULT_SOURCE.to_string(),
+            filter: obs::MOVE_BLUR_FILTER_NAME.to_string(),
+            enabled: true,
+        }));
+        assert!(theirs.calls().is_empty());
+    }
+}
@@ -0,0 +1,642 @@
+//! What channel point rewards do when they're redeemed.
+//!
+//! Rewards are mapped to an action in `channel_point_rewards`, by the reward's
+//! id or by its title. Once the action has run the redemption is fulfilled,
+//! or refunded if it failed, and kept in `channel_point_redemptions`.
+//! Redemptions of rewards that aren't mapped are only logged and kept.
+//!
+//! The rewards themselves are declared in data/rewards.json and synced with
+//! `cargo run --bin chat_rewards`. While running, `RewardModeHandler` turns
+//! them on and off as the scene or stream mode changes.
+
+use crate::audio::AudioOutput;
+use crate::obs::OBSOperations;
+use crate::obs_hotkeys;
+use crate::uberduck;
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use events::{EventHandler, Subscription};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::time::Duration;
+use subd_twitch::rewards::{self as declared, RewardConfig, RewardManager};
+use subd_twitch::tokens::{TokenKind, TokenStore};
+use subd_types::{Event, UberDuckRequest};
+use tokio::sync::broadcast;
+use tokio::time::Instant;
+use twitch_api2::pubsub::channel_points::Redemption;
+use twitch_api2::HelixClient;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RewardAction {
+    Scene(String),
+    Filter {
+        source: String,
+        filter: String,
+    },
+    /// A file in ./MP3s, without the .mp3
+    Sound(String),
+    /// Reads out what the viewer typed in with this UberDuck voice.
+    Speak {
+        voice: String,
+    },
+    Hotkey(String),
+}
+
+impl RewardAction {
+    pub fn from_row(
+        action: &str,
+        target: String,
+        filter: Option<String>,
+    ) -> Result<Self> {
+        Ok(match action {
+            "scene" => RewardAction::Scene(target),
+            "filter" => RewardAction::Filter {
+                filter: filter.ok_or_else(|| {
+                    anyhow!("filter on {} needs a filter name", target)
+                })?,
+                source: target,
+            },
+            "sound" => RewardAction::Sound(target),
+            "tts" => RewardAction::Speak { voice: target },
+            "hotkey" => RewardAction::Hotkey(target),
+            _ => return Err(anyhow!("unknown reward action '{}'", action)),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RewardRoutes {
+    // Reward id or title to its action
+    routes: HashMap<String, RewardAction>,
+}
+
+impl RewardRoutes {
+    pub async fn load(pool: &PgPool) -> Result<Self> {
+        let rows = sqlx::query!(
+            "SELECT reward, action, target, filter FROM channel_point_rewards"
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let mut routes = Self::default();
+        for row in rows {
+            match RewardAction::from_row(&row.action, row.target, row.filter) {
+                Ok(action) => routes.add(row.reward, action),
+                Err(err) => println!("Skipping reward {}: {}", row.reward, err),
+            }
+        }
+        Ok(routes)
+    }
+
+    pub fn add(&mut self, reward: impl Into<String>, action: RewardAction) {
+        self.routes.insert(reward.into(), action);
+    }
+
+    /// The action for `redemption`, looked up by id before title.
+    pub fn action(&self, redemption: &Redemption) -> Option<&RewardAction> {
+        self.routes
+            .get(&redemption.reward.id.to_string())
+            .or_else(|| self.routes.get(&redemption.reward.title))
+    }
+}
+
+/// How a redemption ended up.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RedemptionResult {
+    Fulfilled,
+    /// The action failed, so the viewer gets their points back.
+    Refunded(String),
+    /// Nothing is set up for the reward in `channel_point_rewards`.
+    Unmapped,
+}
+
+impl RedemptionResult {
+    fn status(&self) -> &'static str {
+        match self {
+            RedemptionResult::Fulfilled => "FULFILLED",
+            RedemptionResult::Refunded(_) => "CANCELED",
+            RedemptionResult::Unmapped => "UNMAPPED",
+        }
+    }
+}
+
+/// Where redemptions go once they're done.
+#[async_trait]
+pub trait RedemptionLedger: Send + Sync {
+    async fn finish(
+        &self,
+        redemption: &Redemption,
+        result: &RedemptionResult,
+    ) -> Result<()>;
+}
+
+/// Fulfils or refunds redemptions on Twitch, and keeps every one of them in
+/// `channel_point_redemptions`.
+pub struct TwitchLedger {
+    pool: PgPool,
+    client: reqwest::Client,
+    tokens: TokenStore,
+}
+
+impl TwitchLedger {
+    pub fn new(pool: PgPool, tokens: TokenStore) -> Self {
+        Self {
+            pool,
+            client: reqwest::Client::new(),
+            tokens,
+        }
+    }
+}
+
+#[async_trait]
+impl RedemptionLedger for TwitchLedger {
+    async fn finish(
+        &self,
+        redemption: &Redemption,
+        result: &RedemptionResult,
+    ) -> Result<()> {
+        let mut error = match result {
+            RedemptionResult::Refunded(reason) => Some(reason.clone()),
+            _ => None,
+        };
+
+        if *result != RedemptionResult::Unmapped {
+            let helix: HelixClient<reqwest::Client> =
+                HelixClient::with_client(self.client.clone());
+            let updated =
+                match self.tokens.user_token(TokenKind::Broadcaster).await {
+                    Ok(token) => {
+                        RewardManager::new(&helix, &token)
+                            .set_redemption_status(
+                                &redemption.reward.id.to_string(),
+                                &redemption.id.to_string(),
+                                *result == RedemptionResult::Fulfilled,
+                            )
+                            .await
+                    }
+                    Err(err) => Err(err),
+                };
+            if let Err(err) = updated {
+                // Most likely a reward made on the dashboard, which only the
+                // dashboard can fulfil or refund.
+                let err = format!("couldn't update the status: {:#}", err);
+                error = Some(match error {
+                    Some(reason) => format!("{}; {}", reason, err),
+                    None => err,
+                });
+            }
+        }
+
+        sqlx::query!(
+            r#"INSERT INTO channel_point_redemptions
+               (redemption_id, reward_id, reward_title, user_login,
+                user_input, status, error)
+               VALUES ( $1, $2, $3, $4, $5, $6, $7 )
+               ON CONFLICT (redemption_id) DO NOTHING"#,
+            redemption.id.to_string(),
+            redemption.reward.id.to_string(),
+            redemption.reward.title,
+            redemption.user.login.to_string(),
+            redemption.user_input,
+            result.status(),
+            error,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// How long a handed off redemption waits for its outcome before it's
+/// refunded.
+pub const PENDING_FOR: Duration = Duration::from_secs(5 * 60);
+
+/// Runs the action for each redemption, then fulfils it if the action
+/// worked or refunds it if it didn't.
+///
+/// TTS is finished by the UberDuck handler, so those redemptions wait for
+/// its `Event::RedemptionOutcome`. If it doesn't come within `pending_for`,
+/// or we shut down first, they're refunded.
+pub struct RewardHandler {
+    pub obs_client: Box<dyn OBSOperations>,
+    pub sink: Box<dyn AudioOutput>,
+    pub routes: RewardRoutes,
+    pub ledger: Box<dyn RedemptionLedger>,
+    pub pending_for: Duration,
+}
+
+/// Whether an action is done, or still going somewhere else on the bus.
+enum Ran {
+    Done,
+    HandedOff,
+}
+
+#[async_trait]
+impl EventHandler for RewardHandler {
+    fn subscription(&self) -> Subscription {
+        events::only!(TwitchChannelPointsRedeem, RedemptionOutcome)
+    }
+
+    async fn handle(
+        self: Box<Self>,
+        tx: broadcast::Sender<Event>,
+        mut rx: broadcast::Receiver<Event>,
+    ) -> Result<()> {
+        // Redemptions waiting on a RedemptionOutcome, by redemption id,
+        // with when they give up
+        let mut pending: HashMap<String, (Redemption, Instant)> =
+            HashMap::new();
+
+        loop {
+            let now = Instant::now();
+            let expired: Vec<String> = pending
+                .iter()
+                .filter(|(_, (_, deadline))| *deadline <= now)
+                .map(|(id, _)| id.clone())
+                .collect();
+            for id in expired {
+                if let Some((redemption, _)) = pending.remove(&id) {
+                    let result =
+                        RedemptionResult::Refunded("timed out".to_string());
+                    self.finish(&redemption, result).await;
+                }
+            }
+
+            let next_deadline =
+                pending.values().map(|(_, deadline)| *deadline).min();
+            let event = tokio::select! {
+                _ = sleep_until(next_deadline) => continue,
+                event = events::recv(&mut rx) => event?,
+            };
+
+            let redemption = match event {
+                Event::TwitchChannelPointsRedeem(redemption) => redemption,
+                Event::RedemptionOutcome(outcome) => {
+                    if let Some((redemption, _)) =
+                        pending.remove(&outcome.redemption_id)
+                    {
+                        let result = match outcome.error {
+                            Some(reason) => RedemptionResult::Refunded(reason),
+                            None => RedemptionResult::Fulfilled,
+                        };
+                        self.finish(&redemption, result).await;
+                    }
+                    continue;
+                }
+                Event::Shutdown => {
+                    // Nobody is left to finish these
+                    for (redemption, _) in pending.into_values() {
+                        let result = RedemptionResult::Refunded(
+                            "shut down before it was done".to_string(),
+                        );
+                        self.finish(&redemption, result).await;
+                    }
+                    return Ok(());
+                }
+                _ => continue,
+            };
+
+            let action = match self.routes.action(&redemption) {
+                Some(action) => action,
+                None => {
+                    println!(
+                        "No action for reward {} ({}) redeemed by {}",
+                        redemption.reward.title,
+                        redemption.reward.id,
+                        redemption.user.display_name
+                    );
+                    self.finish(&redemption, RedemptionResult::Unmapped).await;
+                    continue;
+                }
+            };
+
+            match self.run(&tx, action, &redemption).await {
+                Ok(Ran::Done) => {
+                    self.finish(&redemption, RedemptionResult::Fulfilled).await
+                }
+                Ok(Ran::HandedOff) => {
+                    let deadline = Instant::now() + self.pending_for;
+                    pending.insert(
+                        redemption.id.to_string(),
+                        (redemption, deadline),
+                    );
+                }
+                Err(err) => {
+                    println!(
+                        "Error running reward {}: {:?}",
+                        redemption.reward.title, err
+                    );
+                    let result =
+                        RedemptionResult::Refunded(format!("{:#}", err));
+                    self.finish(&redemption, result).await
+                }
+            }
+        }
+    }
+}
+
+/// Never wakes up without a deadline.
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+impl RewardHandler {
+    async fn run(
+        &self,
+        tx: &broadcast::Sender<Event>,
+        action: &RewardAction,
+        redemption: &Redemption,
+    ) -> Result<Ran> {
+        match action {
+            RewardAction::Scene(scene) => {
+                self.obs_client.set_current_scene(scene).await?;
+                // So the rewards follow the scene
+                tx.send(Event::ObsSetScene {
+                    scene: scene.clone(),
+                })?;
+            }
+            RewardAction::Filter { source, filter } => {
+                self.obs_client
+                    .set_filter_enabled(source, filter, true)
+                    .await?
+            }
+            RewardAction::Sound(sound) => {
+                self.sink.play_file(&format!("./MP3s/{}.mp3", sound))?
+            }
+            RewardAction::Speak { voice } => {
+                let voice_text = match &redemption.user_input {
+                    Some(input) if !input.trim().is_empty() => input.clone(),
+                    _ => return Err(anyhow!("nothing to read out")),
+                };
+
+                tx.send(Event::UberDuckRequest(UberDuckRequest {
+                    voice: voice.clone(),
+                    message: uberduck::chop_text(voice_text.clone()),
+                    voice_text,
+                    username: redemption.user.display_name.to_string(),
+                    source: None,
+                    redemption_id: Some(redemption.id.to_string()),
+                }))?;
+                return Ok(Ran::HandedOff);
+            }
+            RewardAction::Hotkey(key) => {
+                obs_hotkeys::trigger_hotkey(key, self.obs_client.as_ref())
+                    .await?
+            }
+        }
+
+        Ok(Ran::Done)
+    }
+
+    async fn finish(&self, redemption: &Redemption, result: RedemptionResult) {
+        println!(
+            "Redemption of {} by {}: {:?}",
+            redemption.reward.title, redemption.user.display_name, result
+        );
+        if let Err(err) = self.ledger.finish(redemption, &result).await {
+            println!("Error finishing redemption {}: {:?}", redemption.id, err);
+        }
+    }
+}
+
+/// Turns the declared rewards on and off, following their `disabled_in`,
+/// whenever the OBS scene or the stream mode changes. Whichever changed last
+/// wins.
+pub struct RewardModeHandler {
+    client: reqwest::Client,
+    tokens: TokenStore,
+    rewards: Vec<RewardConfig>,
+}
+
+impl RewardModeHandler {
+    pub fn new(tokens: TokenStore) -> Result<Self> {
+        Ok(Self {
+            client: reqwest::Client::new(),
+            tokens,
+            rewards: declared::load_rewards(declared::REWARDS_FILE)?,
+        })
+    }
+}
+
+#[async_trait]
+impl EventHandler for RewardModeHandler {
+    fn subscription(&self) -> Subscription {
+        events::only!(ObsSetScene, StreamMode)
+    }
+
+    async fn handle(
+        self: Box<Self>,
+        _tx: broadcast::Sender<Event>,
+        mut rx: broadcast::Receiver<Event>,
+    ) -> Result<()> {
+        loop {
+            let event = events::recv(&mut rx).await?;
+            let mode = match event {
+                Event::ObsSetScene { scene } => scene,
+                Event::StreamMode { mode } => mode,
+                Event::Shutdown => return Ok(()),
+                _ => continue,
+            };
+
+            let helix: HelixClient<reqwest::Client> =
+                HelixClient::with_client(self.client.clone());
+            let applied =
+                match self.tokens.user_token(TokenKind::Broadcaster).await {
+                    Ok(token) => {
+                        RewardManager::new(&helix, &token)
+                            .apply_mode(&self.rewards, &mode)
+                            .await
+                    }
+                    Err(err) => Err(err),
+                };
+            if let Err(err) = applied {
+                println!("Error setting rewards for {}: {:?}", mode, err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{FakeAudio, FakeLedger, FakeOBS, OBSCall};
+    use serde_json::json;
+    use subd_types::RedemptionOutcome;
+
+    fn redeem(id: &str, title: &str, user_input: Option<&str>) -> Event {
+        let reward = json!({
+            "id": id,
+            "channel_id": "1",
+            "title": title,
+            "prompt": "",
+            "cost": 100,
+            "is_user_input_required": user_input.is_some(),
+            "is_sub_only": false,
+            "image": null,
+            "default_image": null,
+            "background_color": "",
+            "is_enabled": true,
+            "is_paused": false,
+            "is_in_stock": true,
+            "max_per_stream": { "is_enabled": false, "max_per_stream": 0 },
+            "max_per_user_per_stream": {
+                "is_enabled": false,
+                "max_per_user_per_stream": 0,
+            },
+            "global_cooldown": {
+                "is_enabled": false,
+                "global_cooldown_seconds": 0,
+            },
+            "should_redemptions_skip_request_queue": false,
+            "template_id": null,
+            "updated_for_indicator_at": null,
+            "redemptions_redeemed_current_stream": null,
+            "cooldown_expires_at": null,
+        });
+
+        Event::TwitchChannelPointsRedeem(
+            serde_json::from_value(json!({
+                "id": format!("redemption-{}", id),
+                "channel_id": "1",
+                "redeemed_at": "2023-01-14T12:00:00Z",
+                "user": {
+                    "id": "2",
+                    "login": "nyxkrage",
+                    "display_name": "NyxKrage",
+                },
+                "user_input": user_input,
+                "status": "UNFULFILLED",
+                "reward": reward,
+            }))
+            .unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn rewards_run_their_action() {
+        let mut routes = RewardRoutes::default();
+        routes.add(
+            "Mandatory Crab Dance",
+            RewardAction::Scene("PC - Crab Rave".to_string()),
+        );
+        routes.add("1234", RewardAction::Sound("bits".to_string()));
+        routes.add(
+            "Say Something",
+            RewardAction::Speak {
+                voice: "Randall".to_string(),
+            },
+        );
+
+        let (obs, audio) = (FakeOBS::default(), FakeAudio::default());
+        let ledger = FakeLedger::default();
+        let handler = RewardHandler {
+            obs_client: Box::new(obs.clone()),
+            sink: Box::new(audio.clone()),
+            routes,
+            ledger: Box::new(ledger.clone()),
+            pending_for: PENDING_FOR,
+        };
+
+        let sent = events::testing::run_handler(
+            handler,
+            vec![
+                redeem("5678", "Mandatory Crab Dance", None),
+                // Found by id, even with the title changed
+                redeem("1234", "Renamed", None),
+                redeem("9999", "Say Something", Some("hello chat")),
+                redeem("0000", "Nobody Mapped Me", None),
+                // Nothing typed in, so nothing to read out
+                redeem("8888", "Say Something", Some(" ")),
+                Event::RedemptionOutcome(RedemptionOutcome {
+                    redemption_id: "redemption-9999".to_string(),
+                    error: None,
+                }),
+                // Still waiting when we shut down
+                redeem("7777", "Say Something", Some("bye chat")),
+            ],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            obs.calls(),
+            vec![OBSCall::SetCurrentScene {
+                scene: "PC - Crab Rave".to_string(),
+            }]
+        );
+        assert_eq!(audio.played(), vec!["./MP3s/bits.mp3".to_string()]);
+        assert!(matches!(
+            &sent[..],
+            [
+                Event::ObsSetScene { scene },
+                Event::UberDuckRequest(request),
+                Event::UberDuckRequest(_),
+            ] if scene == "PC - Crab Rave"
+                && request.voice == "Randall"
+                && request.voice_text == "hello chat"
+                && request.redemption_id.as_deref() == Some("redemption-9999")
+        ));
+
+        let refund = RedemptionResult::Refunded("nothing to read out".into());
+        let shut_down =
+            RedemptionResult::Refunded("shut down before it was done".into());
+        assert_eq!(
+            ledger.finished(),
+            vec![
+                ("redemption-5678".to_string(), RedemptionResult::Fulfilled),
+                ("redemption-1234".to_string(), RedemptionResult::Fulfilled),
+                ("redemption-0000".to_string(), RedemptionResult::Unmapped),
+                ("redemption-8888".to_string(), refund),
+                ("redemption-9999".to_string(), RedemptionResult::Fulfilled),
+                ("redemption-7777".to_string(), shut_down),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn redemptions_without_an_outcome_are_refunded() {
+        let mut routes = RewardRoutes::default();
+        routes.add(
+            "Say Something",
+            RewardAction::Speak {
+                voice: "Randall".to_string(),
+            },
+        );
+
+        let ledger = FakeLedger::default();
+        let handler = RewardHandler {
+            obs_client: Box::new(FakeOBS::default()),
+            sink: Box::new(FakeAudio::default()),
+            routes,
+            ledger: Box::new(ledger.clone()),
+            pending_for: Duration::ZERO,
+        };
+
+        events::testing::run_handler(
+            handler,
+            vec![
+                redeem("9999", "Say Something", Some("hello chat")),
+                // Too late, it was already refunded
+                Event::RedemptionOutcome(RedemptionOutcome {
+                    redemption_id: "redemption-9999".to_string(),
+                    error: None,
+                }),
+            ],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            ledger.finished(),
+            vec![(
+                "redemption-9999".to_string(),
+                RedemptionResult::Refunded("timed out".to_string())
+            )]
+        );
+    }
+}
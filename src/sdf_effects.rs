@@ -1,6 +1,6 @@
 use crate::obs;
+use crate::obs::OBSOperations;
 use anyhow::Result;
-use obws::Client as OBSClient;
 use serde::{Deserialize, Serialize};
 
 // TODO: consider serde defaults???
@@ -106,10 +106,12 @@ pub struct SDFEffectsSettings {
 
 // This just fetches settings around SDF Effects
 // AND NOTHING ELSE!!!
-pub async fn outline(source: &str, obs_client: &OBSClient) -> Result<()> {
+pub async fn outline(
+    source: &str,
+    obs_client: &dyn OBSOperations,
+) -> Result<()> {
     let filter_details = match obs_client
-        .filters()
-        .get(source, obs::SDF_EFFECTS_FILTER_NAME)
+        .get_filter(source, obs::SDF_EFFECTS_FILTER_NAME)
         .await
     {
         Ok(val) => val,
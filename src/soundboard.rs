@@ -0,0 +1,169 @@
+//! Reads chat out loud in each viewer's voice, and plays the sound effect
+//! for every word that has one in ./MP3s.
+
+use crate::audio::AudioOutput;
+use crate::twitch_stream_state;
+use crate::uberduck;
+use anyhow::Result;
+use async_trait::async_trait;
+use events::{EventHandler, Subscription};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::{thread, time};
+use subd_types::{Event, TransformOBSTextRequest, UberDuckRequest};
+use tokio::sync::broadcast;
+
+pub struct SoundHandler {
+    pub sink: Box<dyn AudioOutput>,
+    pub pool: sqlx::PgPool,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct Character {
+    pub voice: Option<String>,
+    pub source: Option<String>,
+}
+
+// Looks through raw-text to either play TTS or play soundeffects
+#[async_trait]
+impl EventHandler for SoundHandler {
+    fn subscription(&self) -> Subscription {
+        events::only!(UserMessage)
+    }
+
+    async fn handle(
+        self: Box<Self>,
+        tx: broadcast::Sender<Event>,
+        mut rx: broadcast::Receiver<Event>,
+    ) -> Result<()> {
+        let paths = fs::read_dir("./MP3s").unwrap();
+        let mut mp3s: HashSet<String> = vec![].into_iter().collect();
+        for path in paths {
+            mp3s.insert(path.unwrap().path().display().to_string());
+        }
+
+        loop {
+            let event = events::recv(&mut rx).await?;
+            let msg = match event {
+                Event::UserMessage(msg) => {
+                    // TODO: Add a list here
+                    if msg.user_name == "Nightbot" {
+                        continue;
+                    }
+                    msg
+                }
+                Event::Shutdown => return Ok(()),
+                _ => continue,
+            };
+            let spoken_string = msg.contents.clone();
+            let voice_text = msg.contents.to_string();
+            let speech_bubble_text = uberduck::chop_text(spoken_string);
+
+            // Anything less than 3 words we don't use
+            let split = voice_text.split(" ");
+            let vec = split.collect::<Vec<&str>>();
+            if vec.len() < 2 {
+                continue;
+            };
+
+            let stream_character = uberduck::build_stream_character(
+                &self.pool,
+                &msg.channel,
+                &msg.user_name,
+            )
+            .await?;
+
+            let state =
+                twitch_stream_state::get_twitch_state(&self.pool, &msg.channel)
+                    .await?;
+
+            let mut character = Character {
+                ..Default::default()
+            };
+
+            // This is all about how to respond to messages from various
+            // types of users
+            if msg.roles.is_twitch_staff() {
+                character.voice =
+                    Some(crate::obs::TWITCH_STAFF_OBS_SOURCE.to_string());
+                character.source =
+                    Some(crate::obs::TWITCH_STAFF_VOICE.to_string());
+            } else if msg.roles.is_twitch_mod() {
+                character.voice =
+                    Some(crate::obs::TWITCH_MOD_DEFAULT_VOICE.to_string());
+            } else if msg.roles.is_twitch_sub() {
+                character.voice = Some(stream_character.voice.clone());
+            } else if !state.sub_only_tts {
+                // This is what everyone get's to speak with
+                // if we are allowing non-subs to speak
+                character.voice = Some(stream_character.voice.clone());
+            }
+
+            // If we have a voice assigned, then we fire off an UberDuck Request
+            match character.voice {
+                Some(voice) => {
+                    let _ = tx.send(Event::UberDuckRequest(UberDuckRequest {
+                        voice,
+                        message: speech_bubble_text,
+                        voice_text,
+                        username: msg.user_name,
+                        source: character.source,
+                        redemption_id: None,
+                    }));
+                }
+                None => {}
+            }
+
+            // If we have the implicit_soundeffects enabled
+            // we go past this!
+            if !state.implicit_soundeffects {
+                continue;
+            }
+
+            let splitmsg = msg
+                .contents
+                .split(" ")
+                .map(|s| s.to_string())
+                .collect::<Vec<String>>();
+
+            let text_source =
+                crate::obs::SOUNDBOARD_TEXT_SOURCE_NAME.to_string();
+
+            for word in splitmsg {
+                let sanitized_word = word.as_str().to_lowercase();
+                let full_name = format!("./MP3s/{}.mp3", sanitized_word);
+
+                if mp3s.contains(&full_name) {
+                    let _ = tx.send(Event::TransformOBSTextRequest(
+                        TransformOBSTextRequest {
+                            message: sanitized_word.clone(),
+                            text_source: text_source.to_string(),
+                        },
+                    ));
+
+                    self.sink.play_file(&full_name)?;
+
+                    // TODO: Look into using these!
+                    // self.sink.volume()
+                    // self.sink.set_volume()
+                    // self.sink.len()
+
+                    // We need this so we can allow to trigger the next word in OBS
+                    // TODO: We should abstract
+                    // and figure out a better way of determine the time
+                    let sleep_time = time::Duration::from_millis(100);
+                    thread::sleep(sleep_time);
+                }
+            }
+
+            // This clears the OBS Text
+            let _ = tx.send(Event::TransformOBSTextRequest(
+                TransformOBSTextRequest {
+                    message: "".to_string(),
+                    text_source: text_source.to_string(),
+                },
+            ));
+        }
+    }
+}
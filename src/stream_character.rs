@@ -1,7 +1,7 @@
 use crate::move_transition;
 use crate::move_transition_bootstrap;
+use crate::obs::OBSOperations;
 use anyhow::Result;
-use obws::Client as OBSClient;
 use sqlx::PgPool;
 use std::path::Path;
 use subd_macros::database_model;
@@ -12,6 +12,9 @@ pub mod user_stream_character_information {
 
     pub struct Model {
         pub username: String,
+
+        /// The channel the voice is used in, empty for every channel.
+        pub channel: String,
         pub obs_character: String,
         pub voice: String,
         pub random: bool,
@@ -27,15 +30,16 @@ impl user_stream_character_information::Model {
             Self,
             r#"
             INSERT INTO user_stream_character_information
-            (username, obs_character, voice)
-            VALUES ( $1, $2, $3 )
-            ON CONFLICT (username)
+            (username, channel, obs_character, voice)
+            VALUES ( $1, $2, $3, $4 )
+            ON CONFLICT (channel, username)
             DO UPDATE SET
-            obs_character = $2,
-            voice = $3
-            RETURNING username, obs_character, voice, random
+            obs_character = $3,
+            voice = $4
+            RETURNING username, channel, obs_character, voice, random
         "#,
             self.username,
+            self.channel,
             self.obs_character,
             self.voice
         )
@@ -44,14 +48,21 @@ impl user_stream_character_information::Model {
     }
 }
 
+/// The voice `username` picked in `channel`, or the one they use everywhere.
 pub async fn get_voice_from_username(
     pool: &PgPool,
+    channel: &str,
     username: &str,
 ) -> Result<String> {
     let res = sqlx::query!(
-        "SELECT voice FROM user_stream_character_information WHERE username = $1",
-        username
-    ).fetch_one(pool).await?;
+        r#"SELECT voice FROM user_stream_character_information
+           WHERE username = $1 AND (channel = $2 OR channel = '')
+           ORDER BY channel = '' LIMIT 1"#,
+        username,
+        channel
+    )
+    .fetch_one(pool)
+    .await?;
     Ok(res.voice)
 }
 
@@ -61,7 +72,7 @@ pub async fn get_voice_from_username(
 // This might not be the best place for this
 pub async fn create_new_obs_character(
     base_source: &str,
-    obs_client: &OBSClient,
+    obs_client: &dyn OBSOperations,
 ) -> Result<()> {
     let scene = "Characters";
 
@@ -86,14 +97,13 @@ pub async fn create_new_obs_character(
 
     // Figure out GIF VS Image
     let _ = obs_client
-        .inputs()
-        .create(obws::requests::inputs::Create {
+        .create_input(
             scene,
-            input: &base_source,
-            kind: "image_source",
-            settings: Some(image_source),
-            enabled: Some(true),
-        })
+            &base_source,
+            "image_source",
+            serde_json::to_value(&image_source)?,
+            Some(true),
+        )
         .await;
 
     // TODO: Fix this path
@@ -105,14 +115,13 @@ pub async fn create_new_obs_character(
     };
     let speech_source_name = format!("{}-speech_bubble", base_source);
     let _ = obs_client
-        .inputs()
-        .create(obws::requests::inputs::Create {
+        .create_input(
             scene,
-            input: &speech_source_name,
-            kind: "image_source",
-            settings: Some(speech_bubble),
-            enabled: Some(true),
-        })
+            &speech_source_name,
+            "image_source",
+            serde_json::to_value(&speech_bubble)?,
+            Some(true),
+        )
         .await;
 
     // let font_flags = obws::common::FontFlags{ }
@@ -143,14 +152,13 @@ pub async fn create_new_obs_character(
 
     let text_source_name = format!("{}-text", base_source);
     let _ = obs_client
-        .inputs()
-        .create(obws::requests::inputs::Create {
+        .create_input(
             scene,
-            input: &text_source_name,
-            kind: "text_ft2_source_v2",
-            settings: Some(text_settings),
-            enabled: Some(true),
-        })
+            &text_source_name,
+            "text_ft2_source_v2",
+            serde_json::to_value(&text_settings)?,
+            Some(true),
+        )
         .await;
 
     // ======================================================
@@ -164,13 +172,15 @@ pub async fn create_new_obs_character(
         value_type: 4,
         ..Default::default()
     };
-    let new_filter = obws::requests::filters::Create {
-        source: &text_source_name,
-        filter: &filter_name,
-        kind: "move_value_filter",
-        settings: Some(move_text_filter),
-    };
-    if let Err(err) = obs_client.filters().create(new_filter).await {
+    if let Err(err) = obs_client
+        .create_filter(
+            &text_source_name,
+            &filter_name,
+            "move_value_filter",
+            serde_json::to_value(&move_text_filter)?,
+        )
+        .await
+    {
         println!("Error Creating Filter: {filter_name} | {:?}", err);
     };
 
@@ -183,7 +193,7 @@ pub async fn create_new_obs_character(
         &base_source,
         &filter_name,
         file_path,
-        &obs_client,
+        obs_client,
     )
     .await;
 
@@ -196,7 +206,7 @@ pub async fn create_new_obs_character(
         &base_source,
         &filter_name,
         file_path,
-        &obs_client,
+        obs_client,
     )
     .await;
 
@@ -209,7 +219,7 @@ pub async fn create_new_obs_character(
         &text_source_name,
         &filter_name,
         file_path,
-        &obs_client,
+        obs_client,
     )
     .await;
 
@@ -222,7 +232,7 @@ pub async fn create_new_obs_character(
         &text_source_name,
         &filter_name,
         file_path,
-        &obs_client,
+        obs_client,
     )
     .await;
 
@@ -235,7 +245,7 @@ pub async fn create_new_obs_character(
         &speech_source_name,
         &filter_name,
         file_path,
-        &obs_client,
+        obs_client,
     )
     .await;
 
@@ -248,7 +258,7 @@ pub async fn create_new_obs_character(
         &speech_source_name,
         &filter_name,
         file_path,
-        &obs_client,
+        obs_client,
     )
     .await;
     Ok(())
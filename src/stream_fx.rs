@@ -1,8 +1,7 @@
 use crate::move_transition;
 use crate::obs;
+use crate::obs::{Filter, OBSOperations};
 use anyhow::Result;
-use obws::responses::filters::SourceFilter;
-use obws::Client as OBSClient;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -217,17 +216,18 @@ impl Default for StreamFXSettings {
 pub async fn default_ortho(
     source: &str,
     _duration: u32,
-    obs_client: &OBSClient,
+    obs_client: &dyn OBSOperations,
 ) -> Result<()> {
     let new_settings = move_transition::default_orthographic_settings();
 
-    let new_settings = obws::requests::filters::SetSettings {
-        source: &source,
-        filter: "3D_Orthographic",
-        settings: new_settings,
-        overlay: None,
-    };
-    obs_client.filters().set_settings(new_settings).await?;
+    obs_client
+        .set_filter_settings(
+            source,
+            "3D_Orthographic",
+            serde_json::to_value(&new_settings)?,
+            None,
+        )
+        .await?;
 
     Ok(())
 }
@@ -238,13 +238,13 @@ pub async fn trigger_ortho(
     filter_setting_name: &str,
     filter_value: f32,
     duration: u32,
-    obs_client: &OBSClient,
+    obs_client: &dyn OBSOperations,
 ) -> Result<()> {
     let move_transition_filter_name = format!("Move_{}", filter_name);
 
-    let filter_details = obs_client.filters().get(&source, &filter_name).await;
+    let filter_details = obs_client.get_filter(source, filter_name).await;
 
-    let filt: SourceFilter = match filter_details {
+    let filt: Filter = match filter_details {
         Ok(val) => val,
         Err(_) => return Ok(()),
     };
@@ -260,13 +260,14 @@ pub async fn trigger_ortho(
             }
         };
 
-    let new_settings = obws::requests::filters::SetSettings {
-        source: &source,
-        filter: filter_name,
-        settings: new_settings,
-        overlay: None,
-    };
-    obs_client.filters().set_settings(new_settings).await?;
+    obs_client
+        .set_filter_settings(
+            source,
+            filter_name,
+            serde_json::to_value(&new_settings)?,
+            None,
+        )
+        .await?;
 
     _ = move_transition::update_and_trigger_move_value_filter(
         source,
@@ -275,7 +276,7 @@ pub async fn trigger_ortho(
         filter_value,
         duration,
         obs::SINGLE_SETTING_VALUE_TYPE,
-        &obs_client,
+        obs_client,
     )
     .await;
     Ok(())
@@ -0,0 +1,328 @@
+//! How many subs the broadcaster has, and how close that is to the sub goal.
+//!
+//! Anything can ask with `Event::RequestTwitchSubCount`, and gets back
+//! `Event::TwitchSubscriptionCount` and `Event::TwitchSubGoal`. Requests
+//! that come in together get one answer, and the count is cached until
+//! someone subs or it gets old. Set the goal with `!subgoal`.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use events::{EventHandler, Subscription};
+use sqlx::PgPool;
+use std::time::Duration;
+use subd_twitch::tokens::{TokenKind, TokenStore};
+use subd_types::{Event, SubGoal};
+use tokio::sync::broadcast;
+use tokio::time::Instant;
+use twitch_api2::HelixClient;
+
+/// How long to wait for more requests before answering.
+const DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// How long a count is good for, unless someone subs.
+const CACHE_FOR: Duration = Duration::from_secs(5 * 60);
+
+pub const DEFAULT_GOAL_TITLE: &str = "Sub goal";
+
+#[async_trait]
+pub trait SubCounter: Send + Sync {
+    async fn count(&self) -> Result<usize>;
+}
+
+/// Counts the broadcaster's subs on Helix.
+pub struct HelixSubCounter {
+    client: reqwest::Client,
+    tokens: TokenStore,
+}
+
+impl HelixSubCounter {
+    pub fn new(tokens: TokenStore) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            tokens,
+        }
+    }
+}
+
+#[async_trait]
+impl SubCounter for HelixSubCounter {
+    async fn count(&self) -> Result<usize> {
+        let token = self.tokens.user_token(TokenKind::Broadcaster).await?;
+        let helix: HelixClient<reqwest::Client> =
+            HelixClient::with_client(self.client.clone());
+        twitch_chat::get_twitch_sub_count(&helix, token).await
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Goal {
+    pub title: String,
+    pub target: usize,
+}
+
+/// The latest goal, if there is one.
+pub async fn load_goal(pool: &PgPool) -> Result<Option<Goal>> {
+    let row = sqlx::query!(
+        "SELECT title, target FROM twitch_sub_goals
+         ORDER BY created_at DESC LIMIT 1"
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.filter(|row| row.target > 0).map(|row| Goal {
+        title: row.title,
+        target: row.target as usize,
+    }))
+}
+
+pub async fn save_goal(
+    pool: &PgPool,
+    title: &str,
+    target: usize,
+    set_by: &str,
+) -> Result<()> {
+    sqlx::query!(
+        r#"INSERT INTO twitch_sub_goals (title, target, set_by)
+           VALUES ( $1, $2, $3 )"#,
+        title,
+        target as i32,
+        set_by,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub struct SubCountHandler {
+    counter: Box<dyn SubCounter>,
+    goal: Option<Goal>,
+    debounce: Duration,
+
+    /// The last count, and when we got it.
+    cached: Option<(Instant, usize)>,
+}
+
+impl SubCountHandler {
+    pub fn new(counter: Box<dyn SubCounter>, goal: Option<Goal>) -> Self {
+        Self {
+            counter,
+            goal,
+            debounce: DEBOUNCE,
+            cached: None,
+        }
+    }
+
+    /// How long to wait for more requests before answering.
+    pub fn debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    async fn answer(&mut self, tx: &broadcast::Sender<Event>) -> Result<()> {
+        let count = match self.cached {
+            Some((at, count)) if at.elapsed() < CACHE_FOR => count,
+            _ => match self.counter.count().await {
+                Ok(count) => {
+                    self.cached = Some((Instant::now(), count));
+                    count
+                }
+                Err(err) => {
+                    println!("Error counting subs: {:#}", err);
+                    return Ok(());
+                }
+            },
+        };
+
+        tx.send(Event::TwitchSubscriptionCount(count))?;
+        tx.send(Event::TwitchSubGoal(self.goal.as_ref().map(|goal| {
+            SubGoal {
+                title: goal.title.clone(),
+                target: goal.target,
+                current: count,
+            }
+        })))?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EventHandler for SubCountHandler {
+    fn subscription(&self) -> Subscription {
+        events::only!(
+            RequestTwitchSubCount,
+            TwitchSubscription,
+            SetTwitchSubGoal
+        )
+    }
+
+    async fn handle(
+        mut self: Box<Self>,
+        tx: broadcast::Sender<Event>,
+        mut rx: broadcast::Receiver<Event>,
+    ) -> Result<()> {
+        // When to answer the requests we've had so far
+        let mut due: Option<Instant> = None;
+
+        loop {
+            let event = tokio::select! {
+                biased;
+
+                _ = tokio::time::sleep_until(due.unwrap_or_else(Instant::now)),
+                    if due.is_some() =>
+                {
+                    due = None;
+                    self.answer(&tx).await?;
+                    continue;
+                }
+                event = events::recv(&mut rx) => event?,
+            };
+
+            match event {
+                Event::RequestTwitchSubCount => {}
+                Event::TwitchSubscription(_) => {
+                    // The count is off now, it gets asked for right after
+                    self.cached = None;
+                    continue;
+                }
+                Event::SetTwitchSubGoal { title, target } => {
+                    self.goal = (target > 0).then_some(Goal { title, target });
+                }
+                Event::Shutdown => return Ok(()),
+                _ => continue,
+            }
+
+            due.get_or_insert_with(|| Instant::now() + self.debounce);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct FakeCounter(Arc<Mutex<usize>>);
+
+    #[async_trait]
+    impl SubCounter for FakeCounter {
+        async fn count(&self) -> Result<usize> {
+            let mut asked = self.0.lock().unwrap();
+            *asked += 1;
+            Ok(40 + *asked)
+        }
+    }
+
+    fn new_sub() -> Event {
+        Event::TwitchSubscription(
+            serde_json::from_value(json!({
+                "subscription": {
+                    "context": "sub",
+                    "channel_id": "1",
+                    "channel_name": "beginbot",
+                    "user_id": "2",
+                    "user_name": "nyxkrage",
+                    "display_name": "NyxKrage",
+                    "time": "2023-01-20T12:00:00Z",
+                    "sub_plan": "1000",
+                    "sub_plan_name": "",
+                    "is_gift": false,
+                    "cumulative_months": 1,
+                    "streak_months": null,
+                    "months": 0,
+                    "benefit_end_month": 0,
+                    "multi_month_duration": 0,
+                    "sub_message": { "message": "", "emotes": [] },
+                },
+            }))
+            .unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn counts_are_cached_until_someone_subs() {
+        let counter = FakeCounter::default();
+        let handler = SubCountHandler::new(
+            Box::new(counter.clone()),
+            Some(Goal {
+                title: "Crab rave".to_string(),
+                target: 50,
+            }),
+        )
+        .debounce(Duration::ZERO);
+
+        let sent = events::testing::run_handler(
+            handler,
+            vec![
+                Event::RequestTwitchSubCount,
+                Event::RequestTwitchSubCount,
+                new_sub(),
+                Event::RequestTwitchSubCount,
+                Event::SetTwitchSubGoal {
+                    title: String::new(),
+                    target: 0,
+                },
+            ],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(*counter.0.lock().unwrap(), 2);
+
+        let answers: Vec<String> = sent
+            .iter()
+            .map(|event| match event {
+                Event::TwitchSubscriptionCount(count) => count.to_string(),
+                Event::TwitchSubGoal(Some(goal)) => {
+                    format!("{} {}/{}", goal.title, goal.current, goal.target)
+                }
+                Event::TwitchSubGoal(None) => "no goal".to_string(),
+                other => panic!("unexpected event {:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            answers,
+            vec![
+                "41",
+                "Crab rave 41/50",
+                "41",
+                "Crab rave 41/50",
+                "42",
+                "Crab rave 42/50",
+                "42",
+                "no goal",
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn requests_wait_for_the_debounce_and_share_one_answer() {
+        let counter = FakeCounter::default();
+        let handler = SubCountHandler::new(Box::new(counter.clone()), None)
+            .debounce(Duration::from_secs(1));
+
+        let (tx, rx) = broadcast::channel(events::BUS_CAPACITY);
+        let mut bus = tx.subscribe();
+        let running = tokio::spawn(Box::new(handler).handle(tx.clone(), rx));
+
+        for _ in 0..3 {
+            tx.send(Event::RequestTwitchSubCount).unwrap();
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        tx.send(Event::Shutdown).unwrap();
+        running.await.unwrap().unwrap();
+
+        let mut counts = vec![];
+        while let Ok(event) = bus.try_recv() {
+            if let Event::TwitchSubscriptionCount(count) = event {
+                counts.push(count);
+            }
+        }
+        assert_eq!(counts, vec![41]);
+        assert_eq!(*counter.0.lock().unwrap(), 1);
+    }
+}
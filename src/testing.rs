@@ -0,0 +1,613 @@
+//! In-memory stand-ins for OBS, audio and the database, so handlers can be
+//! tested without OBS running, speakers attached or Postgres up.
+//!
+//! ```ignore
+//! let obs = FakeOBS::default();
+//! let handler = OBSMessageHandler {
+//!     obs_client: Box::new(obs.clone()),
+//!     store: Box::new(FakeCommandStore::default()),
+//!     ..
+//! };
+//! events::testing::run_handler(handler, vec![..]).await?;
+//! assert_eq!(obs.calls(), vec![..]);
+//! ```
+
+use crate::audio::AudioOutput;
+use crate::cheers::CheerTotal;
+use crate::commands::registry::Permission;
+use crate::cooldowns::Limit;
+use crate::obs::{Filter, OBSOperations, SceneItem};
+use crate::obs_routing::CommandStore;
+use crate::rewards::{RedemptionLedger, RedemptionResult};
+use crate::sub_count::SubCounter;
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use obws::requests::hotkeys::KeyModifiers;
+use obws::requests::scene_items::SceneItemTransform;
+use obws::responses::scene_items::SceneItemTransform as SceneItemTransformInfo;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use subd_types::{UserID, UserMessage, UserPlatform, UserRoles};
+use twitch_api2::pubsub::channel_points::Redemption;
+
+/// One request made to `FakeOBS`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OBSCall {
+    ListFilters {
+        source: String,
+    },
+    GetFilter {
+        source: String,
+        filter: String,
+    },
+    CreateFilter {
+        source: String,
+        filter: String,
+        kind: String,
+        settings: Value,
+    },
+    RemoveFilter {
+        source: String,
+        filter: String,
+    },
+    SetFilterSettings {
+        source: String,
+        filter: String,
+        settings: Value,
+    },
+    SetFilterEnabled {
+        source: String,
+        filter: String,
+        enabled: bool,
+    },
+    FindSceneItem {
+        scene: String,
+        source: String,
+    },
+    ListSceneItems {
+        scene: String,
+    },
+    CreateSceneItem {
+        scene: String,
+        source: String,
+    },
+    GetSceneItemTransform {
+        scene: String,
+        item_id: i64,
+    },
+    SetSceneItemTransform {
+        scene: String,
+        item_id: i64,
+        transform: Value,
+    },
+    SetSceneItemEnabled {
+        scene: String,
+        item_id: i64,
+        enabled: bool,
+    },
+    SetCurrentScene {
+        scene: String,
+    },
+    CreateInput {
+        scene: String,
+        input: String,
+        kind: String,
+    },
+    TriggerHotkey {
+        key: String,
+    },
+}
+
+#[derive(Default)]
+struct OBSState {
+    calls: Vec<OBSCall>,
+    disconnected: bool,
+    // (source, filter) -> settings
+    filters: HashMap<(String, String), Value>,
+    // (scene, source) -> item id
+    scene_items: HashMap<(String, String), i64>,
+    // (scene, item id) -> transform, as OBS would report it
+    transforms: HashMap<(String, i64), Value>,
+}
+
+/// Records every request instead of talking to OBS.
+///
+/// Filters that were never set up come back with empty settings, so commands
+/// fall back to their defaults the same way they do against a fresh OBS.
+/// Sources have to be added to a scene with `with_scene_item` before they
+/// can be found.
+/// Clones share their state, keep one around to inspect after handing the
+/// other to a handler.
+#[derive(Clone, Default)]
+pub struct FakeOBS {
+    state: Arc<Mutex<OBSState>>,
+}
+
+impl FakeOBS {
+    /// Every request made so far, oldest first.
+    pub fn calls(&self) -> Vec<OBSCall> {
+        self.state.lock().unwrap().calls.clone()
+    }
+
+    /// Fails every request, like OBS while it's closed.
+    pub fn disconnected(self) -> Self {
+        self.state.lock().unwrap().disconnected = true;
+        self
+    }
+
+    pub fn with_filter(
+        self,
+        source: &str,
+        filter: &str,
+        settings: Value,
+    ) -> Self {
+        self.state
+            .lock()
+            .unwrap()
+            .filters
+            .insert((source.to_string(), filter.to_string()), settings);
+        self
+    }
+
+    pub fn with_scene_item(self, scene: &str, source: &str, id: i64) -> Self {
+        self.state
+            .lock()
+            .unwrap()
+            .scene_items
+            .insert((scene.to_string(), source.to_string()), id);
+        self
+    }
+
+    /// `transform` is the JSON OBS responds to GetSceneItemTransform with.
+    pub fn with_transform(
+        self,
+        scene: &str,
+        id: i64,
+        transform: Value,
+    ) -> Self {
+        self.state
+            .lock()
+            .unwrap()
+            .transforms
+            .insert((scene.to_string(), id), transform);
+        self
+    }
+
+    fn record(
+        &self,
+        call: OBSCall,
+    ) -> Result<std::sync::MutexGuard<'_, OBSState>> {
+        let mut state = self.state.lock().unwrap();
+        state.calls.push(call);
+        match state.disconnected {
+            true => Err(anyhow!("OBS isn't connected")),
+            false => Ok(state),
+        }
+    }
+}
+
+#[async_trait]
+impl OBSOperations for FakeOBS {
+    async fn list_filters(&self, source: &str) -> Result<Vec<Filter>> {
+        let state = self.record(OBSCall::ListFilters {
+            source: source.to_string(),
+        })?;
+
+        Ok(state
+            .filters
+            .iter()
+            .filter(|((s, _), _)| s == source)
+            .map(|((_, name), settings)| Filter {
+                name: name.clone(),
+                settings: settings.clone(),
+            })
+            .collect())
+    }
+
+    async fn get_filter(&self, source: &str, filter: &str) -> Result<Filter> {
+        let state = self.record(OBSCall::GetFilter {
+            source: source.to_string(),
+            filter: filter.to_string(),
+        })?;
+
+        let settings = state
+            .filters
+            .get(&(source.to_string(), filter.to_string()))
+            .cloned()
+            .unwrap_or_else(|| json!({}));
+        Ok(Filter {
+            name: filter.to_string(),
+            settings,
+        })
+    }
+
+    async fn create_filter(
+        &self,
+        source: &str,
+        filter: &str,
+        kind: &str,
+        settings: Value,
+    ) -> Result<()> {
+        let mut state = self.record(OBSCall::CreateFilter {
+            source: source.to_string(),
+            filter: filter.to_string(),
+            kind: kind.to_string(),
+            settings: settings.clone(),
+        })?;
+
+        state
+            .filters
+            .insert((source.to_string(), filter.to_string()), settings);
+        Ok(())
+    }
+
+    async fn remove_filter(&self, source: &str, filter: &str) -> Result<()> {
+        let mut state = self.record(OBSCall::RemoveFilter {
+            source: source.to_string(),
+            filter: filter.to_string(),
+        })?;
+
+        state
+            .filters
+            .remove(&(source.to_string(), filter.to_string()));
+        Ok(())
+    }
+
+    async fn set_filter_settings(
+        &self,
+        source: &str,
+        filter: &str,
+        settings: Value,
+        _overlay: Option<bool>,
+    ) -> Result<()> {
+        let mut state = self.record(OBSCall::SetFilterSettings {
+            source: source.to_string(),
+            filter: filter.to_string(),
+            settings: settings.clone(),
+        })?;
+
+        state
+            .filters
+            .insert((source.to_string(), filter.to_string()), settings);
+        Ok(())
+    }
+
+    async fn set_filter_enabled(
+        &self,
+        source: &str,
+        filter: &str,
+        enabled: bool,
+    ) -> Result<()> {
+        self.record(OBSCall::SetFilterEnabled {
+            source: source.to_string(),
+            filter: filter.to_string(),
+            enabled,
+        })?;
+        Ok(())
+    }
+
+    async fn find_scene_item(
+        &self,
+        scene: &str,
+        source: &str,
+    ) -> Result<Option<i64>> {
+        let state = self.record(OBSCall::FindSceneItem {
+            scene: scene.to_string(),
+            source: source.to_string(),
+        })?;
+
+        Ok(state
+            .scene_items
+            .get(&(scene.to_string(), source.to_string()))
+            .copied())
+    }
+
+    async fn list_scene_items(&self, scene: &str) -> Result<Vec<SceneItem>> {
+        let state = self.record(OBSCall::ListSceneItems {
+            scene: scene.to_string(),
+        })?;
+
+        Ok(state
+            .scene_items
+            .keys()
+            .filter(|(s, _)| s == scene)
+            .map(|(_, source)| SceneItem {
+                source_name: source.clone(),
+            })
+            .collect())
+    }
+
+    async fn create_scene_item(
+        &self,
+        scene: &str,
+        source: &str,
+        _enabled: Option<bool>,
+    ) -> Result<()> {
+        let mut state = self.record(OBSCall::CreateSceneItem {
+            scene: scene.to_string(),
+            source: source.to_string(),
+        })?;
+
+        let id = state.scene_items.len() as i64 + 1;
+        state
+            .scene_items
+            .insert((scene.to_string(), source.to_string()), id);
+        Ok(())
+    }
+
+    async fn scene_item_transform(
+        &self,
+        scene: &str,
+        item_id: i64,
+    ) -> Result<SceneItemTransformInfo> {
+        let state = self.record(OBSCall::GetSceneItemTransform {
+            scene: scene.to_string(),
+            item_id,
+        })?;
+
+        let transform = state
+            .transforms
+            .get(&(scene.to_string(), item_id))
+            .cloned()
+            .ok_or_else(|| {
+                anyhow!("no transform for item {} in {}", item_id, scene)
+            })?;
+        Ok(serde_json::from_value(transform)?)
+    }
+
+    async fn set_scene_item_transform(
+        &self,
+        scene: &str,
+        item_id: i64,
+        transform: SceneItemTransform,
+    ) -> Result<()> {
+        self.record(OBSCall::SetSceneItemTransform {
+            scene: scene.to_string(),
+            item_id,
+            transform: serde_json::to_value(&transform)?,
+        })?;
+        Ok(())
+    }
+
+    async fn set_scene_item_enabled(
+        &self,
+        scene: &str,
+        item_id: i64,
+        enabled: bool,
+    ) -> Result<()> {
+        self.record(OBSCall::SetSceneItemEnabled {
+            scene: scene.to_string(),
+            item_id,
+            enabled,
+        })?;
+        Ok(())
+    }
+
+    async fn set_current_scene(&self, scene: &str) -> Result<()> {
+        self.record(OBSCall::SetCurrentScene {
+            scene: scene.to_string(),
+        })?;
+        Ok(())
+    }
+
+    async fn create_input(
+        &self,
+        scene: &str,
+        input: &str,
+        kind: &str,
+        _settings: Value,
+        _enabled: Option<bool>,
+    ) -> Result<()> {
+        self.record(OBSCall::CreateInput {
+            scene: scene.to_string(),
+            input: input.to_string(),
+            kind: kind.to_string(),
+        })?;
+        Ok(())
+    }
+
+    async fn trigger_hotkey(
+        &self,
+        key: &str,
+        _modifiers: KeyModifiers,
+    ) -> Result<()> {
+        self.record(OBSCall::TriggerHotkey {
+            key: key.to_string(),
+        })?;
+        Ok(())
+    }
+}
+
+/// Remembers what it was asked to play, and returns straight away.
+#[derive(Clone, Default)]
+pub struct FakeAudio {
+    played: Arc<Mutex<Vec<String>>>,
+}
+
+impl FakeAudio {
+    pub fn played(&self) -> Vec<String> {
+        self.played.lock().unwrap().clone()
+    }
+}
+
+impl AudioOutput for FakeAudio {
+    fn play_file(&self, path: &str) -> Result<()> {
+        self.played.lock().unwrap().push(path.to_string());
+        Ok(())
+    }
+}
+
+/// Remembers how every redemption ended up, instead of telling Twitch.
+#[derive(Clone, Default)]
+pub struct FakeLedger {
+    finished: Arc<Mutex<Vec<(String, RedemptionResult)>>>,
+}
+
+impl FakeLedger {
+    /// Redemption ids and results, in the order they were finished.
+    pub fn finished(&self) -> Vec<(String, RedemptionResult)> {
+        self.finished.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl RedemptionLedger for FakeLedger {
+    async fn finish(
+        &self,
+        redemption: &Redemption,
+        result: &RedemptionResult,
+    ) -> Result<()> {
+        self.finished
+            .lock()
+            .unwrap()
+            .push((redemption.id.to_string(), result.clone()));
+        Ok(())
+    }
+}
+
+/// Always has the same number of subs.
+#[derive(Clone, Default)]
+pub struct FakeSubCounter(pub usize);
+
+#[async_trait]
+impl SubCounter for FakeSubCounter {
+    async fn count(&self) -> Result<usize> {
+        Ok(self.0)
+    }
+}
+
+/// Keeps sub goals in memory instead of Postgres, and forgets everything else
+/// commands save. A `down` store fails every query, like a database that
+/// can't be reached.
+#[derive(Clone, Default)]
+pub struct FakeCommandStore {
+    down: bool,
+    goals: Arc<Mutex<Vec<(String, usize)>>>,
+}
+
+impl FakeCommandStore {
+    pub fn down() -> Self {
+        Self {
+            down: true,
+            ..Self::default()
+        }
+    }
+
+    /// Every sub goal saved so far, as (title, target).
+    pub fn goals(&self) -> Vec<(String, usize)> {
+        self.goals.lock().unwrap().clone()
+    }
+
+    fn query(&self) -> Result<()> {
+        match self.down {
+            true => Err(anyhow!("the database is down")),
+            false => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl CommandStore for FakeCommandStore {
+    async fn save_limit(&self, _command: &str, _limit: Limit) -> Result<()> {
+        self.query()
+    }
+
+    async fn save_burst(
+        &self,
+        _permission: Permission,
+        _burst: u32,
+    ) -> Result<()> {
+        self.query()
+    }
+
+    async fn save_grant(
+        &self,
+        _channel: &str,
+        _user: &str,
+        _command: &str,
+        _granted_by: &str,
+    ) -> Result<()> {
+        self.query()
+    }
+
+    async fn delete_grant(
+        &self,
+        _channel: &str,
+        _user: &str,
+        _command: &str,
+    ) -> Result<()> {
+        self.query()
+    }
+
+    async fn record_refusal(
+        &self,
+        _channel: &str,
+        _user: &str,
+        _command: &str,
+        _required: Permission,
+    ) -> Result<()> {
+        self.query()
+    }
+
+    async fn set_implicit_soundeffects(
+        &self,
+        _channel: &str,
+        _soundeffects: bool,
+    ) -> Result<()> {
+        self.query()
+    }
+
+    async fn save_goal(
+        &self,
+        title: &str,
+        target: usize,
+        _set_by: &str,
+    ) -> Result<()> {
+        self.query()?;
+        self.goals.lock().unwrap().push((title.to_string(), target));
+        Ok(())
+    }
+
+    async fn cheer_total(
+        &self,
+        _channel: &str,
+        _user_name: &str,
+    ) -> Result<i64> {
+        self.query()?;
+        Ok(0)
+    }
+
+    async fn cheer_leaderboard(
+        &self,
+        _channel: &str,
+        _limit: i64,
+    ) -> Result<Vec<CheerTotal>> {
+        self.query()?;
+        Ok(vec![])
+    }
+
+    async fn set_voice(
+        &self,
+        _voice: String,
+        _username: String,
+        _channel: &str,
+    ) -> Result<()> {
+        self.query()
+    }
+}
+
+/// A chat message from `user_name` in beginbot's channel, with no roles.
+pub fn user_message(user_name: &str, contents: &str) -> UserMessage {
+    UserMessage {
+        user_id: UserID(uuid::Uuid::new_v4()),
+        user_name: user_name.to_string(),
+        user_login: Some(user_name.to_lowercase()),
+        roles: UserRoles::default(),
+        platform: UserPlatform::Twitch,
+        contents: contents.to_string(),
+        channel: "beginbot".to_string(),
+        message_id: Some(uuid::Uuid::new_v4().to_string()),
+        platform_user_id: Some(format!("{}-id", user_name.to_lowercase())),
+    }
+}
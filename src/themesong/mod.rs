@@ -321,13 +321,17 @@ impl ThemesongPlayer {
 
 #[async_trait]
 impl events::EventHandler for ThemesongPlayer {
+    fn subscription(&self) -> events::Subscription {
+        events::only!(ThemesongPlay, UserMessage)
+    }
+
     async fn handle(
         mut self: Box<Self>,
         tx: broadcast::Sender<Event>,
         mut rx: broadcast::Receiver<Event>,
     ) -> Result<()> {
         loop {
-            let event = rx.recv().await?;
+            let event = events::recv(&mut rx).await?;
             match event {
                 Event::ThemesongPlay(ThemesongPlay::Start {
                     user_id, ..
@@ -356,6 +360,8 @@ impl events::EventHandler for ThemesongPlayer {
                     }
                 }
 
+                Event::Shutdown => return Ok(()),
+
                 _ => continue,
             };
         }
@@ -371,17 +377,22 @@ pub struct ThemesongDownloader {
 
 #[async_trait]
 impl events::EventHandler for ThemesongDownloader {
+    fn subscription(&self) -> events::Subscription {
+        events::only!(ThemesongDownload)
+    }
+
     async fn handle(
         mut self: Box<Self>,
         tx: broadcast::Sender<Event>,
         mut rx: broadcast::Receiver<Event>,
     ) -> Result<()> {
         loop {
-            let event = rx.recv().await?;
+            let event = events::recv(&mut rx).await?;
             let msg = match event {
                 Event::ThemesongDownload(ThemesongDownload::Request {
                     msg,
                 }) => msg,
+                Event::Shutdown => return Ok(()),
                 _ => continue,
             };
 
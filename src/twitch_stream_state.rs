@@ -7,6 +7,9 @@ pub mod twitch_stream_state {
     use super::*;
 
     pub struct Model {
+        /// The channel it's for, empty for the default of every channel
+        /// without its own.
+        pub channel: String,
         pub sub_only_tts: bool,
         pub explicit_soundeffects: bool,
         pub implicit_soundeffects: bool,
@@ -22,10 +25,12 @@ impl twitch_stream_state::Model {
             Self,
             r#"
             INSERT INTO twitch_stream_state
-            (sub_only_tts, explicit_soundeffects, implicit_soundeffects)
-            VALUES ( $1, $2, $3 )
-            RETURNING sub_only_tts, explicit_soundeffects, implicit_soundeffects
+            (channel, sub_only_tts, explicit_soundeffects, implicit_soundeffects)
+            VALUES ( $1, $2, $3, $4 )
+            RETURNING channel, sub_only_tts, explicit_soundeffects,
+              implicit_soundeffects
         "#,
+            self.channel,
             true,
             true,
             true,
@@ -35,11 +40,15 @@ impl twitch_stream_state::Model {
     }
 }
 pub async fn update_implicit_soundeffects(
+    channel: &str,
     soundeffects: bool,
     pool: &PgPool,
 ) -> Result<()> {
     let _res = sqlx::query!(
-        "UPDATE twitch_stream_state SET implicit_soundeffects = $1",
+        r#"INSERT INTO twitch_stream_state (channel, implicit_soundeffects)
+           VALUES ( $1, $2 )
+           ON CONFLICT (channel) DO UPDATE SET implicit_soundeffects = $2"#,
+        channel,
         soundeffects
     )
     .execute(pool)
@@ -48,16 +57,33 @@ pub async fn update_implicit_soundeffects(
     Ok(())
 }
 
+/// The state of `channel`, or the default state if it has none of its own.
 pub async fn get_twitch_state(
     pool: &PgPool,
+    channel: &str,
 ) -> Result<twitch_stream_state::Model> {
-    let res = sqlx::query!("SELECT * FROM twitch_stream_state")
-        .fetch_one(pool)
-        .await?;
-    let model = twitch_stream_state::Model {
-        sub_only_tts: res.sub_only_tts,
-        explicit_soundeffects: res.explicit_soundeffects,
-        implicit_soundeffects: res.implicit_soundeffects,
+    let res = sqlx::query!(
+        r#"SELECT * FROM twitch_stream_state
+           WHERE channel = $1 OR channel = ''
+           ORDER BY channel = '' LIMIT 1"#,
+        channel
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    let model = match res {
+        Some(res) => twitch_stream_state::Model {
+            channel: res.channel,
+            sub_only_tts: res.sub_only_tts,
+            explicit_soundeffects: res.explicit_soundeffects,
+            implicit_soundeffects: res.implicit_soundeffects,
+        },
+        None => twitch_stream_state::Model {
+            channel: String::new(),
+            sub_only_tts: false,
+            explicit_soundeffects: false,
+            implicit_soundeffects: false,
+        },
     };
     Ok(model)
 }
@@ -0,0 +1,96 @@
+//! Keeps the Twitch tokens fresh while the bot runs, so nothing starts
+//! failing hours into a stream.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use events::{EventHandler, Subscription};
+use std::time::Duration;
+use subd_twitch::tokens::{TokenKind, TokenStore};
+use subd_types::Event;
+use tokio::sync::broadcast;
+
+/// Twitch wants tokens validated once an hour.
+const VALIDATE_EVERY: Duration = Duration::from_secs(60 * 60);
+
+pub struct TokenRefreshHandler {
+    pub tokens: TokenStore,
+
+    /// How often to check whether a token needs refreshing. Has to be well
+    /// under `subd_twitch::tokens::REFRESH_MARGIN`.
+    pub every: Duration,
+}
+
+impl TokenRefreshHandler {
+    pub fn new(tokens: TokenStore) -> Self {
+        Self {
+            tokens,
+            every: Duration::from_secs(5 * 60),
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for TokenRefreshHandler {
+    fn subscription(&self) -> Subscription {
+        // Nothing but Shutdown
+        Subscription::new(&[], |_| false)
+    }
+
+    async fn handle(
+        self: Box<Self>,
+        _: broadcast::Sender<Event>,
+        mut rx: broadcast::Receiver<Event>,
+    ) -> Result<()> {
+        let mut interval = tokio::time::interval(self.every);
+        let validate_every =
+            (VALIDATE_EVERY.as_secs() / self.every.as_secs().max(1)).max(1);
+
+        let mut ticks: u64 = 0;
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {},
+                event = events::recv(&mut rx) => match event? {
+                    Event::Shutdown => return Ok(()),
+                    _ => continue,
+                },
+            }
+
+            for kind in TokenKind::ALL {
+                let result = if ticks % validate_every == 0 {
+                    self.tokens.validate(kind).await.map(|_| ())
+                } else {
+                    self.tokens.user_token(kind).await.map(|_| ())
+                };
+
+                if let Err(err) = result {
+                    println!(
+                        "Error keeping the {} token fresh: {:#}",
+                        kind.as_str(),
+                        err
+                    );
+                }
+            }
+            ticks += 1;
+        }
+    }
+}
+
+/// Says which features won't work because a token is missing scopes, or
+/// isn't valid at all.
+pub async fn check_scopes(tokens: &TokenStore) {
+    for kind in TokenKind::ALL {
+        match tokens.validate(kind).await {
+            Ok(missing) => {
+                for feature in missing {
+                    println!(
+                        "{} won't work, the {} token is missing {}",
+                        feature.feature,
+                        kind.as_str(),
+                        feature.missing.join(", ")
+                    );
+                }
+            }
+            Err(err) => println!("{:#}", err),
+        }
+    }
+}
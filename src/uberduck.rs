@@ -1,9 +1,10 @@
+use crate::audio::AudioOutput;
 use crate::obs;
 use crate::stream_character;
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
-use events::EventHandler;
+use events::{EventHandler, Subscription};
 use rand::thread_rng;
 use rand::Rng;
 use rodio::*;
@@ -11,10 +12,10 @@ use serde::{Deserialize, Serialize};
 use std::env;
 use std::fs;
 use std::fs::File;
-use std::io::BufReader;
 use std::io::{BufWriter, Write};
 use std::{thread, time};
 use subd_types::Event;
+use subd_types::RedemptionOutcome;
 use subd_types::SourceVisibilityRequest;
 use subd_types::StreamCharacterRequest;
 use subd_types::TransformOBSTextRequest;
@@ -22,7 +23,7 @@ use subd_types::UberDuckRequest;
 use tokio::sync::broadcast;
 
 pub struct UberDuckHandler {
-    pub sink: Sink,
+    pub sink: Box<dyn AudioOutput>,
     pub pool: sqlx::PgPool,
 }
 
@@ -77,155 +78,177 @@ pub fn twitch_chat_filename(username: String, voice: String) -> String {
 
 #[async_trait]
 impl EventHandler for UberDuckHandler {
+    fn subscription(&self) -> Subscription {
+        events::only!(UberDuckRequest)
+    }
+
     async fn handle(
         self: Box<Self>,
         tx: broadcast::Sender<Event>,
         mut rx: broadcast::Receiver<Event>,
     ) -> Result<()> {
         loop {
-            let event = rx.recv().await?;
+            let event = events::recv(&mut rx).await?;
             let msg = match event {
                 Event::UberDuckRequest(msg) => msg,
+                Event::Shutdown => return Ok(()),
                 _ => continue,
             };
 
+            println!("We are trying for an Uberduck request: {}", msg.voice);
+
+            let redemption_id = msg.redemption_id.clone();
             let ch = msg.message.chars().next().unwrap();
-            if ch == '!' {
-                continue;
+            let result = if ch == '!' {
+                Err(anyhow!("commands aren't read out"))
+            } else {
+                self.speak(&tx, msg).await
             };
+            if let Err(err) = &result {
+                println!("Error with the Uberduck request: {:?}", err);
+            }
 
-            println!("We are trying for an Uberduck request: {}", msg.voice);
+            if let Some(redemption_id) = redemption_id {
+                tx.send(Event::RedemptionOutcome(RedemptionOutcome {
+                    redemption_id,
+                    error: result.err().map(|err| format!("{:#}", err)),
+                }))?;
+            }
+        }
+    }
+}
+
+impl UberDuckHandler {
+    /// Plays `msg` once Uberduck has made it, in the voice it asks for.
+    async fn speak(
+        &self,
+        tx: &broadcast::Sender<Event>,
+        msg: UberDuckRequest,
+    ) -> Result<()> {
+        // Clips play on our stream, so use the character from our channel
+        let channel = subd_types::consts::get_twitch_broadcaster_username();
+        let stream_character =
+            build_stream_character(&self.pool, &channel, &msg.username).await?;
+        println!("\n\tStream Character: {:?}\n", stream_character);
+
+        let source = match msg.source {
+            Some(source) => source,
+            None => stream_character.source.clone(),
+        };
 
-            let stream_character =
-                build_stream_character(&self.pool, &msg.username).await?;
-            println!("\n\tStream Character: {:?}\n", stream_character);
+        let (username, secret) = uberduck_creds();
+
+        let client = reqwest::Client::new();
+        let res = client
+            .post("https://api.uberduck.ai/speak")
+            .basic_auth(username.clone(), Some(secret.clone()))
+            .json(&[("speech", msg.voice_text), ("voice", msg.voice.clone())])
+            .send()
+            .await?
+            .json::<UberDuckVoiceResponse>()
+            .await?;
+
+        let uuid = match res.uuid {
+            Some(x) => x,
+            None => bail!("uberduck didn't return a uuid"),
+        };
 
-            let source = match msg.source {
-                Some(source) => source,
-                None => stream_character.source.clone(),
-            };
+        loop {
+            let url =
+                format!("https://api.uberduck.ai/speak-status?uuid={}", &uuid);
 
             let (username, secret) = uberduck_creds();
-
-            let client = reqwest::Client::new();
-            let res = client
-                .post("https://api.uberduck.ai/speak")
-                .basic_auth(username.clone(), Some(secret.clone()))
-                .json(&[
-                    ("speech", msg.voice_text),
-                    ("voice", msg.voice.clone()),
-                ])
+            let response = client
+                .get(url)
+                .basic_auth(username, Some(secret))
                 .send()
-                .await?
-                .json::<UberDuckVoiceResponse>()
                 .await?;
 
-            let uuid = match res.uuid {
-                Some(x) => x,
-                None => continue,
-            };
-
-            loop {
-                let url = format!(
-                    "https://api.uberduck.ai/speak-status?uuid={}",
-                    &uuid
-                );
-
-                let (username, secret) = uberduck_creds();
-                let response = client
-                    .get(url)
-                    .basic_auth(username, Some(secret))
-                    .send()
-                    .await?;
-
-                // Show Loading Duck
-                let _ = tx.send(Event::SourceVisibilityRequest(
-                    SourceVisibilityRequest {
-                        scene: "Characters".to_string(),
-                        source: "loading_duck".to_string(),
-                        enabled: true,
-                    },
-                ));
-
-                let text = response.text().await?;
-                // we need to this to be better
-                let file_resp: UberDuckFileResponse =
-                    serde_json::from_str(&text)?;
-                println!("Uberduck Finished at: {:?}", file_resp.finished_at);
-
-                match file_resp.path {
-                    Some(new_url) => {
-                        let _ = tx.send(Event::SourceVisibilityRequest(
-                            // TODO: Abstract these values out
-                            SourceVisibilityRequest {
-                                scene: "Characters".to_string(),
-                                source: "loading_duck".to_string(),
-                                enabled: false,
-                            },
-                        ));
-
-                        let text_source = format!("{}-text", source.clone());
-                        let _ = tx.send(Event::TransformOBSTextRequest(
-                            TransformOBSTextRequest {
-                                message: msg.message.clone(),
-                                text_source,
-                            },
-                        ));
-
-                        // So the filename is fucking up
-                        // it's not unique
-                        let filename =
-                            twitch_chat_filename(msg.username, msg.voice);
-                        let full_filename = format!("{}.wav", filename);
-
-                        // I WANT TO SAVE THIS FILE
-                        println!("Trying to Save: {}", full_filename);
-                        let local_path = format!(
-                            "./TwitchChatTTSRecordings/{}",
-                            full_filename
-                        );
-                        let response = client.get(new_url).send().await?;
-                        let file = File::create(local_path.clone())?;
-                        let mut writer = BufWriter::new(file);
-                        writer.write_all(&response.bytes().await?)?;
-                        println!("Downloaded File From Uberduck, Playing Soon: {:?}!", local_path);
-
-                        let _ = tx.send(Event::StreamCharacterRequest(
-                            StreamCharacterRequest {
-                                source: source.clone(),
-                                enabled: true,
-                            },
-                        ));
-
-                        // Hmm We shouldn't fail here then
-                        let file =
-                            BufReader::new(File::open(local_path).unwrap());
-                        self.sink.append(
-                            Decoder::new(BufReader::new(file)).unwrap(),
-                        );
-                        self.sink.sleep_until_end();
-
-                        // THIS IS HIDING THE PERSON AFTER
-                        // We might want to wait a little longer, then hide
-                        // we could also kick off a hide event
-                        let ten_millis = time::Duration::from_millis(1000);
-
-                        thread::sleep(ten_millis);
-
-                        let source = source.clone();
-                        let _ = tx.send(Event::StreamCharacterRequest(
-                            StreamCharacterRequest {
-                                source,
-                                enabled: false,
-                            },
-                        ));
-                        break;
-                    }
-                    None => {
-                        // Wait 1 second before seeing if the file is ready.
-                        let ten_millis = time::Duration::from_millis(1000);
-                        thread::sleep(ten_millis);
-                    }
+            // Show Loading Duck
+            let _ = tx.send(Event::SourceVisibilityRequest(
+                SourceVisibilityRequest {
+                    scene: "Characters".to_string(),
+                    source: "loading_duck".to_string(),
+                    enabled: true,
+                },
+            ));
+
+            let text = response.text().await?;
+            // we need to this to be better
+            let file_resp: UberDuckFileResponse = serde_json::from_str(&text)?;
+            println!("Uberduck Finished at: {:?}", file_resp.finished_at);
+
+            match file_resp.path {
+                Some(new_url) => {
+                    let _ = tx.send(Event::SourceVisibilityRequest(
+                        // TODO: Abstract these values out
+                        SourceVisibilityRequest {
+                            scene: "Characters".to_string(),
+                            source: "loading_duck".to_string(),
+                            enabled: false,
+                        },
+                    ));
+
+                    let text_source = format!("{}-text", source.clone());
+                    let _ = tx.send(Event::TransformOBSTextRequest(
+                        TransformOBSTextRequest {
+                            message: msg.message.clone(),
+                            text_source,
+                        },
+                    ));
+
+                    // So the filename is fucking up
+                    // it's not unique
+                    let filename =
+                        twitch_chat_filename(msg.username, msg.voice);
+                    let full_filename = format!("{}.wav", filename);
+
+                    // I WANT TO SAVE THIS FILE
+                    println!("Trying to Save: {}", full_filename);
+                    let local_path =
+                        format!("./TwitchChatTTSRecordings/{}", full_filename);
+                    let response = client.get(new_url).send().await?;
+                    let file = File::create(local_path.clone())?;
+                    let mut writer = BufWriter::new(file);
+                    writer.write_all(&response.bytes().await?)?;
+                    println!(
+                        "Downloaded File From Uberduck, Playing Soon: {:?}!",
+                        local_path
+                    );
+
+                    let _ = tx.send(Event::StreamCharacterRequest(
+                        StreamCharacterRequest {
+                            source: source.clone(),
+                            enabled: true,
+                        },
+                    ));
+
+                    // Hmm We shouldn't fail here then
+                    self.sink.play_file(&local_path)?;
+
+                    // THIS IS HIDING THE PERSON AFTER
+                    // We might want to wait a little longer, then hide
+                    // we could also kick off a hide event
+                    let ten_millis = time::Duration::from_millis(1000);
+
+                    thread::sleep(ten_millis);
+
+                    let source = source.clone();
+                    let _ = tx.send(Event::StreamCharacterRequest(
+                        StreamCharacterRequest {
+                            source,
+                            enabled: false,
+                        },
+                    ));
+                    return Ok(());
+                }
+                None if file_resp.failed_at.is_some() => {
+                    bail!("uberduck couldn't make the clip")
+                }
+                None => {
+                    // Wait 1 second before seeing if the file is ready.
+                    let ten_millis = time::Duration::from_millis(1000);
+                    thread::sleep(ten_millis);
                 }
             }
         }
@@ -266,10 +289,12 @@ fn find_obs_character(_voice: &str) -> &str {
 pub async fn set_voice(
     voice: String,
     username: String,
+    channel: &str,
     pool: &sqlx::PgPool,
 ) -> Result<()> {
     let model = stream_character::user_stream_character_information::Model {
         username: username.clone(),
+        channel: channel.to_string(),
         voice: voice.to_string(),
         obs_character: "Seal".to_string(),
         random: false,
@@ -303,6 +328,7 @@ pub async fn talk_in_voice(
         voice_text,
         username,
         source: None,
+        redemption_id: None,
     }));
     Ok(())
 }
@@ -336,28 +362,33 @@ pub async fn use_random_voice(
         voice_text,
         username,
         source: None,
+        redemption_id: None,
     }));
     Ok(())
 }
 
 pub async fn build_stream_character(
     pool: &sqlx::PgPool,
+    channel: &str,
     username: &str,
 ) -> Result<StreamCharacter> {
     // TODO: Abstract this out
     let default_voice = "arbys";
 
-    let voice =
-        match stream_character::get_voice_from_username(pool, username).await {
-            Ok(voice) => voice,
-            Err(_) => {
-                return Ok(StreamCharacter {
-                    username: username.to_string(),
-                    voice: default_voice.to_string(),
-                    source: obs::DEFAULT_STREAM_CHARACTER_SOURCE.to_string(),
-                })
-            }
-        };
+    let voice = match stream_character::get_voice_from_username(
+        pool, channel, username,
+    )
+    .await
+    {
+        Ok(voice) => voice,
+        Err(_) => {
+            return Ok(StreamCharacter {
+                username: username.to_string(),
+                voice: default_voice.to_string(),
+                source: obs::DEFAULT_STREAM_CHARACTER_SOURCE.to_string(),
+            })
+        }
+    };
 
     let character = find_obs_character(&voice);
 
@@ -1,6 +1,6 @@
 use anyhow::Result;
 use async_trait::async_trait;
-use events::EventHandler;
+use events::{EventHandler, Subscription};
 use subd_types::Event;
 use tokio::sync::broadcast;
 
@@ -8,14 +8,19 @@ pub struct UserMessageHandler {}
 
 #[async_trait]
 impl EventHandler for UserMessageHandler {
+    fn subscription(&self) -> Subscription {
+        events::only!(UserMessage)
+    }
+
     async fn handle(
         self: Box<Self>,
         _: broadcast::Sender<Event>,
         mut rx: broadcast::Receiver<Event>,
     ) -> Result<()> {
         loop {
-            let msg = match rx.recv().await? {
+            let msg = match events::recv(&mut rx).await? {
                 Event::UserMessage(msg) => msg,
+                Event::Shutdown => return Ok(()),
                 _ => continue,
             };
 